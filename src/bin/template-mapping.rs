@@ -0,0 +1,61 @@
+//! Write a starter `TemplateMapping` for a PDF form, ready for
+//! `TemplateMapping::load` once reviewed:
+//!
+//! ```text
+//! cargo run --bin template-mapping -- templates/new-form.pdf new-form.json
+//! ```
+//!
+//! Without an output path the mapping is printed instead.
+
+use std::process::ExitCode;
+
+use ufv_timesheet_util::helpers::pdf::inspect_template;
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let Some(template_path) = args.next() else {
+        eprintln!("Usage: template-mapping <template.pdf> [mapping.json]");
+        return ExitCode::FAILURE;
+    };
+    let output_path = args.next();
+
+    let report = match inspect_template(&template_path) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Failed to inspect {}: {}", template_path, e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let Some(mapping) = report.mapping else {
+        eprintln!("No field found for: {}", report.missing_columns.join(", "));
+        eprintln!("Columns repeated per row, to map by hand:");
+        for group in &report.row_groups {
+            eprintln!("  {} ({} rows)", group.column, group.fields.len());
+        }
+        return ExitCode::FAILURE;
+    };
+
+    let json = match serde_json::to_string_pretty(&mapping) {
+        Ok(json) => json,
+        Err(e) => {
+            eprintln!("Failed to serialize mapping: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match output_path {
+        Some(output_path) => {
+            if let Err(e) = std::fs::write(&output_path, json) {
+                eprintln!("Failed to write {}: {}", output_path, e);
+                return ExitCode::FAILURE;
+            }
+            println!(
+                "Wrote a mapping for {} fields to {}",
+                report.fields.len(),
+                output_path
+            );
+        }
+        None => println!("{}", json),
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::{
+    approval::{escape_html, with_preamble},
+    email::TimesheetEmail,
+    pdf::{TimesheetData, TimesheetEntry},
+};
+
+/// Stamped on amended PDFs and put in front of their subject
+pub const REVISED: &str = "REVISED";
+
+/// How a timesheet row differs from the submitted version
+#[derive(Clone, Debug, PartialEq)]
+pub enum RowChange {
+    Added(TimesheetEntry),
+    Removed(TimesheetEntry),
+    Changed {
+        before: TimesheetEntry,
+        after: TimesheetEntry,
+    },
+}
+
+/// Where an amendment sits in a period's history
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Revision {
+    /// 1 for the first amendment, 2 for the next, and so on
+    pub number: u32,
+    /// ID of the submission this one replaces
+    pub revises: String,
+    /// The changes as plain text, see [`changes_text`]
+    pub changes: String,
+}
+
+/// Compare submitted and current entries. Rows are matched on date and start
+/// time; unmatched rows on the same date are paired up as changes.
+pub fn diff(before: &TimesheetData, after: &TimesheetData) -> Vec<RowChange> {
+    let key = |entry: &TimesheetEntry| (entry.month, entry.day, entry.start.clone());
+    let mut removed: Vec<&TimesheetEntry> = Vec::new();
+    let mut changes = Vec::new();
+    let mut unmatched: Vec<&TimesheetEntry> = after.entries.iter().collect();
+
+    for old in &before.entries {
+        match unmatched.iter().position(|new| key(new) == key(old)) {
+            Some(index) => {
+                let new = unmatched.remove(index);
+                if new != old {
+                    changes.push(RowChange::Changed {
+                        before: old.clone(),
+                        after: new.clone(),
+                    });
+                }
+            }
+            None => removed.push(old),
+        }
+    }
+
+    for old in removed {
+        match unmatched
+            .iter()
+            .position(|new| (new.month, new.day) == (old.month, old.day))
+        {
+            Some(index) => changes.push(RowChange::Changed {
+                before: old.clone(),
+                after: unmatched.remove(index).clone(),
+            }),
+            None => changes.push(RowChange::Removed(old.clone())),
+        }
+    }
+    changes.extend(unmatched.into_iter().cloned().map(RowChange::Added));
+
+    changes.sort_by_key(|change| {
+        let entry = match change {
+            RowChange::Added(entry) | RowChange::Removed(entry) => entry,
+            RowChange::Changed { after, .. } => after,
+        };
+        (entry.month, entry.day, entry.start.clone())
+    });
+    changes
+}
+
+fn row(entry: &TimesheetEntry) -> String {
+    let mut row = format!(
+        "{}/{} {}-{}, {} h",
+        entry.month, entry.day, entry.start, entry.end, entry.paid_hours
+    );
+    if let Some(notes) = entry
+        .notes
+        .as_deref()
+        .filter(|notes| !notes.trim().is_empty())
+    {
+        row.push_str(&format!(" ({})", notes.trim()));
+    }
+    row
+}
+
+fn change_line(change: &RowChange) -> String {
+    match change {
+        RowChange::Added(entry) => format!("Added:   {}", row(entry)),
+        RowChange::Removed(entry) => format!("Removed: {}", row(entry)),
+        RowChange::Changed { before, after } => {
+            format!("Changed: {}\n    now: {}", row(before), row(after))
+        }
+    }
+}
+
+/// The changes as plain text, one row per line, with the old and new totals
+pub fn changes_text(
+    changes: &[RowChange],
+    before: &TimesheetData,
+    after: &TimesheetData,
+) -> String {
+    let mut text = changes
+        .iter()
+        .map(change_line)
+        .collect::<Vec<_>>()
+        .join("\n");
+    text.push_str(&format!(
+        "\nTotal:   {} h, was {} h",
+        after.total_hours, before.total_hours
+    ));
+    text
+}
+
+/// The amended timesheet email: `REVISED` in the subject and the changes
+/// above the usual body
+pub fn amended_email(timesheet_email: &TimesheetEmail, revision: &Revision) -> TimesheetEmail {
+    let intro = format!(
+        "This is revision {} of a timesheet that was already submitted. \
+         It replaces the earlier version. Changes:",
+        revision.number
+    );
+
+    with_preamble(
+        timesheet_email,
+        &format!("{}: {}", REVISED, timesheet_email.subject),
+        &format!("{}\n\n{}\n\n", intro, revision.changes),
+        &format!(
+            "<p><strong>{}</strong></p>\n<pre>{}</pre>\n<hr>\n",
+            escape_html(&intro),
+            escape_html(&revision.changes)
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(day: u32, start: &str, end: &str, paid_hours: f64) -> TimesheetEntry {
+        TimesheetEntry {
+            month: 10,
+            day,
+            start: start.to_string(),
+            end: end.to_string(),
+            paid_hours,
+            notes: None,
+        }
+    }
+
+    fn timesheet(entries: Vec<TimesheetEntry>) -> TimesheetData {
+        TimesheetData {
+            total_hours: entries.iter().map(|entry| entry.paid_hours).sum(),
+            entries,
+            employee: None,
+            period: None,
+        }
+    }
+
+    #[test]
+    fn unchanged_entries_have_no_changes() {
+        let entries = vec![
+            entry(10, "09:00", "12:00", 3.0),
+            entry(11, "13:00", "15:00", 2.0),
+        ];
+        assert_eq!(
+            diff(&timesheet(entries.clone()), &timesheet(entries)),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn matches_rows_on_date_and_start() {
+        let before = timesheet(vec![
+            entry(10, "09:00", "12:00", 3.0),
+            entry(11, "09:00", "12:00", 3.0),
+            entry(13, "09:00", "12:00", 3.0),
+        ]);
+        let after = timesheet(vec![
+            entry(20, "13:00", "15:00", 2.0),
+            entry(10, "09:00", "13:00", 4.0),
+            entry(11, "10:00", "12:00", 2.0),
+        ]);
+
+        assert_eq!(
+            diff(&before, &after),
+            vec![
+                RowChange::Changed {
+                    before: entry(10, "09:00", "12:00", 3.0),
+                    after: entry(10, "09:00", "13:00", 4.0),
+                },
+                // A new start time on the same date is still the same shift
+                RowChange::Changed {
+                    before: entry(11, "09:00", "12:00", 3.0),
+                    after: entry(11, "10:00", "12:00", 2.0),
+                },
+                RowChange::Removed(entry(13, "09:00", "12:00", 3.0)),
+                RowChange::Added(entry(20, "13:00", "15:00", 2.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn notes_count_as_changes() {
+        let before = timesheet(vec![entry(10, "09:00", "12:00", 3.0)]);
+        let mut noted = entry(10, "09:00", "12:00", 3.0);
+        noted.notes = Some("Covered the front desk".to_string());
+        let after = timesheet(vec![noted.clone()]);
+
+        let changes = diff(&before, &after);
+        assert_eq!(
+            changes,
+            vec![RowChange::Changed {
+                before: entry(10, "09:00", "12:00", 3.0),
+                after: noted,
+            }]
+        );
+        assert_eq!(
+            changes_text(&changes, &before, &after),
+            "Changed: 10/10 09:00-12:00, 3 h\n    now: 10/10 09:00-12:00, 3 h (Covered the front desk)\n\
+             Total:   3 h, was 3 h"
+        );
+    }
+}
@@ -0,0 +1,281 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::helpers::email::{RenderedTimesheet, TimesheetEmail};
+
+pub const APPROVE: &str = "approve";
+pub const REJECT: &str = "reject";
+
+/// Supervisor sign-off before a timesheet goes to payroll. With this set,
+/// the timesheet is first emailed to `supervisor` with approve and reject
+/// links, and only forwarded to the usual recipients once approved.
+#[derive(Clone, Debug)]
+pub struct ApprovalConfig {
+    /// Supervisor's address, e.g. `Sam Lee <sam.lee@ufv.ca>`
+    pub supervisor: String,
+    /// Public URL the service's router is mounted at, used to build links,
+    /// e.g. `https://example.com/timesheet`
+    pub base_url: String,
+    /// Key for signing approval links
+    pub secret: String,
+    /// How long approval links stay valid
+    pub link_ttl: Duration,
+    /// Who hears about rejections. When empty, the sender does.
+    pub notify_on_reject: Vec<String>,
+}
+
+impl ApprovalConfig {
+    /// Approval with links that stay valid for a week
+    pub fn new(
+        supervisor: impl Into<String>,
+        base_url: impl Into<String>,
+        secret: impl Into<String>,
+    ) -> Self {
+        Self {
+            supervisor: supervisor.into(),
+            base_url: base_url.into(),
+            secret: secret.into(),
+            link_ttl: Duration::days(7),
+            notify_on_reject: Vec::new(),
+        }
+    }
+
+    /// Link to the page where the supervisor confirms a decision
+    pub fn link(&self, token: &str) -> String {
+        format!(
+            "{}/timesheet-approval?token={}",
+            self.base_url.trim_end_matches('/'),
+            token
+        )
+    }
+}
+
+/// A rendered timesheet waiting for the supervisor
+#[derive(Clone, Debug)]
+pub struct PendingApproval {
+    pub timesheet: RenderedTimesheet,
+    /// Idempotency keys to record against the payroll email once approved
+    pub idempotency_keys: Vec<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// The email asking the supervisor to approve or reject
+pub fn request_email(
+    timesheet_email: &TimesheetEmail,
+    approve_url: &str,
+    reject_url: &str,
+    expires_at: DateTime<Utc>,
+) -> TimesheetEmail {
+    let expires = expires_at.format("%b %-d, %Y at %H:%M UTC");
+    let text = format!(
+        "This timesheet needs your approval before it goes to payroll.\n\n\
+         Approve: {}\n\
+         Reject: {}\n\n\
+         These links expire {}.\n\n",
+        approve_url, reject_url, expires
+    );
+    let html = format!(
+        "<p>This timesheet needs your approval before it goes to payroll.</p>\n\
+         <p><a href=\"{}\">Approve</a> &middot; <a href=\"{}\">Reject</a></p>\n\
+         <p><small>These links expire {}.</small></p>\n<hr>\n",
+        escape_html(approve_url),
+        escape_html(reject_url),
+        expires
+    );
+
+    with_preamble(
+        timesheet_email,
+        &format!("Approval needed: {}", timesheet_email.subject),
+        &text,
+        &html,
+    )
+}
+
+/// The payroll email, noting who approved it and when
+pub fn approved_email(
+    timesheet_email: &TimesheetEmail,
+    supervisor: &str,
+    approved_at: DateTime<Utc>,
+) -> TimesheetEmail {
+    let note = format!(
+        "Approved by {} on {}.",
+        supervisor,
+        approved_at.format("%b %-d, %Y at %H:%M UTC")
+    );
+
+    with_preamble(
+        timesheet_email,
+        &timesheet_email.subject,
+        &format!("{}\n\n", note),
+        &format!("<p><strong>{}</strong></p>\n", escape_html(&note)),
+    )
+}
+
+/// Text of the rejection notice sent to the employee
+pub fn rejection_text(subject: &str, supervisor: &str, comment: &str) -> String {
+    let comment = comment.trim();
+    format!(
+        "{} rejected \"{}\".\n\n{}",
+        supervisor,
+        subject,
+        if comment.is_empty() {
+            "No comment was given.".to_string()
+        } else {
+            format!("Comment:\n{}", comment)
+        }
+    )
+}
+
+/// Put extra text before both bodies, inside the HTML `<body>` if there is one
+pub(crate) fn with_preamble(
+    timesheet_email: &TimesheetEmail,
+    subject: &str,
+    text: &str,
+    html: &str,
+) -> TimesheetEmail {
+    let mut email = timesheet_email.clone();
+    email.subject = subject.to_string();
+    email.body.text = format!("{}{}", text, email.body.text);
+
+    let body_start = email.body.html.find("<body").and_then(|start| {
+        email.body.html[start..]
+            .find('>')
+            .map(|end| start + end + 1)
+    });
+    match body_start {
+        Some(index) => email.body.html.insert_str(index, &format!("\n{}", html)),
+        None => email.body.html.insert_str(0, html),
+    }
+    email
+}
+
+/// Page where the supervisor confirms a decision. Links only show this form,
+/// so mail scanners that follow links can't approve anything.
+pub fn confirm_page(token: &str, action: &str, subject: &str) -> String {
+    let comment = if action == REJECT {
+        "<p><label>Comment for the employee<br>\
+         <textarea name=\"comment\" rows=\"4\" cols=\"50\"></textarea></label></p>"
+    } else {
+        ""
+    };
+    let button = if action == REJECT {
+        "Reject timesheet"
+    } else {
+        "Approve timesheet"
+    };
+
+    page(
+        subject,
+        &format!(
+            "<form method=\"post\" action=\"timesheet-approval\">\
+             <input type=\"hidden\" name=\"token\" value=\"{}\">{}\
+             <button type=\"submit\">{}</button></form>",
+            escape_html(token),
+            comment,
+            button
+        ),
+    )
+}
+
+/// A minimal HTML page with a heading and some already-escaped content
+pub fn page(heading: &str, content: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{0}</title></head>\
+         <body style=\"font-family: Helvetica, Arial, sans-serif;\"><h1>{0}</h1>{1}</body></html>",
+        escape_html(heading),
+        content
+    )
+}
+
+pub fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::email::{EmailBody, EmailConfig};
+    use crate::helpers::pdf::{TimesheetData, TimesheetEntry};
+
+    fn timesheet_email(html: &str) -> TimesheetEmail {
+        let config =
+            EmailConfig::new("timesheets@example.com", vec!["payroll@ufv.ca".to_string()]);
+        let data = TimesheetData::try_from(Vec::<TimesheetEntry>::new()).unwrap();
+        let mut email = config.render(&data, "").unwrap();
+        email.body = EmailBody {
+            html: html.to_string(),
+            text: "Timesheet".to_string(),
+        };
+        email
+    }
+
+    #[test]
+    fn request_email_puts_both_links_before_the_timesheet() {
+        let email = request_email(
+            &timesheet_email("<body><p>Timesheet</p></body>"),
+            "https://example.com/timesheet-approval?token=a&b",
+            "https://example.com/timesheet-approval?token=c",
+            "2026-10-24T07:00:00Z".parse().unwrap(),
+        );
+
+        assert_eq!(email.subject, "Approval needed: Timesheet");
+        assert!(email.body.text.starts_with(
+            "This timesheet needs your approval before it goes to payroll.\n\n\
+             Approve: https://example.com/timesheet-approval?token=a&b\n\
+             Reject: https://example.com/timesheet-approval?token=c\n\n\
+             These links expire Oct 24, 2026 at 07:00 UTC.\n\nTimesheet"
+        ));
+        assert!(email.body.html.starts_with("<body>\n<p>This timesheet needs"));
+        assert!(email.body.html.contains("token=a&amp;b\">Approve</a>"));
+        assert!(email.body.html.ends_with("<hr>\n<p>Timesheet</p></body>"));
+    }
+
+    #[test]
+    fn approved_email_notes_the_supervisor_and_keeps_the_subject() {
+        let email = approved_email(
+            &timesheet_email("<p>Timesheet</p>"),
+            "Sam <sam@ufv.ca>",
+            "2026-10-20T16:30:00Z".parse().unwrap(),
+        );
+
+        assert_eq!(email.subject, "Timesheet");
+        assert_eq!(
+            email.body.text,
+            "Approved by Sam <sam@ufv.ca> on Oct 20, 2026 at 16:30 UTC.\n\nTimesheet"
+        );
+        // Without a <body>, the note goes first
+        assert!(email.body.html.starts_with(
+            "<p><strong>Approved by Sam &lt;sam@ufv.ca&gt; on Oct 20, 2026 at 16:30 UTC.\
+             </strong></p>\n<p>"
+        ));
+    }
+
+    #[test]
+    fn rejection_text_includes_the_comment_if_any() {
+        assert_eq!(
+            rejection_text("Timesheet", "Sam", "  Oct 12 was a holiday.\n"),
+            "Sam rejected \"Timesheet\".\n\nComment:\nOct 12 was a holiday."
+        );
+        assert_eq!(
+            rejection_text("Timesheet", "Sam", " "),
+            "Sam rejected \"Timesheet\".\n\nNo comment was given."
+        );
+    }
+
+    #[test]
+    fn confirm_page_asks_for_a_comment_only_when_rejecting() {
+        let reject = confirm_page("a\"b", REJECT, "Timesheet <draft>");
+        assert!(reject.contains("<h1>Timesheet &lt;draft&gt;</h1>"));
+        assert!(reject.contains("name=\"token\" value=\"a&quot;b\""));
+        assert!(reject.contains("<textarea name=\"comment\""));
+        assert!(reject.contains(">Reject timesheet</button>"));
+
+        let approve = confirm_page("token", APPROVE, "Timesheet");
+        assert!(!approve.contains("<textarea"));
+        assert!(approve.contains(">Approve timesheet</button>"));
+    }
+}
@@ -0,0 +1,748 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{fmt, fs, path::PathBuf, str::FromStr, sync::Mutex};
+use tracing::{error, info};
+
+use crate::helpers::{amendment::Revision, email::RenderedTimesheet, pdf::TimesheetData};
+
+/// Where a submission stands
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmissionStatus {
+    /// Emailed to the official recipients
+    Sent,
+    /// Emailed to the supervisor, waiting for a decision
+    AwaitingApproval,
+    /// Turned down by the supervisor
+    Rejected,
+    /// The email could not be sent
+    Failed,
+}
+
+impl SubmissionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubmissionStatus::Sent => "sent",
+            SubmissionStatus::AwaitingApproval => "awaiting_approval",
+            SubmissionStatus::Rejected => "rejected",
+            SubmissionStatus::Failed => "failed",
+        }
+    }
+}
+
+impl fmt::Display for SubmissionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SubmissionStatus {
+    type Err = String;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        match status {
+            "sent" => Ok(SubmissionStatus::Sent),
+            "awaiting_approval" => Ok(SubmissionStatus::AwaitingApproval),
+            "rejected" => Ok(SubmissionStatus::Rejected),
+            "failed" => Ok(SubmissionStatus::Failed),
+            other => Err(format!("Unknown submission status '{}'", other)),
+        }
+    }
+}
+
+/// What the mail provider last reported about a submission's email
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// The receiving server hasn't accepted it yet, but delivery is being retried
+    Delayed,
+    Delivered,
+    Opened,
+    /// The receiving server refused it
+    Bounced,
+    /// A recipient marked it as spam
+    Complained,
+    /// The provider couldn't send it
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Delayed => "delayed",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Opened => "opened",
+            DeliveryStatus::Bounced => "bounced",
+            DeliveryStatus::Complained => "complained",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Delayed => "is delayed",
+            DeliveryStatus::Delivered => "was delivered",
+            DeliveryStatus::Opened => "was opened",
+            DeliveryStatus::Bounced => "bounced",
+            DeliveryStatus::Complained => "was marked as spam",
+            DeliveryStatus::Failed => "failed to send",
+        }
+    }
+
+    /// Whether the email didn't reach its recipients as it should have
+    pub fn is_problem(&self) -> bool {
+        matches!(
+            self,
+            DeliveryStatus::Bounced | DeliveryStatus::Complained | DeliveryStatus::Failed
+        )
+    }
+
+    /// Events can arrive out of order, so a later stage is never replaced
+    /// by an earlier one, e.g. `opened` by `delivered`
+    fn stage(&self) -> u8 {
+        match self {
+            DeliveryStatus::Delayed => 0,
+            DeliveryStatus::Delivered => 1,
+            DeliveryStatus::Opened => 2,
+            DeliveryStatus::Bounced | DeliveryStatus::Complained | DeliveryStatus::Failed => 3,
+        }
+    }
+}
+
+impl fmt::Display for DeliveryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DeliveryStatus {
+    type Err = String;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        match status {
+            "delayed" => Ok(DeliveryStatus::Delayed),
+            "delivered" => Ok(DeliveryStatus::Delivered),
+            "opened" => Ok(DeliveryStatus::Opened),
+            "bounced" => Ok(DeliveryStatus::Bounced),
+            "complained" => Ok(DeliveryStatus::Complained),
+            "failed" => Ok(DeliveryStatus::Failed),
+            other => Err(format!("Unknown delivery status '{}'", other)),
+        }
+    }
+}
+
+/// One emailed timesheet, as it was sent. The PDF itself is kept by the
+/// store and looked up by `pdf_sha256`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Submission {
+    pub id: String,
+    /// Period, employee and entries exactly as submitted
+    pub timesheet: TimesheetData,
+    pub pdf_sha256: String,
+    pub subject: String,
+    pub recipients: Vec<String>,
+    pub email_id: Option<String>,
+    pub status: SubmissionStatus,
+    pub submitted_at: DateTime<Utc>,
+    /// When the entries were read from Notion. Edits made in Notion after
+    /// this are not in the timesheet, even if it was sent much later, e.g.
+    /// after waiting for approval.
+    pub rendered_at: DateTime<Utc>,
+    /// The submission this one re-sent, if any
+    pub resend_of: Option<String>,
+    /// Set when this amends an earlier submission
+    pub revision: Option<Revision>,
+    /// Latest delivery status reported for `email_id`, if any
+    pub delivery: Option<DeliveryStatus>,
+    pub delivery_updated_at: Option<DateTime<Utc>>,
+}
+
+impl Submission {
+    /// Record of emailing `timesheet` to `recipients`. It gets `status` if
+    /// the email went out, or `Failed` if it didn't.
+    pub fn new(
+        id: String,
+        timesheet: &RenderedTimesheet,
+        recipients: Vec<String>,
+        sent: &Result<String, String>,
+        status: SubmissionStatus,
+    ) -> Self {
+        Self {
+            id,
+            timesheet: timesheet.data.clone(),
+            pdf_sha256: pdf_hash(&timesheet.pdf),
+            subject: timesheet.email.subject.clone(),
+            recipients,
+            email_id: sent.as_ref().ok().cloned(),
+            status: if sent.is_ok() {
+                status
+            } else {
+                SubmissionStatus::Failed
+            },
+            submitted_at: Utc::now(),
+            rendered_at: timesheet.rendered_at,
+            resend_of: None,
+            revision: timesheet.revision.clone(),
+            delivery: None,
+            delivery_updated_at: None,
+        }
+    }
+
+    /// Record a delivery status reported at `at`. Returns false, leaving the
+    /// submission as it was, when it's already recorded or older news.
+    pub fn record_delivery(&mut self, status: DeliveryStatus, at: DateTime<Utc>) -> bool {
+        if self
+            .delivery
+            .is_some_and(|current| current == status || current.stage() > status.stage())
+        {
+            return false;
+        }
+        self.delivery = Some(status);
+        self.delivery_updated_at = Some(at);
+        true
+    }
+
+    pub fn period(&self) -> Option<(NaiveDate, NaiveDate)> {
+        self.timesheet.period
+    }
+}
+
+/// Hex SHA-256 of a PDF, used to store and check it
+pub fn pdf_hash(pdf: &[u8]) -> String {
+    Sha256::digest(pdf)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Keeps every submission and its PDF
+pub trait SubmissionStore: Send + Sync {
+    /// Add a submission, or replace the one with the same ID
+    fn save(&self, submission: &Submission) -> Result<(), String>;
+    /// Store a PDF under its hash
+    fn save_pdf(&self, pdf: &[u8]) -> Result<String, String>;
+    fn get(&self, id: &str) -> Result<Option<Submission>, String>;
+    /// The submission whose email has this provider ID
+    fn find_by_email_id(&self, email_id: &str) -> Result<Option<Submission>, String>;
+    /// All submissions, newest first
+    fn list(&self) -> Result<Vec<Submission>, String>;
+    /// The latest submission payroll received for a period
+    fn latest_sent(
+        &self,
+        period: (NaiveDate, NaiveDate),
+    ) -> Result<Option<Submission>, String> {
+        Ok(self.list()?.into_iter().find(|submission| {
+            submission.period() == Some(period) && submission.status == SubmissionStatus::Sent
+        }))
+    }
+    /// The PDF with this hash, checked against it
+    fn pdf(&self, sha256: &str) -> Result<Vec<u8>, String>;
+}
+
+/// Keeps submissions in a SQLite database and PDFs as files named by hash
+pub struct SqliteStore {
+    connection: Mutex<Connection>,
+    blob_dir: PathBuf,
+}
+
+impl SqliteStore {
+    /// Open the database at `db_path` and the PDF folder at `blob_dir`,
+    /// creating either if needed
+    pub fn open(db_path: impl Into<PathBuf>, blob_dir: impl Into<PathBuf>) -> Result<Self, String> {
+        let db_path = db_path.into();
+        let blob_dir = blob_dir.into();
+
+        fs::create_dir_all(&blob_dir).map_err(|e| {
+            error!("Failed to create PDF folder {}: {}", blob_dir.display(), e);
+            format!("Failed to create PDF folder {}: {}", blob_dir.display(), e)
+        })?;
+        let connection = Connection::open(&db_path).map_err(|e| {
+            error!("Failed to open archive {}: {}", db_path.display(), e);
+            format!("Failed to open archive {}: {}", db_path.display(), e)
+        })?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS submissions (
+                    id TEXT PRIMARY KEY,
+                    period_start TEXT,
+                    period_end TEXT,
+                    timesheet TEXT NOT NULL,
+                    pdf_sha256 TEXT NOT NULL,
+                    subject TEXT NOT NULL,
+                    recipients TEXT NOT NULL,
+                    email_id TEXT,
+                    status TEXT NOT NULL,
+                    submitted_at TEXT NOT NULL,
+                    resend_of TEXT,
+                    revision INTEGER,
+                    revises TEXT,
+                    changes TEXT,
+                    delivery TEXT,
+                    delivery_updated_at TEXT,
+                    rendered_at TEXT
+                );
+                CREATE INDEX IF NOT EXISTS submissions_period
+                    ON submissions (period_start, period_end);
+                CREATE INDEX IF NOT EXISTS submissions_email_id ON submissions (email_id);",
+            )
+            .map_err(|e| format!("Failed to set up archive {}: {}", db_path.display(), e))?;
+        add_missing_columns(
+            &connection,
+            &[
+                ("revision", "INTEGER"),
+                ("revises", "TEXT"),
+                ("changes", "TEXT"),
+                ("delivery", "TEXT"),
+                ("delivery_updated_at", "TEXT"),
+                ("rendered_at", "TEXT"),
+            ],
+        )
+        .map_err(|e| format!("Failed to upgrade archive {}: {}", db_path.display(), e))?;
+
+        info!(
+            "Opened submission archive {} with PDFs in {}",
+            db_path.display(),
+            blob_dir.display()
+        );
+        Ok(Self {
+            connection: Mutex::new(connection),
+            blob_dir,
+        })
+    }
+
+    fn connection(&self) -> Result<std::sync::MutexGuard<'_, Connection>, String> {
+        self.connection
+            .lock()
+            .map_err(|_| "Archive lock poisoned".to_string())
+    }
+
+    fn blob_path(&self, sha256: &str) -> PathBuf {
+        self.blob_dir.join(format!("{}.pdf", sha256))
+    }
+}
+
+/// Add columns that archives made by older versions don't have yet
+fn add_missing_columns(connection: &Connection, columns: &[(&str, &str)]) -> rusqlite::Result<()> {
+    let existing = connection
+        .prepare("SELECT name FROM pragma_table_info('submissions')")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    for (name, kind) in columns {
+        if !existing.iter().any(|column| column == name) {
+            info!("Adding column {} to the submission archive", name);
+            connection.execute(
+                &format!("ALTER TABLE submissions ADD COLUMN {} {}", name, kind),
+                [],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+const COLUMNS: &str = "id, timesheet, pdf_sha256, subject, recipients, email_id, status, \
+                       submitted_at, resend_of, revision, revises, changes, delivery, \
+                       delivery_updated_at, rendered_at";
+
+fn submission_from_row(row: &Row) -> Result<Submission, String> {
+    let column = |index: usize| -> Result<Option<String>, String> {
+        row.get(index)
+            .map_err(|e| format!("Failed to read submission: {}", e))
+    };
+    let required = |index: usize| -> Result<String, String> {
+        column(index)?.ok_or("Submission is missing a column".to_string())
+    };
+
+    let time = |value: String, what: &str| -> Result<DateTime<Utc>, String> {
+        DateTime::parse_from_rfc3339(&value)
+            .map(|time| time.with_timezone(&Utc))
+            .map_err(|e| format!("Corrupt {}: {}", what, e))
+    };
+
+    let submitted_at = time(required(7)?, "submission time")?;
+    Ok(Submission {
+        id: required(0)?,
+        timesheet: serde_json::from_str(&required(1)?)
+            .map_err(|e| format!("Corrupt timesheet snapshot: {}", e))?,
+        pdf_sha256: required(2)?,
+        subject: required(3)?,
+        recipients: serde_json::from_str(&required(4)?)
+            .map_err(|e| format!("Corrupt recipients: {}", e))?,
+        email_id: column(5)?,
+        status: required(6)?.parse()?,
+        submitted_at,
+        // Archives from before render times were kept only have the send time
+        rendered_at: column(14)?
+            .map(|value| time(value, "render time"))
+            .transpose()?
+            .unwrap_or(submitted_at),
+        resend_of: column(8)?,
+        revision: match row
+            .get::<_, Option<u32>>(9)
+            .map_err(|e| format!("Failed to read submission: {}", e))?
+        {
+            Some(number) => Some(Revision {
+                number,
+                revises: column(10)?.unwrap_or_default(),
+                changes: column(11)?.unwrap_or_default(),
+            }),
+            None => None,
+        },
+        delivery: column(12)?.map(|status| status.parse()).transpose()?,
+        delivery_updated_at: column(13)?
+            .map(|value| time(value, "delivery time"))
+            .transpose()?,
+    })
+}
+
+impl SubmissionStore for SqliteStore {
+    fn save(&self, submission: &Submission) -> Result<(), String> {
+        let timesheet = serde_json::to_string(&submission.timesheet)
+            .map_err(|e| format!("Failed to serialize timesheet snapshot: {}", e))?;
+        let recipients = serde_json::to_string(&submission.recipients)
+            .map_err(|e| format!("Failed to serialize recipients: {}", e))?;
+        let period = submission.period();
+        let revision = submission.revision.as_ref();
+
+        self.connection()?
+            .execute(
+                "INSERT OR REPLACE INTO submissions (id, period_start, period_end, timesheet, \
+                 pdf_sha256, subject, recipients, email_id, status, submitted_at, resend_of, \
+                 revision, revises, changes, delivery, delivery_updated_at, rendered_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, \
+                 ?17)",
+                params![
+                    submission.id,
+                    period.map(|period| period.0.to_string()),
+                    period.map(|period| period.1.to_string()),
+                    timesheet,
+                    submission.pdf_sha256,
+                    submission.subject,
+                    recipients,
+                    submission.email_id,
+                    submission.status.as_str(),
+                    submission.submitted_at.to_rfc3339(),
+                    submission.resend_of,
+                    revision.map(|revision| revision.number),
+                    revision.map(|revision| &revision.revises),
+                    revision.map(|revision| &revision.changes),
+                    submission.delivery.map(|status| status.as_str()),
+                    submission.delivery_updated_at.map(|time| time.to_rfc3339()),
+                    submission.rendered_at.to_rfc3339(),
+                ],
+            )
+            .map(|_| ())
+            .map_err(|e| {
+                error!("Failed to save submission {}: {}", submission.id, e);
+                format!("Failed to save submission {}: {}", submission.id, e)
+            })
+    }
+
+    fn save_pdf(&self, pdf: &[u8]) -> Result<String, String> {
+        let sha256 = pdf_hash(pdf);
+        let path = self.blob_path(&sha256);
+        if path.exists() {
+            return Ok(sha256);
+        }
+
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, pdf)
+            .and_then(|_| fs::rename(&tmp_path, &path))
+            .map_err(|e| {
+                error!("Failed to write PDF {}: {}", path.display(), e);
+                format!("Failed to write PDF {}: {}", path.display(), e)
+            })?;
+        Ok(sha256)
+    }
+
+    fn get(&self, id: &str) -> Result<Option<Submission>, String> {
+        self.connection()?
+            .query_row(
+                &format!("SELECT {} FROM submissions WHERE id = ?1", COLUMNS),
+                params![id],
+                |row| Ok(submission_from_row(row)),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to load submission {}: {}", id, e))?
+            .transpose()
+    }
+
+    fn find_by_email_id(&self, email_id: &str) -> Result<Option<Submission>, String> {
+        self.connection()?
+            .query_row(
+                &format!(
+                    "SELECT {} FROM submissions WHERE email_id = ?1 \
+                     ORDER BY submitted_at DESC LIMIT 1",
+                    COLUMNS
+                ),
+                params![email_id],
+                |row| Ok(submission_from_row(row)),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to look up email {}: {}", email_id, e))?
+            .transpose()
+    }
+
+    fn list(&self) -> Result<Vec<Submission>, String> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM submissions ORDER BY submitted_at DESC",
+                COLUMNS
+            ))
+            .map_err(|e| format!("Failed to list submissions: {}", e))?;
+        let rows = statement
+            .query_map([], |row| Ok(submission_from_row(row)))
+            .map_err(|e| format!("Failed to list submissions: {}", e))?;
+
+        rows.map(|row| row.map_err(|e| format!("Failed to list submissions: {}", e))?)
+            .collect()
+    }
+
+    fn latest_sent(
+        &self,
+        period: (NaiveDate, NaiveDate),
+    ) -> Result<Option<Submission>, String> {
+        self.connection()?
+            .query_row(
+                &format!(
+                    "SELECT {} FROM submissions \
+                     WHERE period_start = ?1 AND period_end = ?2 AND status = ?3 \
+                     ORDER BY submitted_at DESC LIMIT 1",
+                    COLUMNS
+                ),
+                params![
+                    period.0.to_string(),
+                    period.1.to_string(),
+                    SubmissionStatus::Sent.as_str()
+                ],
+                |row| Ok(submission_from_row(row)),
+            )
+            .optional()
+            .map_err(|e| {
+                format!(
+                    "Failed to look up submissions for {} to {}: {}",
+                    period.0, period.1, e
+                )
+            })?
+            .transpose()
+    }
+
+    fn pdf(&self, sha256: &str) -> Result<Vec<u8>, String> {
+        let path = self.blob_path(sha256);
+        let pdf = fs::read(&path).map_err(|e| {
+            error!("Failed to read PDF {}: {}", path.display(), e);
+            format!("Failed to read PDF {}: {}", path.display(), e)
+        })?;
+
+        if pdf_hash(&pdf) != sha256 {
+            error!("PDF {} does not match its hash", path.display());
+            return Err(format!("PDF {} does not match its hash", path.display()));
+        }
+        Ok(pdf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::pdf::TimesheetEntry;
+    use chrono::TimeZone;
+
+    /// A fresh database and PDF folder, removed when dropped
+    struct TempArchive {
+        dir: PathBuf,
+    }
+
+    impl TempArchive {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("archive-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            Self { dir }
+        }
+
+        fn db_path(&self) -> PathBuf {
+            self.dir.join("submissions.sqlite")
+        }
+
+        fn open(&self) -> SqliteStore {
+            SqliteStore::open(self.db_path(), self.dir.join("pdfs")).unwrap()
+        }
+    }
+
+    impl Drop for TempArchive {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn period(start_day: u32) -> (NaiveDate, NaiveDate) {
+        let start = NaiveDate::from_ymd_opt(2026, 10, start_day).unwrap();
+        (start, start + chrono::Days::new(14))
+    }
+
+    fn submission(id: &str, start_day: u32, status: SubmissionStatus, hour: u32) -> Submission {
+        let submitted_at = Utc.with_ymd_and_hms(2026, 10, 24, hour, 0, 0).unwrap();
+        Submission {
+            id: id.to_string(),
+            timesheet: TimesheetData {
+                entries: vec![TimesheetEntry {
+                    month: 10,
+                    day: start_day,
+                    start: "09:00".to_string(),
+                    end: "12:00".to_string(),
+                    paid_hours: 3.0,
+                    notes: None,
+                }],
+                total_hours: 3.0,
+                employee: Some("Test Employee".to_string()),
+                period: Some(period(start_day)),
+            },
+            pdf_sha256: pdf_hash(b"%PDF"),
+            subject: format!("Timesheet {}", id),
+            recipients: vec!["payroll@example.com".to_string()],
+            email_id: Some(format!("email-{}", id)),
+            status,
+            submitted_at,
+            rendered_at: submitted_at - chrono::Duration::minutes(5),
+            resend_of: None,
+            revision: None,
+            delivery: None,
+            delivery_updated_at: None,
+        }
+    }
+
+    fn ids(submissions: &[Submission]) -> Vec<&str> {
+        submissions.iter().map(|submission| submission.id.as_str()).collect()
+    }
+
+    #[test]
+    fn saves_gets_and_lists_newest_first() {
+        let archive = TempArchive::new();
+        let store = archive.open();
+        store.save(&submission("a", 9, SubmissionStatus::Sent, 10)).unwrap();
+        store.save(&submission("b", 9, SubmissionStatus::Sent, 12)).unwrap();
+
+        let mut revised = submission("c", 9, SubmissionStatus::AwaitingApproval, 11);
+        revised.revision = Some(Revision {
+            number: 1,
+            revises: "a".to_string(),
+            changes: "Added: 10/9".to_string(),
+        });
+        store.save(&revised).unwrap();
+
+        let loaded = store.get("c").unwrap().unwrap();
+        assert_eq!(loaded.timesheet.entries, revised.timesheet.entries);
+        assert_eq!(loaded.rendered_at, revised.rendered_at);
+        assert_eq!(loaded.revision.unwrap().revises, "a");
+        assert!(store.get("missing").unwrap().is_none());
+        assert_eq!(ids(&store.list().unwrap()), vec!["b", "c", "a"]);
+
+        // Saving again replaces, and survives reopening
+        revised.status = SubmissionStatus::Sent;
+        store.save(&revised).unwrap();
+        drop(store);
+        let store = archive.open();
+        assert_eq!(store.get("c").unwrap().unwrap().status, SubmissionStatus::Sent);
+        assert_eq!(store.list().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn finds_latest_sent_for_a_period() {
+        let archive = TempArchive::new();
+        let store = archive.open();
+        store.save(&submission("old", 9, SubmissionStatus::Sent, 10)).unwrap();
+        store.save(&submission("new", 9, SubmissionStatus::Sent, 11)).unwrap();
+        store.save(&submission("pending", 9, SubmissionStatus::AwaitingApproval, 12)).unwrap();
+        store.save(&submission("other", 24, SubmissionStatus::Sent, 13)).unwrap();
+
+        assert_eq!(store.latest_sent(period(9)).unwrap().unwrap().id, "new");
+        assert_eq!(store.latest_sent(period(24)).unwrap().unwrap().id, "other");
+        assert!(store.latest_sent(period(1)).unwrap().is_none());
+    }
+
+    #[test]
+    fn finds_by_email_id() {
+        let archive = TempArchive::new();
+        let store = archive.open();
+        store.save(&submission("a", 9, SubmissionStatus::Sent, 10)).unwrap();
+
+        assert_eq!(store.find_by_email_id("email-a").unwrap().unwrap().id, "a");
+        assert!(store.find_by_email_id("email-b").unwrap().is_none());
+    }
+
+    #[test]
+    fn checks_pdfs_against_their_hash() {
+        let archive = TempArchive::new();
+        let store = archive.open();
+        let sha256 = store.save_pdf(b"%PDF-1.5 timesheet").unwrap();
+        assert_eq!(sha256, pdf_hash(b"%PDF-1.5 timesheet"));
+        assert_eq!(store.pdf(&sha256).unwrap(), b"%PDF-1.5 timesheet");
+
+        fs::write(store.blob_path(&sha256), b"%PDF-1.5 tampered").unwrap();
+        assert!(store
+            .pdf(&sha256)
+            .unwrap_err()
+            .contains("does not match its hash"));
+        assert!(store.pdf(&pdf_hash(b"never stored")).is_err());
+    }
+
+    #[test]
+    fn upgrades_archives_from_older_versions() {
+        let archive = TempArchive::new();
+        let old = submission("old", 9, SubmissionStatus::Sent, 10);
+        {
+            let connection = Connection::open(archive.db_path()).unwrap();
+            connection
+                .execute_batch(
+                    "CREATE TABLE submissions (
+                        id TEXT PRIMARY KEY,
+                        period_start TEXT,
+                        period_end TEXT,
+                        timesheet TEXT NOT NULL,
+                        pdf_sha256 TEXT NOT NULL,
+                        subject TEXT NOT NULL,
+                        recipients TEXT NOT NULL,
+                        email_id TEXT,
+                        status TEXT NOT NULL,
+                        submitted_at TEXT NOT NULL,
+                        resend_of TEXT
+                    );",
+                )
+                .unwrap();
+            connection
+                .execute(
+                    "INSERT INTO submissions VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, NULL)",
+                    params![
+                        old.id,
+                        period(9).0.to_string(),
+                        period(9).1.to_string(),
+                        serde_json::to_string(&old.timesheet).unwrap(),
+                        old.pdf_sha256,
+                        old.subject,
+                        serde_json::to_string(&old.recipients).unwrap(),
+                        old.email_id,
+                        old.status.as_str(),
+                        old.submitted_at.to_rfc3339(),
+                    ],
+                )
+                .unwrap();
+        }
+
+        let store = archive.open();
+        let loaded = store.get("old").unwrap().unwrap();
+        assert_eq!(loaded.rendered_at, old.submitted_at);
+        assert!(loaded.revision.is_none());
+        assert!(loaded.delivery.is_none());
+
+        let mut delivered = loaded;
+        delivered.record_delivery(DeliveryStatus::Delivered, Utc::now());
+        store.save(&delivered).unwrap();
+        assert_eq!(
+            store.get("old").unwrap().unwrap().delivery,
+            Some(DeliveryStatus::Delivered)
+        );
+    }
+}
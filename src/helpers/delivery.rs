@@ -0,0 +1,206 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Duration, Local, Utc};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::helpers::archive::{DeliveryStatus, Submission};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Delivery status from Resend's webhooks. Resend signs deliveries through
+/// Svix; add the endpoint in the Resend dashboard and copy its signing
+/// secret here.
+#[derive(Clone, Debug)]
+pub struct DeliveryConfig {
+    /// The endpoint's signing secret, e.g. `whsec_MfKQ9r8GKYqrTwjUPD8ILPZIo2LaLaSw`
+    pub signing_secret: String,
+    /// How far a delivery's timestamp may be from now before it is turned
+    /// away as a replay
+    pub tolerance: Duration,
+}
+
+impl DeliveryConfig {
+    /// Accept deliveries stamped within 5 minutes of now
+    pub fn new(signing_secret: impl Into<String>) -> Self {
+        Self {
+            signing_secret: signing_secret.into(),
+            tolerance: Duration::minutes(5),
+        }
+    }
+
+    /// Check a delivery's `svix-id`, `svix-timestamp` and `svix-signature`
+    /// headers against its raw body
+    pub fn verify(
+        &self,
+        message_id: &str,
+        timestamp: &str,
+        signatures: &str,
+        body: &[u8],
+    ) -> Result<(), String> {
+        let sent_at = timestamp
+            .parse::<i64>()
+            .ok()
+            .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+            .ok_or("Malformed webhook timestamp".to_string())?;
+        if (Utc::now() - sent_at).abs() > self.tolerance {
+            return Err(format!(
+                "Webhook timestamp {} is too old or too new",
+                sent_at
+            ));
+        }
+
+        let secret = self
+            .signing_secret
+            .strip_prefix("whsec_")
+            .unwrap_or(&self.signing_secret);
+        let key = STANDARD
+            .decode(secret)
+            .map_err(|e| format!("Malformed webhook signing secret: {}", e))?;
+        let mut mac = HmacSha256::new_from_slice(&key).expect("HMAC accepts any key length");
+        mac.update(format!("{}.{}.", message_id, timestamp).as_bytes());
+        mac.update(body);
+
+        // Several space-separated `v1,<base64>` signatures while a secret
+        // is being rotated; any one will do
+        let valid = signatures
+            .split_whitespace()
+            .filter_map(|signature| signature.strip_prefix("v1,"))
+            .filter_map(|signature| STANDARD.decode(signature).ok())
+            .any(|signature| mac.clone().verify_slice(&signature).is_ok());
+        if valid {
+            Ok(())
+        } else {
+            Err("Invalid webhook signature".to_string())
+        }
+    }
+}
+
+/// One Resend webhook delivery, e.g. `email.bounced`
+#[derive(Clone, Debug, Deserialize)]
+pub struct DeliveryEvent {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub created_at: DateTime<Utc>,
+    pub data: DeliveryData,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct DeliveryData {
+    pub email_id: String,
+    /// Details of a bounce, such as its type and the receiving server's message
+    pub bounce: Option<serde_json::Value>,
+}
+
+impl DeliveryEvent {
+    /// The delivery status the event reports, or `None` for events that
+    /// aren't tracked, such as `email.sent` and `email.clicked`
+    pub fn status(&self) -> Option<DeliveryStatus> {
+        match self.event_type.as_str() {
+            "email.delivery_delayed" => Some(DeliveryStatus::Delayed),
+            "email.delivered" => Some(DeliveryStatus::Delivered),
+            "email.opened" => Some(DeliveryStatus::Opened),
+            "email.bounced" => Some(DeliveryStatus::Bounced),
+            "email.complained" => Some(DeliveryStatus::Complained),
+            "email.failed" => Some(DeliveryStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// Error report for a timesheet email that didn't arrive
+pub fn problem_report(
+    submission: &Submission,
+    status: DeliveryStatus,
+    event: &DeliveryEvent,
+) -> String {
+    let details = event
+        .data
+        .bounce
+        .as_ref()
+        .map(|bounce| format!("\n\nDetails: {}", bounce))
+        .unwrap_or_default();
+
+    format!(
+        "Timesheet email \"{}\" (submission {}, email ID {}) to {} {} on {}.{}\n\n\
+         Check the address, then resend it with POST /timesheet-submissions/{}/resend.",
+        submission.subject,
+        submission.id,
+        event.data.email_id,
+        submission.recipients.join(", "),
+        status.description(),
+        event
+            .created_at
+            .with_timezone(&Local)
+            .format("%b %-d, %Y at %H:%M"),
+        details,
+        submission.id
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Example from Svix's docs on verifying webhooks manually
+    const SECRET: &str = "whsec_MfKQ9r8GKYqrTwjUPD8ILPZIo2LaLaSw";
+    const MESSAGE_ID: &str = "msg_p5jXN8AQM9LWM0D4loKWxJek";
+    const TIMESTAMP: &str = "1614265330";
+    const BODY: &[u8] = br#"{"test": 2432232314}"#;
+    const SIGNATURE: &str = "v1,g0hM9SsE+OTPJTGt/tmIKtSyZlE3uFJELVlNIOLJ1OE=";
+
+    /// The example is from 2021, so accept any timestamp
+    fn config() -> DeliveryConfig {
+        DeliveryConfig {
+            tolerance: Duration::days(365 * 100),
+            ..DeliveryConfig::new(SECRET)
+        }
+    }
+
+    #[test]
+    fn verifies_known_signature() {
+        assert_eq!(
+            config().verify(MESSAGE_ID, TIMESTAMP, SIGNATURE, BODY),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_tampered_body_and_id() {
+        let config = config();
+        assert!(config
+            .verify(MESSAGE_ID, TIMESTAMP, SIGNATURE, br#"{"test": 2432232315}"#)
+            .is_err());
+        assert!(config
+            .verify("msg_other", TIMESTAMP, SIGNATURE, BODY)
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_timestamps_outside_tolerance() {
+        let config = DeliveryConfig::new(SECRET);
+        let error = config
+            .verify(MESSAGE_ID, TIMESTAMP, SIGNATURE, BODY)
+            .unwrap_err();
+        assert!(error.contains("too old or too new"), "{}", error);
+
+        let future = (Utc::now() + Duration::minutes(10)).timestamp().to_string();
+        assert!(config.verify(MESSAGE_ID, &future, SIGNATURE, BODY).is_err());
+        assert!(config.verify(MESSAGE_ID, "soon", SIGNATURE, BODY).is_err());
+    }
+
+    #[test]
+    fn accepts_any_signature_while_rotating() {
+        let config = config();
+        let rotated = format!(
+            "v1,Ceo5qEr07ixe2NLpvHk3FH9bwy/WavXrAFQ/9tdO6mc= v2,unknown {}",
+            SIGNATURE
+        );
+        assert_eq!(config.verify(MESSAGE_ID, TIMESTAMP, &rotated, BODY), Ok(()));
+
+        let all_stale = "v1,Ceo5qEr07ixe2NLpvHk3FH9bwy/WavXrAFQ/9tdO6mc= v1,not-base64";
+        assert!(config
+            .verify(MESSAGE_ID, TIMESTAMP, all_stale, BODY)
+            .is_err());
+    }
+}
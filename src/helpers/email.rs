@@ -1,101 +1,398 @@
-use chrono::Datelike;
-use resend_rs::{
-    types::{Attachment, CreateEmailBaseOptions, CreateEmailResponse},
-    Resend,
-};
+use chrono::{DateTime, Utc};
 use tracing::{error, info};
 
-use crate::helpers::notion;
+use crate::helpers::{amendment::Revision, pdf::TimesheetData};
+
+mod mailer;
+mod template;
+#[cfg(test)]
+pub(crate) mod testing;
+
+pub use mailer::{EmailAttachment, EmailMessage, FileMailer, Mailer, ResendMailer, SmtpMailer};
+pub use template::{EmailBody, EmailTemplates};
+
+const DEFAULT_SUBJECT: &str = "Timesheet\
+    {% if period_start_short %} {{ period_start_short }} to {{ period_end_short }}{% endif %}\
+    {% if employee %} - {{ employee }}{% endif %}";
+const DEFAULT_FILENAME: &str = "Timesheet";
+
+/// Who emails are sent from and to, and how they are named
+#[derive(Clone, Debug)]
+pub struct EmailConfig {
+    /// Sender, e.g. `Jane Doe <jane@example.com>`
+    pub from: String,
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub bcc: Vec<String>,
+    pub reply_to: Vec<String>,
+    /// Recipients of error reports. When empty they go to the sender.
+    pub error_to: Vec<String>,
+    /// Template for the timesheet email's subject line
+    pub subject_template: String,
+    /// Template for attachment file names, without the extension
+    pub filename_template: String,
+    /// Templates for the timesheet email's body
+    pub templates: EmailTemplates,
+}
+
+/// A timesheet email ready to send
+#[derive(Clone, Debug)]
+pub struct TimesheetEmail {
+    pub subject: String,
+    pub body: EmailBody,
+    file_stem: String,
+}
+
+/// A timesheet with everything needed to email it
+#[derive(Clone, Debug)]
+pub struct RenderedTimesheet {
+    pub data: TimesheetData,
+    pub email: TimesheetEmail,
+    pub pdf: Vec<u8>,
+    /// Spreadsheet copies sent alongside the PDF
+    pub attachments: Vec<EmailAttachment>,
+    /// Set when this amends an earlier submission
+    pub revision: Option<Revision>,
+    /// When `data` was read from Notion
+    pub rendered_at: DateTime<Utc>,
+}
+
+impl EmailConfig {
+    /// Config with the default subject, file name and body templates
+    pub fn new(from: impl Into<String>, to: Vec<String>) -> Self {
+        Self {
+            from: from.into(),
+            to,
+            cc: Vec::new(),
+            bcc: Vec::new(),
+            reply_to: Vec::new(),
+            error_to: Vec::new(),
+            subject_template: DEFAULT_SUBJECT.to_string(),
+            filename_template: DEFAULT_FILENAME.to_string(),
+            templates: EmailTemplates::default(),
+        }
+    }
+
+    /// Render the subject, body and attachment names for a timesheet
+    pub fn render(&self, data: &TimesheetData, notion_url: &str) -> Result<TimesheetEmail, String> {
+        Ok(TimesheetEmail {
+            subject: template::render_line(&self.subject_template, data, notion_url)?,
+            body: self.templates.render(data, notion_url)?,
+            file_stem: self.file_stem(data)?,
+        })
+    }
+
+    /// Attachment or download name for a timesheet, e.g. `Timesheet.pdf`
+    pub fn filename(&self, data: &TimesheetData, extension: &str) -> Result<String, String> {
+        Ok(format!("{}.{}", self.file_stem(data)?, extension))
+    }
+
+    fn file_stem(&self, data: &TimesheetData) -> Result<String, String> {
+        let stem = template::render_line(&self.filename_template, data, "")?
+            .chars()
+            .filter(|c| !matches!(c, '/' | '\\' | '"' | ':' | '\r' | '\n'))
+            .collect::<String>();
+        if stem.trim().is_empty() {
+            return Err(format!(
+                "File name template '{}' rendered an empty name",
+                self.filename_template
+            ));
+        }
+        Ok(stem)
+    }
+
+    /// Everyone the timesheet email goes to: `to`, `cc` and `bcc`
+    pub fn recipients(&self) -> Vec<String> {
+        [&self.to, &self.cc, &self.bcc]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    /// Recipients of error reports: `error_to`, or the sender
+    pub fn error_recipients(&self) -> Vec<String> {
+        if self.error_to.is_empty() {
+            vec![self.from.clone()]
+        } else {
+            self.error_to.clone()
+        }
+    }
+
+    /// Start an email to `to`, copying in the configured cc, bcc and reply-to
+    fn message(&self, to: Vec<String>, subject: &str, text: &str) -> EmailMessage {
+        EmailMessage {
+            from: self.from.clone(),
+            to,
+            cc: self.cc.clone(),
+            bcc: self.bcc.clone(),
+            reply_to: self.reply_to.clone(),
+            subject: subject.to_string(),
+            text: text.to_string(),
+            ..EmailMessage::default()
+        }
+    }
+}
+
+impl TimesheetEmail {
+    /// Attachment name with the given extension
+    pub fn filename(&self, extension: &str) -> String {
+        format!("{}.{}", self.file_stem, extension)
+    }
+}
 
 pub async fn send_email(
-    resend: &Resend,
+    mailer: &dyn Mailer,
+    config: &EmailConfig,
     email_content: &str,
     subject_: Option<&str>,
-    attachment: Option<Attachment>,
-) -> Result<CreateEmailResponse, resend_rs::Error> {
-    let from = "devnull03 <dev@dvnl.work>";
-    let to = ["arnav@dvnl.work"];
+    attachment: Option<EmailAttachment>,
+) -> Result<String, String> {
     let subject = subject_.unwrap_or("Email sent from webhooks server");
 
     info!("Preparing to send email with subject: {}", subject);
 
-    let mut email = CreateEmailBaseOptions::new(from, to, subject).with_text(email_content);
-
-    if let Some(attachment) = attachment {
-        email = email.with_attachment(attachment);
-    }
+    let mut email = config.message(config.to.clone(), subject, email_content);
+    email.attachments.extend(attachment);
 
-    let result = resend.emails.send(email).await;
+    let result = mailer.send(&email).await;
     match &result {
-        Ok(response) => info!("Email sent successfully with ID: {}", response.id),
+        Ok(id) => info!("Email sent successfully with ID: {}", id),
         Err(e) => error!("Failed to send email: {}", e),
     }
 
     result
 }
 
+/// Send the timesheet PDF with a rendered HTML and plain-text body, plus any
+/// extra attachments such as CSV or XLSX copies of the same data
 pub async fn send_timesheet_email(
-    resend: &Resend,
+    mailer: &dyn Mailer,
+    config: &EmailConfig,
+    timesheet_email: &TimesheetEmail,
     timesheet: Vec<u8>,
-) -> Result<CreateEmailResponse, resend_rs::Error> {
-    let from = "devnull03 <dev@dvnl.work>";
-    let to = ["arnav.mehta@student.ufv.ca", "arnav@dvnl.work"];
-
-    let period = notion::utils::get_current_pay_period();
-    info!(
-        "Sending timesheet for pay period: {:?} to {:?}",
-        period.0, period.1
+    extra_attachments: Vec<EmailAttachment>,
+) -> Result<String, String> {
+    let email = config.message(
+        config.to.clone(),
+        &timesheet_email.subject,
+        &timesheet_email.body.text,
     );
+    deliver_timesheet(mailer, email, timesheet_email, timesheet, extra_attachments).await
+}
 
-    let subject = format!(
-        "Timesheet {}/{} to {}/{} - Arnav Mehta",
-        period.0.month(),
-        period.0.day(),
-        period.1.month(),
-        period.1.day()
-    );
+/// Send a timesheet to a single reviewer, such as a supervisor, without
+/// copying in the configured cc or bcc recipients
+pub async fn send_timesheet_for_review(
+    mailer: &dyn Mailer,
+    config: &EmailConfig,
+    reviewer: &str,
+    timesheet_email: &TimesheetEmail,
+    timesheet: Vec<u8>,
+    extra_attachments: Vec<EmailAttachment>,
+) -> Result<String, String> {
+    let email = EmailMessage {
+        from: config.from.clone(),
+        to: vec![reviewer.to_string()],
+        reply_to: config.reply_to.clone(),
+        subject: timesheet_email.subject.clone(),
+        text: timesheet_email.body.text.clone(),
+        ..EmailMessage::default()
+    };
+    deliver_timesheet(mailer, email, timesheet_email, timesheet, extra_attachments).await
+}
 
-    info!("Preparing email with subject: {}", &subject);
+async fn deliver_timesheet(
+    mailer: &dyn Mailer,
+    mut email: EmailMessage,
+    timesheet_email: &TimesheetEmail,
+    timesheet: Vec<u8>,
+    extra_attachments: Vec<EmailAttachment>,
+) -> Result<String, String> {
+    info!("Preparing email with subject: {}", &timesheet_email.subject);
     info!("Timesheet attachment size: {} bytes", timesheet.len());
 
-    let email = CreateEmailBaseOptions::new(from, to, &subject)
-        .with_text(&subject)
-        .with_attachment(
-            Attachment::from_content(timesheet)
-                .with_filename("Timesheet.pdf")
-                .with_content_type("pdf"),
-        );
+    email.html = Some(timesheet_email.body.html.clone());
+    email.attachments.push(EmailAttachment {
+        filename: timesheet_email.filename("pdf"),
+        content_type: "application/pdf".to_string(),
+        content: timesheet,
+    });
+    email.attachments.extend(extra_attachments);
 
-    let result = resend.emails.send(email).await;
+    let result = mailer.send(&email).await;
     match &result {
-        Ok(response) => info!("Timesheet email sent successfully with ID: {}", response.id),
+        Ok(id) => info!("Timesheet email sent successfully with ID: {}", id),
         Err(e) => error!("Failed to send timesheet email: {}", e),
     }
 
     result
 }
 
+/// Send a plain-text notice to specific people, without copying in the
+/// configured cc or bcc recipients
+pub async fn send_notice(
+    mailer: &dyn Mailer,
+    config: &EmailConfig,
+    to: Vec<String>,
+    subject: &str,
+    text: &str,
+) -> Result<String, String> {
+    info!("Sending notice '{}' to {:?}", subject, to);
+
+    let email = EmailMessage {
+        from: config.from.clone(),
+        to,
+        subject: subject.to_string(),
+        text: text.to_string(),
+        ..EmailMessage::default()
+    };
+
+    let result = mailer.send(&email).await;
+    match &result {
+        Ok(id) => info!("Notice sent successfully with ID: {}", id),
+        Err(e) => error!("Failed to send notice: {}", e),
+    }
+
+    result
+}
+
+/// Report an error to `error_to`, without copying in cc or bcc recipients
 pub async fn send_error_info(
-    resend: &Resend,
+    mailer: &dyn Mailer,
+    config: &EmailConfig,
     error_info: &str,
-) -> Result<CreateEmailResponse, resend_rs::Error> {
-    let from = "devnull03 <dev@dvnl.work>";
-    let to = ["dev@dvnl.work"];
-    let subject = "Error from UFV timesheet service";
-
+) -> Result<String, String> {
     info!("Sending error information email");
     info!("Error details: {}", error_info);
 
-    let email = CreateEmailBaseOptions::new(from, to, subject).with_text(error_info);
+    send_notice(
+        mailer,
+        config,
+        config.error_recipients(),
+        "Error from UFV timesheet service",
+        error_info,
+    )
+    .await
+}
 
-    let result = resend.emails.send(email).await;
-    match &result {
-        Ok(response) => info!(
-            "Error info email sent successfully with ID: {}",
-            response.id
-        ),
-        Err(e) => error!("Failed to send error info email: {}", e),
+#[cfg(test)]
+mod tests {
+    use super::testing::RecordingMailer;
+    use super::*;
+    use crate::helpers::pdf::TimesheetEntry;
+    use chrono::NaiveDate;
+
+    fn config() -> EmailConfig {
+        EmailConfig {
+            cc: vec!["Sam Lee <sam.lee@ufv.ca>".to_string()],
+            bcc: vec!["records@example.com".to_string()],
+            reply_to: vec!["Ada Lovelace <ada@student.ufv.ca>".to_string()],
+            ..EmailConfig::new(
+                "Timesheets <timesheets@example.com>",
+                vec!["payroll@ufv.ca".to_string()],
+            )
+        }
     }
 
-    result
+    fn timesheet() -> TimesheetData {
+        TimesheetData {
+            entries: vec![TimesheetEntry {
+                month: 10,
+                day: 12,
+                start: "9:00 AM".to_string(),
+                end: "12:00 PM".to_string(),
+                paid_hours: 3.0,
+                notes: None,
+            }],
+            total_hours: 3.0,
+            employee: Some("Ada Lovelace".to_string()),
+            period: Some((
+                NaiveDate::from_ymd_opt(2026, 10, 9).unwrap(),
+                NaiveDate::from_ymd_opt(2026, 10, 23).unwrap(),
+            )),
+        }
+    }
+
+    fn csv_attachment() -> EmailAttachment {
+        EmailAttachment {
+            filename: "Timesheet.csv".to_string(),
+            content_type: "text/csv".to_string(),
+            content: b"date\n".to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn timesheet_email_copies_in_cc_bcc_and_reply_to() {
+        let mailer = RecordingMailer::default();
+        let config = config();
+        let email = config.render(&timesheet(), "").unwrap();
+        assert_eq!(email.subject, "Timesheet 10/9 to 10/23 - Ada Lovelace");
+
+        let pdf = b"%PDF".to_vec();
+        let id = send_timesheet_email(&mailer, &config, &email, pdf, vec![csv_attachment()])
+            .await
+            .unwrap();
+        assert_eq!(id, "email-1");
+
+        let message = mailer.last();
+        assert_eq!(message.from, "Timesheets <timesheets@example.com>");
+        assert_eq!(message.to, ["payroll@ufv.ca"]);
+        assert_eq!(message.cc, ["Sam Lee <sam.lee@ufv.ca>"]);
+        assert_eq!(message.bcc, ["records@example.com"]);
+        assert_eq!(message.reply_to, ["Ada Lovelace <ada@student.ufv.ca>"]);
+        assert_eq!(message.subject, email.subject);
+        assert_eq!(message.html.as_deref(), Some(email.body.html.as_str()));
+        let names: Vec<_> = message.attachments.iter().map(|a| a.filename.as_str()).collect();
+        assert_eq!(names, ["Timesheet.pdf", "Timesheet.csv"]);
+
+        assert_eq!(
+            config.recipients(),
+            ["payroll@ufv.ca", "Sam Lee <sam.lee@ufv.ca>", "records@example.com"]
+        );
+    }
+
+    #[tokio::test]
+    async fn reviews_and_notices_leave_out_cc_and_bcc() {
+        let mailer = RecordingMailer::default();
+        let config = config();
+        let email = config.render(&timesheet(), "").unwrap();
+
+        let supervisor = "Sam Lee <sam.lee@ufv.ca>";
+        send_timesheet_for_review(&mailer, &config, supervisor, &email, Vec::new(), Vec::new())
+            .await
+            .unwrap();
+        let review = mailer.last();
+        assert_eq!(review.to, [supervisor]);
+        assert!(review.cc.is_empty() && review.bcc.is_empty());
+        assert_eq!(review.reply_to, ["Ada Lovelace <ada@student.ufv.ca>"]);
+
+        send_error_info(&mailer, &config, "Notion is down").await.unwrap();
+        let report = mailer.last();
+        assert_eq!(report.to, ["Timesheets <timesheets@example.com>"]);
+        assert!(report.cc.is_empty() && report.bcc.is_empty() && report.reply_to.is_empty());
+        assert_eq!(report.text, "Notion is down");
+    }
+
+    #[test]
+    fn file_names_drop_characters_paths_cannot_hold() {
+        let config = EmailConfig {
+            filename_template: "Timesheet {{ period_start_iso }}/{{ employee }}: \"final\""
+                .to_string(),
+            ..config()
+        };
+        assert_eq!(
+            config.filename(&timesheet(), "pdf").unwrap(),
+            "Timesheet 2026-10-09Ada Lovelace final.pdf"
+        );
+
+        let config = EmailConfig {
+            filename_template: "{{ nothing }}".to_string(),
+            ..config
+        };
+        assert_eq!(
+            config.filename(&timesheet(), "pdf").unwrap_err(),
+            "File name template '{{ nothing }}' rendered an empty name"
+        );
+    }
 }
@@ -0,0 +1,386 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use lettre::{
+    message::{header::ContentType, Attachment as MimeAttachment, Mailbox, MultiPart, SinglePart},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use resend_rs::{
+    types::{Attachment, CreateEmailBaseOptions},
+    Resend,
+};
+use std::{
+    fs,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tracing::{error, info};
+
+/// An email ready to hand to a [`Mailer`]
+#[derive(Clone, Debug, Default)]
+pub struct EmailMessage {
+    pub from: String,
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub bcc: Vec<String>,
+    pub reply_to: Vec<String>,
+    pub subject: String,
+    pub text: String,
+    /// HTML alternative to the plain-text body
+    pub html: Option<String>,
+    pub attachments: Vec<EmailAttachment>,
+}
+
+#[derive(Clone, Debug)]
+pub struct EmailAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
+
+/// Something that can deliver an email. Returns an ID for the sent message:
+/// the provider's ID, the Message-ID header, or the path written to.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, message: &EmailMessage) -> Result<String, String>;
+}
+
+/// Sends through the Resend API
+#[derive(Clone)]
+pub struct ResendMailer {
+    resend: Resend,
+}
+
+impl ResendMailer {
+    pub fn new(resend: Resend) -> Self {
+        Self { resend }
+    }
+}
+
+#[async_trait]
+impl Mailer for ResendMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<String, String> {
+        let mut email =
+            CreateEmailBaseOptions::new(&message.from, message.to.clone(), &message.subject)
+                .with_text(&message.text);
+        if let Some(html) = &message.html {
+            email = email.with_html(html);
+        }
+        for address in &message.cc {
+            email = email.with_cc(address);
+        }
+        for address in &message.bcc {
+            email = email.with_bcc(address);
+        }
+        if !message.reply_to.is_empty() {
+            email = email.with_reply_multiple(&message.reply_to);
+        }
+        for attachment in &message.attachments {
+            email = email.with_attachment(
+                Attachment::from_content(attachment.content.clone())
+                    .with_filename(&attachment.filename)
+                    .with_content_type(&attachment.content_type),
+            );
+        }
+
+        match self.resend.emails.send(email).await {
+            Ok(response) => Ok(response.id.to_string()),
+            Err(e) => {
+                error!("Resend failed to send email: {}", e);
+                Err(format!("Resend failed to send email: {}", e))
+            }
+        }
+    }
+}
+
+/// Sends through an SMTP relay, such as a university mail server
+#[derive(Clone)]
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    /// Connect with STARTTLS on port 587, the usual setup for
+    /// authenticated submission
+    pub fn starttls(host: &str, username: &str, password: &str) -> Result<Self, String> {
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+            .map_err(|e| format!("Invalid SMTP relay {}: {}", host, e))?
+            .credentials(Credentials::new(username.to_string(), password.to_string()))
+            .build();
+        Ok(Self { transport })
+    }
+
+    /// Connect with implicit TLS on port 465
+    pub fn tls(host: &str, username: &str, password: &str) -> Result<Self, String> {
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+            .map_err(|e| format!("Invalid SMTP relay {}: {}", host, e))?
+            .credentials(Credentials::new(username.to_string(), password.to_string()))
+            .build();
+        Ok(Self { transport })
+    }
+
+    /// Use an already configured transport, e.g. an unencrypted relay on
+    /// localhost built with `AsyncSmtpTransport::builder_dangerous`
+    pub fn from_transport(transport: AsyncSmtpTransport<Tokio1Executor>) -> Self {
+        Self { transport }
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<String, String> {
+        let mime = build_mime(message, false)?;
+        let message_id = message_id(&mime);
+
+        self.transport.send(mime).await.map_err(|e| {
+            error!("SMTP failed to send email: {}", e);
+            format!("SMTP failed to send email: {}", e)
+        })?;
+        Ok(message_id)
+    }
+}
+
+/// Writes each email to disk instead of sending it, either as loose `.eml`
+/// files or into a maildir that mail clients can open
+pub struct FileMailer {
+    dir: PathBuf,
+    maildir: bool,
+    counter: AtomicU64,
+}
+
+impl FileMailer {
+    /// Write `<timestamp>-<n>.eml` files into `dir`
+    pub fn eml(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            maildir: false,
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    /// Deliver into the `new` folder of a maildir at `dir`, creating it if needed
+    pub fn maildir(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            maildir: true,
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    fn write(&self, contents: &[u8]) -> Result<PathBuf, std::io::Error> {
+        let unique = format!(
+            "{}-{}-{}",
+            Utc::now().format("%Y%m%dT%H%M%S%.6f"),
+            std::process::id(),
+            self.counter.fetch_add(1, Ordering::Relaxed)
+        );
+
+        if !self.maildir {
+            fs::create_dir_all(&self.dir)?;
+            let path = self.dir.join(format!("{}.eml", unique));
+            fs::write(&path, contents)?;
+            return Ok(path);
+        }
+
+        for folder in ["tmp", "new", "cur"] {
+            fs::create_dir_all(self.dir.join(folder))?;
+        }
+        // Maildir delivery: write to tmp, then move into new in one step
+        let name = format!("{}.timesheet", unique);
+        let tmp_path = self.dir.join("tmp").join(&name);
+        let new_path = self.dir.join("new").join(&name);
+        fs::write(&tmp_path, contents)?;
+        fs::rename(&tmp_path, &new_path)?;
+        Ok(new_path)
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<String, String> {
+        let contents = build_mime(message, true)?.formatted();
+
+        match self.write(&contents) {
+            Ok(path) => {
+                info!("Saved email '{}' to {}", message.subject, path.display());
+                Ok(path.display().to_string())
+            }
+            Err(e) => {
+                error!("Failed to save email to {}: {}", self.dir.display(), e);
+                Err(format!(
+                    "Failed to save email to {}: {}",
+                    self.dir.display(),
+                    e
+                ))
+            }
+        }
+    }
+}
+
+/// Build a MIME message. Saved copies keep the Bcc header so the file shows
+/// everyone it was meant for.
+fn build_mime(message: &EmailMessage, keep_bcc: bool) -> Result<Message, String> {
+    let mailbox = |address: &String| {
+        address
+            .parse::<Mailbox>()
+            .map_err(|e| format!("Invalid email address '{}': {}", address, e))
+    };
+
+    let mut builder = Message::builder()
+        .from(mailbox(&message.from)?)
+        .subject(&message.subject)
+        .message_id(None);
+    for address in &message.to {
+        builder = builder.to(mailbox(address)?);
+    }
+    for address in &message.cc {
+        builder = builder.cc(mailbox(address)?);
+    }
+    for address in &message.bcc {
+        builder = builder.bcc(mailbox(address)?);
+    }
+    for address in &message.reply_to {
+        builder = builder.reply_to(mailbox(address)?);
+    }
+    if keep_bcc {
+        builder = builder.keep_bcc();
+    }
+
+    let body = match &message.html {
+        Some(html) => MultiPart::alternative_plain_html(message.text.clone(), html.clone()),
+        None => MultiPart::mixed().singlepart(SinglePart::plain(message.text.clone())),
+    };
+    let mut content = MultiPart::mixed().multipart(body);
+    for attachment in &message.attachments {
+        let content_type = ContentType::parse(&attachment.content_type).map_err(|e| {
+            format!(
+                "Invalid content type '{}' for {}: {}",
+                attachment.content_type, attachment.filename, e
+            )
+        })?;
+        content = content.singlepart(
+            MimeAttachment::new(attachment.filename.clone())
+                .body(attachment.content.clone(), content_type),
+        );
+    }
+
+    builder.multipart(content).map_err(|e| {
+        error!("Failed to build email: {}", e);
+        format!("Failed to build email: {}", e)
+    })
+}
+
+fn message_id(message: &Message) -> String {
+    message
+        .headers()
+        .get_raw("Message-ID")
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A folder in the temp directory, removed with its contents when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            Self(std::env::temp_dir().join(format!("mailer-{}", uuid::Uuid::new_v4())))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn message() -> EmailMessage {
+        EmailMessage {
+            from: "Timesheets <timesheets@example.com>".to_string(),
+            to: vec!["payroll@ufv.ca".to_string()],
+            cc: vec!["Sam Lee <sam.lee@ufv.ca>".to_string()],
+            bcc: vec!["records@example.com".to_string()],
+            reply_to: vec!["ada@student.ufv.ca".to_string()],
+            subject: "Timesheet 10/9 to 10/23".to_string(),
+            text: "Attached is my timesheet.".to_string(),
+            html: Some("<p>Attached is my timesheet.</p>".to_string()),
+            attachments: vec![EmailAttachment {
+                filename: "Timesheet.pdf".to_string(),
+                content_type: "application/pdf".to_string(),
+                content: b"%PDF-1.5\n%\xe2\xe3\xcf\xd3\n".to_vec(),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_a_readable_eml_file() {
+        let dir = TempDir::new();
+        let mailer = FileMailer::eml(&dir.0);
+
+        let path = mailer.send(&message()).await.unwrap();
+        assert!(path.starts_with(&*dir.0.to_string_lossy()) && path.ends_with(".eml"));
+
+        let eml = fs::read_to_string(&path).unwrap();
+        for header in [
+            "From: Timesheets <timesheets@example.com>\r\n",
+            "To: payroll@ufv.ca\r\n",
+            "Cc: \"Sam Lee\" <sam.lee@ufv.ca>\r\n",
+            "Bcc: records@example.com\r\n",
+            "Reply-To: ada@student.ufv.ca\r\n",
+            "Subject: Timesheet 10/9 to 10/23\r\n",
+            "Message-ID: <",
+        ] {
+            assert!(eml.contains(header), "missing {:?} in\n{}", header, eml);
+        }
+        assert!(eml.contains("Content-Type: text/plain; charset=utf-8"));
+        assert!(eml.contains("Attached is my timesheet."));
+        assert!(eml.contains("Content-Type: text/html; charset=utf-8"));
+        assert!(eml.contains("Content-Disposition: attachment; filename=\"Timesheet.pdf\""));
+        // Binary content is base64 encoded
+        assert!(eml.contains(
+            "Content-Transfer-Encoding: base64\r\n\r\nJVBERi0xLjUKJeLjz9MK\r\n"
+        ));
+
+        // Each message gets its own file
+        let second = mailer.send(&message()).await.unwrap();
+        assert_ne!(second, path);
+        assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn delivers_into_the_new_folder_of_a_maildir() {
+        let dir = TempDir::new();
+        let mailer = FileMailer::maildir(&dir.0);
+
+        let path = mailer.send(&message()).await.unwrap();
+        assert_eq!(
+            PathBuf::from(&path).parent().unwrap(),
+            dir.0.join("new").as_path()
+        );
+        let eml = fs::read_to_string(&path).unwrap();
+        assert!(eml.contains("Subject: Timesheet 10/9 to 10/23"));
+        assert_eq!(fs::read_dir(dir.0.join("tmp")).unwrap().count(), 0);
+        assert!(dir.0.join("cur").is_dir());
+    }
+
+    #[tokio::test]
+    async fn rejects_an_invalid_address_without_writing() {
+        let dir = TempDir::new();
+        let mailer = FileMailer::eml(&dir.0);
+        let message = EmailMessage {
+            cc: vec!["not an address".to_string()],
+            ..message()
+        };
+
+        let error = mailer.send(&message).await.unwrap_err();
+        assert!(
+            error.starts_with("Invalid email address 'not an address':"),
+            "{}",
+            error
+        );
+        assert!(!dir.0.exists());
+    }
+}
@@ -0,0 +1,325 @@
+use minijinja::{context, Environment};
+use serde::Serialize;
+use std::fs;
+use tracing::{error, info};
+
+use crate::helpers::pdf::TimesheetData;
+
+const DEFAULT_HTML: &str = include_str!("../../../templates/email.html");
+const DEFAULT_TEXT: &str = include_str!("../../../templates/email.txt");
+
+/// Jinja-style templates for the timesheet email body. The HTML template is
+/// auto-escaped; the text template is sent as the plain-text alternative.
+///
+/// Both templates, and the subject and file name templates on
+/// [`super::EmailConfig`], can use:
+///
+/// - `employee`
+/// - `period_start`, `period_end`: like "Oct 9, 2025"
+/// - `period_start_short`, `period_end_short`: like "10/9"
+/// - `period_start_iso`, `period_end_iso`: like "2025-10-09"
+/// - `total_hours`
+/// - `shifts`: each with `date`, `start`, `end`, `hours` and `notes`
+/// - `warnings`
+/// - `notion_url`
+///
+/// The period variables are `none` when the pay period is unknown.
+#[derive(Clone, Debug)]
+pub struct EmailTemplates {
+    pub html: String,
+    pub text: String,
+}
+
+/// A rendered email body
+#[derive(Clone, Debug)]
+pub struct EmailBody {
+    pub html: String,
+    pub text: String,
+}
+
+#[derive(Serialize)]
+struct Shift {
+    date: String,
+    start: String,
+    end: String,
+    hours: String,
+    notes: Option<String>,
+}
+
+impl Default for EmailTemplates {
+    fn default() -> Self {
+        Self {
+            html: DEFAULT_HTML.to_string(),
+            text: DEFAULT_TEXT.to_string(),
+        }
+    }
+}
+
+impl EmailTemplates {
+    /// Load templates from files, such as edited copies of
+    /// `templates/email.html` and `templates/email.txt`
+    pub fn from_files(html_path: &str, text_path: &str) -> Result<Self, String> {
+        let read = |path: &str| {
+            fs::read_to_string(path).map_err(|e| {
+                error!("Failed to read email template {}: {}", path, e);
+                format!("Failed to read email template {}: {}", path, e)
+            })
+        };
+
+        let templates = Self {
+            html: read(html_path)?,
+            text: read(text_path)?,
+        };
+        // Catch syntax errors now rather than when the timesheet is sent
+        templates.environment()?;
+
+        info!(
+            "Loaded email templates from {} and {}",
+            html_path, text_path
+        );
+        Ok(templates)
+    }
+
+    fn environment(&self) -> Result<Environment<'_>, String> {
+        let mut env = Environment::new();
+        env.add_template("email.html", &self.html)
+            .map_err(|e| format!("Invalid HTML email template: {}", e))?;
+        env.add_template("email.txt", &self.text)
+            .map_err(|e| format!("Invalid text email template: {}", e))?;
+        Ok(env)
+    }
+
+    /// Render both bodies for a timesheet
+    pub fn render(&self, data: &TimesheetData, notion_url: &str) -> Result<EmailBody, String> {
+        let env = self.environment()?;
+        let ctx = template_context(data, notion_url);
+
+        let render = |name: &str| {
+            env.get_template(name)
+                .and_then(|template| template.render(&ctx))
+                .map_err(|e| {
+                    error!("Failed to render email template {}: {}", name, e);
+                    format!("Failed to render email template {}: {}", name, e)
+                })
+        };
+
+        Ok(EmailBody {
+            html: render("email.html")?,
+            text: render("email.txt")?,
+        })
+    }
+}
+
+/// Render a one-line template such as a subject or file name
+pub(super) fn render_line(
+    template: &str,
+    data: &TimesheetData,
+    notion_url: &str,
+) -> Result<String, String> {
+    Environment::new()
+        .render_str(template, template_context(data, notion_url))
+        .map(|line| line.trim().to_string())
+        .map_err(|e| {
+            error!("Failed to render template '{}': {}", template, e);
+            format!("Failed to render template '{}': {}", template, e)
+        })
+}
+
+fn template_context(data: &TimesheetData, notion_url: &str) -> minijinja::Value {
+    let period = |format: &str| {
+        data.period.map(|(start, end)| {
+            (
+                start.format(format).to_string(),
+                end.format(format).to_string(),
+            )
+        })
+    };
+    let (period_start, period_end) = period("%b %-d, %Y").unzip();
+    let (period_start_short, period_end_short) = period("%-m/%-d").unzip();
+    let (period_start_iso, period_end_iso) = period("%Y-%m-%d").unzip();
+
+    let shifts = data
+        .entries
+        .iter()
+        .map(|entry| Shift {
+            date: data
+                .entry_date(entry)
+                .map(|date| date.format("%a %b %-d").to_string())
+                .unwrap_or(format!("{}/{}", entry.month, entry.day)),
+            start: entry.start.clone(),
+            end: entry.end.clone(),
+            hours: entry.paid_hours.to_string(),
+            notes: entry.notes.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    context! {
+        employee => data.employee,
+        period_start,
+        period_end,
+        period_start_short,
+        period_end_short,
+        period_start_iso,
+        period_end_iso,
+        total_hours => data.total_hours.to_string(),
+        shifts,
+        warnings => data.warnings(),
+        notion_url,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::pdf::TimesheetEntry;
+    use chrono::NaiveDate;
+    use std::path::PathBuf;
+
+    /// A template file in the temp folder, removed when dropped
+    struct TempTemplate(PathBuf);
+
+    impl TempTemplate {
+        fn new(extension: &str, contents: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "email-{}.{}",
+                uuid::Uuid::new_v4(),
+                extension
+            ));
+            fs::write(&path, contents).unwrap();
+            Self(path)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TempTemplate {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn entry(day: u32, start: &str, end: &str, paid_hours: f64) -> TimesheetEntry {
+        TimesheetEntry {
+            month: 10,
+            day,
+            start: start.to_string(),
+            end: end.to_string(),
+            paid_hours,
+            notes: None,
+        }
+    }
+
+    fn timesheet(entries: Vec<TimesheetEntry>) -> TimesheetData {
+        TimesheetData {
+            total_hours: entries.iter().map(|entry| entry.paid_hours).sum(),
+            entries,
+            employee: Some("Ada <Lovelace>".to_string()),
+            period: Some((
+                NaiveDate::from_ymd_opt(2026, 10, 9).unwrap(),
+                NaiveDate::from_ymd_opt(2026, 10, 23).unwrap(),
+            )),
+        }
+    }
+
+    #[test]
+    fn renders_the_shift_table_in_both_bodies() {
+        let data = timesheet(vec![
+            entry(12, "9:00 AM", "12:00 PM", 3.0),
+            entry(14, "1:00 PM", "3:30 PM", 2.5),
+        ]);
+        let body = EmailTemplates::default()
+            .render(&data, "https://www.notion.so/shifts")
+            .unwrap();
+
+        assert!(body.html.contains(
+            "Attached is Ada &lt;Lovelace&gt;'s timesheet for Oct 9, 2026 to Oct 23, 2026."
+        ));
+        assert!(body.html.contains(
+            "<td>Wed Oct 14</td>\n        <td>1:00 PM</td>\n        <td>3:30 PM</td>"
+        ));
+        assert!(body.html.contains("<td style=\"text-align: right;\">5.5</td>"));
+        // Auto-escaping turns slashes into entities, which browsers decode
+        assert!(body.html.contains("<a href=\"https:&#x2f;&#x2f;www.notion.so&#x2f;shifts\">"));
+        assert!(!body.html.contains("Please check"));
+
+        assert!(body.text.contains(
+            "Attached is Ada <Lovelace>'s timesheet for Oct 9, 2026 to Oct 23, 2026."
+        ));
+        assert!(body.text.contains(
+            "Mon Oct 12  9:00 AM - 12:00 PM  3 h\nWed Oct 14  1:00 PM - 3:30 PM  2.5 h\n"
+        ));
+        assert!(body.text.contains("Total: 5.5 h"));
+        assert!(!body.text.contains("Please check"));
+    }
+
+    #[test]
+    fn lists_warnings_in_both_bodies() {
+        let data = timesheet(vec![
+            entry(12, "9:00 AM", "12:00 PM", 3.0),
+            entry(30, "9:00 AM", "", 0.0),
+        ]);
+        let body = EmailTemplates::default().render(&data, "").unwrap();
+
+        let outside = "Shift on 2026-10-30 is outside the pay period 2026-10-09 to 2026-10-23";
+        assert!(body.html.contains(
+            "<p><strong>Please check:</strong></p>\n  <ul>\n    \
+             <li>Shift on 10&#x2f;30 9:00 AM has no paid hours</li>"
+        ));
+        assert!(body.html.contains(&format!("<li>{}</li>\n  </ul>", outside)));
+        assert_eq!(body.html.matches("<li>").count(), 3);
+        assert!(body.text.contains(&format!(
+            "Please check:\n\
+             - Shift on 10/30 9:00 AM has no paid hours\n\
+             - Shift on 10/30 is missing a start or finish time\n\
+             - {}\n",
+            outside
+        )));
+    }
+
+    #[test]
+    fn leaves_out_the_period_when_it_is_unknown() {
+        let mut data = timesheet(vec![entry(12, "9:00 AM", "12:00 PM", 3.0)]);
+        data.employee = None;
+        data.period = None;
+        let body = EmailTemplates::default().render(&data, "").unwrap();
+
+        assert!(body.text.contains("Attached is my timesheet.\n"));
+        assert!(body.text.contains("10/12  9:00 AM - 12:00 PM  3 h"));
+    }
+
+    #[test]
+    fn custom_template_files_replace_the_wording() {
+        let html = TempTemplate::new(
+            "html",
+            "<p>Hi payroll, {{ employee }} worked {{ total_hours }} hours \
+             from {{ period_start_iso }}.</p>",
+        );
+        let text = TempTemplate::new(
+            "txt",
+            "{% for shift in shifts %}{{ shift.date }}: {{ shift.hours }}\n{% endfor %}",
+        );
+        let templates = EmailTemplates::from_files(html.path(), text.path()).unwrap();
+
+        let data = timesheet(vec![entry(12, "9:00 AM", "12:00 PM", 3.0)]);
+        let body = templates.render(&data, "").unwrap();
+        assert_eq!(
+            body.html,
+            "<p>Hi payroll, Ada &lt;Lovelace&gt; worked 3 hours from 2026-10-09.</p>"
+        );
+        assert_eq!(body.text, "Mon Oct 12: 3\n");
+    }
+
+    #[test]
+    fn rejects_a_template_file_that_does_not_parse() {
+        let html = TempTemplate::new("html", "<p>{% if employee %}unclosed</p>");
+        let text = TempTemplate::new("txt", "Hello");
+        let error = EmailTemplates::from_files(html.path(), text.path()).unwrap_err();
+        assert!(error.starts_with("Invalid HTML email template:"), "{}", error);
+
+        let error =
+            EmailTemplates::from_files("/nonexistent/email.html", text.path()).unwrap_err();
+        assert!(error.starts_with("Failed to read email template /nonexistent/email.html:"));
+    }
+}
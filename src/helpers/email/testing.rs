@@ -0,0 +1,30 @@
+//! A mailer for tests that need to see what was sent
+
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+
+use super::{EmailMessage, Mailer};
+
+/// Keeps every message instead of sending it. Clones share the same list,
+/// so a test can keep one after handing the mailer to a service.
+#[derive(Clone, Default)]
+pub(crate) struct RecordingMailer(Arc<Mutex<Vec<EmailMessage>>>);
+
+impl RecordingMailer {
+    pub(crate) fn sent(&self) -> Vec<EmailMessage> {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub(crate) fn last(&self) -> EmailMessage {
+        self.sent().pop().unwrap()
+    }
+}
+
+#[async_trait]
+impl Mailer for RecordingMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<String, String> {
+        let mut sent = self.0.lock().unwrap();
+        sent.push(message.clone());
+        Ok(format!("email-{}", sent.len()))
+    }
+}
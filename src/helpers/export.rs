@@ -0,0 +1,353 @@
+use chrono::{Datelike, NaiveDate};
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook, Worksheet, XlsxError};
+use std::fmt;
+use std::str::FromStr;
+use tracing::{error, info};
+
+use crate::helpers::pdf::{TimesheetData, TimesheetEntry};
+
+/// Spreadsheet-friendly formats a timesheet can be exported to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Xlsx,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Xlsx => {
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+            }
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "xlsx" => Ok(ExportFormat::Xlsx),
+            other => Err(format!("Unknown export format '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// Serialize a timesheet in the given format
+pub fn export_timesheet(data: &TimesheetData, format: ExportFormat) -> Result<Vec<u8>, String> {
+    let bytes = match format {
+        ExportFormat::Csv => timesheet_csv(data),
+        ExportFormat::Xlsx => timesheet_xlsx(data),
+    }?;
+
+    info!(
+        "Exported timesheet as {}, size: {} bytes",
+        format,
+        bytes.len()
+    );
+    Ok(bytes)
+}
+
+/// One row per entry. The `date` column is only filled when the pay period
+/// is known; `month` and `day` are always present. Text that a spreadsheet
+/// would run as a formula is prefixed with `'`.
+pub fn timesheet_csv(data: &TimesheetData) -> Result<Vec<u8>, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+
+    let write = |writer: &mut csv::Writer<Vec<u8>>, record: &[String]| {
+        writer.write_record(record).map_err(|e| {
+            error!("Failed to write CSV record: {}", e);
+            format!("Failed to write CSV record: {}", e)
+        })
+    };
+
+    write(
+        &mut writer,
+        &[
+            "date",
+            "month",
+            "day",
+            "start",
+            "end",
+            "paid_hours",
+            "notes",
+        ]
+        .map(str::to_string),
+    )?;
+
+    for entry in &data.entries {
+        write(
+            &mut writer,
+            &[
+                entry_date(data, entry),
+                entry.month.to_string(),
+                entry.day.to_string(),
+                spreadsheet_text(&entry.start),
+                spreadsheet_text(&entry.end),
+                entry.paid_hours.to_string(),
+                spreadsheet_text(entry.notes.as_deref().unwrap_or_default()),
+            ],
+        )?;
+    }
+
+    writer.into_inner().map_err(|e| {
+        error!("Failed to finish CSV: {}", e);
+        format!("Failed to finish CSV: {}", e)
+    })
+}
+
+/// A workbook with an `Entries` sheet of shifts and a `Summary` sheet with
+/// the employee, pay period, weekly hours and total
+pub fn timesheet_xlsx(data: &TimesheetData) -> Result<Vec<u8>, String> {
+    build_workbook(data).map_err(|e| {
+        error!("Failed to build XLSX workbook: {}", e);
+        format!("Failed to build XLSX workbook: {}", e)
+    })
+}
+
+fn build_workbook(data: &TimesheetData) -> Result<Vec<u8>, XlsxError> {
+    let bold = Format::new().set_bold();
+    let date_format = Format::new().set_num_format("yyyy-mm-dd");
+    let hours_format = Format::new().set_num_format("0.00");
+    let bold_hours = Format::new().set_bold().set_num_format("0.00");
+
+    let mut workbook = Workbook::new();
+
+    let entries = workbook.add_worksheet().set_name("Entries")?;
+    for (column, heading) in ["Date", "Start", "Finish", "Paid hours", "Notes"]
+        .iter()
+        .enumerate()
+    {
+        entries.write_string_with_format(0, column as u16, *heading, &bold)?;
+    }
+    entries.set_freeze_panes(1, 0)?;
+
+    let mut row = 1;
+    for entry in &data.entries {
+        write_date(entries, row, 0, data, entry, &date_format)?;
+        entries.write_string(row, 1, &entry.start)?;
+        entries.write_string(row, 2, &entry.end)?;
+        entries.write_number_with_format(row, 3, entry.paid_hours, &hours_format)?;
+        if let Some(notes) = &entry.notes {
+            entries.write_string(row, 4, notes)?;
+        }
+        row += 1;
+    }
+    entries.write_string_with_format(row, 2, "Total", &bold)?;
+    entries.write_number_with_format(row, 3, data.total_hours, &bold_hours)?;
+    entries.set_column_width(0, 12)?;
+    entries.set_column_width(3, 11)?;
+    entries.set_column_width(4, 60)?;
+
+    let summary = workbook.add_worksheet().set_name("Summary")?;
+    summary.write_string_with_format(0, 0, "Employee", &bold)?;
+    summary.write_string(0, 1, data.employee.as_deref().unwrap_or_default())?;
+    summary.write_string_with_format(1, 0, "Pay period start", &bold)?;
+    summary.write_string_with_format(2, 0, "Pay period end", &bold)?;
+    if let Some((start, end)) = data.period {
+        summary.write_datetime_with_format(1, 1, excel_date(start)?, &date_format)?;
+        summary.write_datetime_with_format(2, 1, excel_date(end)?, &date_format)?;
+    }
+    summary.write_string_with_format(3, 0, "Shifts", &bold)?;
+    summary.write_number(3, 1, data.entries.len() as f64)?;
+    summary.write_string_with_format(4, 0, "Total hours", &bold)?;
+    summary.write_number_with_format(4, 1, data.total_hours, &bold_hours)?;
+
+    let weeks = data.weekly_hours();
+    if !weeks.is_empty() {
+        summary.write_string_with_format(6, 0, "Week of", &bold)?;
+        summary.write_string_with_format(6, 1, "Hours", &bold)?;
+        for (index, (week_start, hours)) in weeks.into_iter().enumerate() {
+            let row = 7 + index as u32;
+            summary.write_datetime_with_format(row, 0, excel_date(week_start)?, &date_format)?;
+            summary.write_number_with_format(row, 1, hours, &hours_format)?;
+        }
+    }
+    summary.set_column_width(0, 18)?;
+    summary.set_column_width(1, 24)?;
+
+    workbook.save_to_buffer()
+}
+
+/// Quote text starting with `=`, `+`, `-` or `@` so spreadsheets opening the
+/// CSV show it instead of evaluating it
+fn spreadsheet_text(text: &str) -> String {
+    if text.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", text)
+    } else {
+        text.to_string()
+    }
+}
+
+fn entry_date(data: &TimesheetData, entry: &TimesheetEntry) -> String {
+    data.entry_date(entry)
+        .map(|date| date.to_string())
+        .unwrap_or_default()
+}
+
+/// Write a real date cell when the year is known, otherwise `month/day` text
+fn write_date(
+    sheet: &mut Worksheet,
+    row: u32,
+    column: u16,
+    data: &TimesheetData,
+    entry: &TimesheetEntry,
+    format: &Format,
+) -> Result<(), XlsxError> {
+    match data.entry_date(entry) {
+        Some(date) => {
+            sheet.write_datetime_with_format(row, column, excel_date(date)?, format)?;
+        }
+        None => {
+            sheet.write_string(row, column, format!("{}/{}", entry.month, entry.day))?;
+        }
+    }
+    Ok(())
+}
+
+fn excel_date(date: NaiveDate) -> Result<ExcelDateTime, XlsxError> {
+    ExcelDateTime::from_ymd(date.year() as u16, date.month() as u8, date.day() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn entry(day: u32, paid_hours: f64, notes: Option<&str>) -> TimesheetEntry {
+        TimesheetEntry {
+            month: 10,
+            day,
+            start: "9:00 AM".to_string(),
+            end: "12:00 PM".to_string(),
+            paid_hours,
+            notes: notes.map(str::to_string),
+        }
+    }
+
+    fn timesheet(entries: Vec<TimesheetEntry>) -> TimesheetData {
+        TimesheetData {
+            total_hours: entries.iter().map(|entry| entry.paid_hours).sum(),
+            entries,
+            employee: Some("Ada Lovelace".to_string()),
+            period: Some((
+                NaiveDate::from_ymd_opt(2026, 10, 9).unwrap(),
+                NaiveDate::from_ymd_opt(2026, 10, 23).unwrap(),
+            )),
+        }
+    }
+
+    fn csv_rows(data: &TimesheetData) -> Vec<Vec<String>> {
+        let csv = timesheet_csv(data).unwrap();
+        csv::Reader::from_reader(csv.as_slice())
+            .records()
+            .map(|record| record.unwrap().iter().map(str::to_string).collect())
+            .collect()
+    }
+
+    /// The XML of one part of an XLSX file, e.g. `xl/sharedStrings.xml`
+    fn xlsx_part(xlsx: &[u8], name: &str) -> String {
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(xlsx)).unwrap();
+        let mut part = String::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_string(&mut part)
+            .unwrap();
+        part
+    }
+
+    #[test]
+    fn quotes_notes_with_commas_quotes_and_newlines() {
+        let data = timesheet(vec![entry(
+            12,
+            3.0,
+            Some("Lab 2, \"intro\" session\nthen marking"),
+        )]);
+        let csv = String::from_utf8(timesheet_csv(&data).unwrap()).unwrap();
+
+        assert!(csv.starts_with("date,month,day,start,end,paid_hours,notes\n"));
+        assert!(csv.contains("\"Lab 2, \"\"intro\"\" session\nthen marking\""));
+        assert_eq!(
+            csv_rows(&data),
+            [[
+                "2026-10-12",
+                "10",
+                "12",
+                "9:00 AM",
+                "12:00 PM",
+                "3",
+                "Lab 2, \"intro\" session\nthen marking",
+            ]]
+        );
+    }
+
+    #[test]
+    fn prefixes_notes_a_spreadsheet_would_run_as_a_formula() {
+        let data = timesheet(vec![
+            entry(12, 1.0, Some("=HYPERLINK(\"http://example.com\")")),
+            entry(13, 1.0, Some("+1")),
+            entry(14, 1.0, Some("-1 break")),
+            entry(15, 1.0, Some("@SUM(A1)")),
+            entry(16, 1.0, Some("Tutoring = 1 hour")),
+            entry(19, 1.0, None),
+        ]);
+        let notes: Vec<String> = csv_rows(&data).into_iter().map(|row| row[6].clone()).collect();
+        assert_eq!(
+            notes,
+            [
+                "'=HYPERLINK(\"http://example.com\")",
+                "'+1",
+                "'-1 break",
+                "'@SUM(A1)",
+                "Tutoring = 1 hour",
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_the_date_empty_without_a_pay_period() {
+        let mut data = timesheet(vec![entry(12, 3.0, None)]);
+        data.period = None;
+        assert_eq!(csv_rows(&data)[0][..3], ["", "10", "12"]);
+    }
+
+    #[test]
+    fn builds_a_workbook_with_entries_and_a_summary() {
+        let data = timesheet(vec![
+            entry(12, 3.0, Some("=1+1")),
+            entry(20, 2.5, Some("Marking")),
+        ]);
+        let xlsx = export_timesheet(&data, ExportFormat::Xlsx).unwrap();
+
+        let workbook = xlsx_part(&xlsx, "xl/workbook.xml");
+        assert!(workbook.contains("name=\"Entries\""));
+        assert!(workbook.contains("name=\"Summary\""));
+
+        // Cells are typed, so notes are stored as text and never as formulas
+        let strings = xlsx_part(&xlsx, "xl/sharedStrings.xml");
+        for text in ["Paid hours", "=1+1", "Marking", "Ada Lovelace", "Week of"] {
+            assert!(strings.contains(&format!(">{}<", text)), "missing {}", text);
+        }
+        let entries = xlsx_part(&xlsx, "xl/worksheets/sheet1.xml");
+        assert!(!entries.contains("<f>"));
+        assert!(entries.contains("<v>5.5</v>"));
+    }
+}
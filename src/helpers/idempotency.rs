@@ -0,0 +1,357 @@
+use chrono::NaiveDate;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, fs, path::PathBuf, sync::Mutex};
+use tracing::{error, info};
+
+use crate::helpers::pdf::TimesheetData;
+
+/// Remembers which submissions were already emailed, so webhook retries and
+/// unchanged data don't send payroll another copy. Values are email IDs.
+pub trait IdempotencyStore: Send + Sync {
+    fn get(&self, key: &str) -> Result<Option<String>, String>;
+    fn put(&self, key: &str, email_id: &str) -> Result<(), String>;
+
+    /// Whether keys survive a restart. The scheduler only catches up on
+    /// missed rules when they do, so it can tell what already ran.
+    fn persistent(&self) -> bool {
+        false
+    }
+}
+
+/// Store key for a Notion automation event
+pub fn event_key(event_id: &str) -> String {
+    format!("event:{}", event_id)
+}
+
+/// Store key for any submission of a pay period, used to tell whether the
+/// period still needs submitting
+pub fn period_key(period: (NaiveDate, NaiveDate)) -> String {
+    format!("period:{}:{}", period.0, period.1)
+}
+
+/// Store key for a scheduled rule having run for a pay period, so catching
+/// up after a restart doesn't run it twice
+pub fn schedule_key(rule: &str, period: (NaiveDate, NaiveDate)) -> String {
+    format!("schedule:{}:{}:{}", rule, period.0, period.1)
+}
+
+/// Store key for the pay period and entries of a timesheet. The employee
+/// name is left out, so only changes to the hours count as new data.
+pub fn content_key(data: &TimesheetData) -> String {
+    let content = json!({
+        "period": data.period,
+        "entries": data.entries,
+    });
+    format!("content:{}", sha256_hex(content.to_string().as_bytes()))
+}
+
+/// Store key for an alert about changes made after a submission, so the
+/// same changes are only reported once
+pub fn late_edit_key(submission_id: &str, changes: &str) -> String {
+    format!("late-edit:{}:{}", submission_id, sha256_hex(changes.as_bytes()))
+}
+
+/// The email that last sent this exact data for `period`. Data that matches
+/// an older submission but not the latest one, such as hours changed and
+/// then changed back, differs from payroll's copy and has to be sent again.
+pub fn sent_unchanged(
+    store: &dyn IdempotencyStore,
+    data: &TimesheetData,
+    period: (NaiveDate, NaiveDate),
+) -> Result<Option<String>, String> {
+    let Some(latest) = store.get(&period_key(period))? else {
+        return Ok(None);
+    };
+    let sent = store.get(&content_key(data))?;
+    Ok(sent.filter(|email_id| *email_id == latest))
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Keeps keys for the life of the process
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: Mutex<HashMap<String, String>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IdempotencyStore for MemoryStore {
+    fn get(&self, key: &str) -> Result<Option<String>, String> {
+        let entries = self
+            .entries
+            .lock()
+            .map_err(|_| "Idempotency store lock poisoned".to_string())?;
+        Ok(entries.get(key).cloned())
+    }
+
+    fn put(&self, key: &str, email_id: &str) -> Result<(), String> {
+        let mut entries = self
+            .entries
+            .lock()
+            .map_err(|_| "Idempotency store lock poisoned".to_string())?;
+        entries.insert(key.to_string(), email_id.to_string());
+        Ok(())
+    }
+}
+
+/// Keeps keys in a JSON file so they survive restarts
+pub struct FileStore {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, String>>,
+}
+
+impl FileStore {
+    /// Open the store at `path`, starting empty if the file doesn't exist yet
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let entries = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| {
+                error!("Idempotency file {} is corrupt: {}", path.display(), e);
+                format!("Idempotency file {} is corrupt: {}", path.display(), e)
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                error!("Failed to read idempotency file {}: {}", path.display(), e);
+                return Err(format!(
+                    "Failed to read idempotency file {}: {}",
+                    path.display(),
+                    e
+                ));
+            }
+        };
+
+        info!(
+            "Loaded {} idempotency keys from {}",
+            entries.len(),
+            path.display()
+        );
+        Ok(Self {
+            path,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Rewrite the whole file, replacing it in one step so a crash can't
+    /// leave it half written
+    fn save(&self, entries: &HashMap<String, String>) -> Result<(), String> {
+        let json = serde_json::to_vec_pretty(entries)
+            .map_err(|e| format!("Failed to serialize idempotency keys: {}", e))?;
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, json)
+            .and_then(|_| fs::rename(&tmp_path, &self.path))
+            .map_err(|e| {
+                error!(
+                    "Failed to write idempotency file {}: {}",
+                    self.path.display(),
+                    e
+                );
+                format!(
+                    "Failed to write idempotency file {}: {}",
+                    self.path.display(),
+                    e
+                )
+            })
+    }
+}
+
+impl IdempotencyStore for FileStore {
+    fn get(&self, key: &str) -> Result<Option<String>, String> {
+        let entries = self
+            .entries
+            .lock()
+            .map_err(|_| "Idempotency store lock poisoned".to_string())?;
+        Ok(entries.get(key).cloned())
+    }
+
+    fn put(&self, key: &str, email_id: &str) -> Result<(), String> {
+        let mut entries = self
+            .entries
+            .lock()
+            .map_err(|_| "Idempotency store lock poisoned".to_string())?;
+        entries.insert(key.to_string(), email_id.to_string());
+        self.save(&entries)
+    }
+
+    fn persistent(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::pdf::TimesheetEntry;
+    use std::{sync::Arc, thread};
+
+    /// A path in the temp folder for a store file, removed when dropped
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new() -> Self {
+            Self(std::env::temp_dir().join(format!("idempotency-{}.json", uuid::Uuid::new_v4())))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn file_store_keeps_keys_across_reload() {
+        let file = TempFile::new();
+        let store = FileStore::open(&file.0).unwrap();
+        assert_eq!(store.get("event:1").unwrap(), None);
+        store.put("event:1", "email-1").unwrap();
+        store
+            .put("period:2026-10-09:2026-10-23", "email-1")
+            .unwrap();
+        drop(store);
+
+        let store = FileStore::open(&file.0).unwrap();
+        assert_eq!(store.get("event:1").unwrap().as_deref(), Some("email-1"));
+        assert_eq!(
+            store
+                .get("period:2026-10-09:2026-10-23")
+                .unwrap()
+                .as_deref(),
+            Some("email-1")
+        );
+        assert!(store.persistent());
+    }
+
+    #[test]
+    fn file_store_replaces_a_key_already_stored() {
+        let file = TempFile::new();
+        let store = FileStore::open(&file.0).unwrap();
+        store
+            .put("schedule:reminder:2026-10-09:2026-10-23", "email-1")
+            .unwrap();
+        store
+            .put("schedule:reminder:2026-10-09:2026-10-23", "email-2")
+            .unwrap();
+        assert_eq!(
+            store
+                .get("schedule:reminder:2026-10-09:2026-10-23")
+                .unwrap()
+                .as_deref(),
+            Some("email-2")
+        );
+        drop(store);
+
+        let store = FileStore::open(&file.0).unwrap();
+        assert_eq!(
+            store
+                .get("schedule:reminder:2026-10-09:2026-10-23")
+                .unwrap()
+                .as_deref(),
+            Some("email-2")
+        );
+    }
+
+    #[test]
+    fn file_store_keeps_keys_written_at_once() {
+        let file = TempFile::new();
+        let store = Arc::new(FileStore::open(&file.0).unwrap());
+
+        let writers = (0..8)
+            .map(|writer| {
+                let store = store.clone();
+                thread::spawn(move || {
+                    for key in 0..10 {
+                        store
+                            .put(&format!("event:{}-{}", writer, key), "email")
+                            .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        drop(store);
+
+        let store = FileStore::open(&file.0).unwrap();
+        for writer in 0..8 {
+            for key in 0..10 {
+                assert!(
+                    store
+                        .get(&format!("event:{}-{}", writer, key))
+                        .unwrap()
+                        .is_some()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn file_store_refuses_a_corrupt_file() {
+        let file = TempFile::new();
+        fs::write(&file.0, b"{not json").unwrap();
+        let error = FileStore::open(&file.0).err().unwrap();
+        assert!(error.contains("corrupt"), "{}", error);
+    }
+
+    fn timesheet(hours: f64) -> TimesheetData {
+        TimesheetData::try_from(vec![TimesheetEntry {
+            month: 10,
+            day: 12,
+            start: "09:00".to_string(),
+            end: "12:00".to_string(),
+            paid_hours: hours,
+            notes: None,
+        }])
+        .unwrap()
+    }
+
+    /// Record a submission the way the service does once it is sent
+    fn send(
+        store: &MemoryStore,
+        data: &TimesheetData,
+        period: (NaiveDate, NaiveDate),
+        email_id: &str,
+    ) {
+        store.put(&content_key(data), email_id).unwrap();
+        store.put(&period_key(period), email_id).unwrap();
+    }
+
+    #[test]
+    fn resends_data_changed_back_to_an_earlier_version() {
+        let store = MemoryStore::new();
+        let period = (
+            NaiveDate::from_ymd_opt(2026, 10, 9).unwrap(),
+            NaiveDate::from_ymd_opt(2026, 10, 23).unwrap(),
+        );
+        let (a, b) = (timesheet(3.0), timesheet(4.0));
+        assert_eq!(sent_unchanged(&store, &a, period).unwrap(), None);
+
+        send(&store, &a, period, "email-a");
+        assert_eq!(
+            sent_unchanged(&store, &a, period).unwrap().as_deref(),
+            Some("email-a")
+        );
+        assert_eq!(sent_unchanged(&store, &b, period).unwrap(), None);
+
+        send(&store, &b, period, "email-b");
+        // Payroll's latest copy is B, so A is a change again
+        assert_eq!(sent_unchanged(&store, &a, period).unwrap(), None);
+
+        send(&store, &a, period, "email-a2");
+        assert_eq!(
+            sent_unchanged(&store, &a, period).unwrap().as_deref(),
+            Some("email-a2")
+        );
+    }
+}
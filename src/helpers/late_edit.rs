@@ -0,0 +1,274 @@
+use chrono::{DateTime, Duration, DurationRound, Local, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::{
+    helpers::{
+        amendment::REVISED,
+        approval::{escape_html, page},
+        period::PayPeriod,
+    },
+    models::notion::Page,
+};
+
+pub const AMEND: &str = "amend";
+
+/// Watch submitted pay periods for shifts edited in Notion afterwards. With
+/// this set, the employee is emailed what changed and a link to send a
+/// revised timesheet. Needs a submission archive.
+#[derive(Clone, Debug)]
+pub struct LateEditConfig {
+    /// Employee's address for alerts, e.g. `Alex Doe <alex.doe@student.ufv.ca>`
+    pub recipient: String,
+    /// Public URL the service's router is mounted at, used to build links,
+    /// e.g. `https://example.com/timesheet`
+    pub base_url: String,
+    /// Key for signing amend links
+    pub secret: String,
+    /// How long amend links stay valid
+    pub link_ttl: Duration,
+    /// How often to check on a timer. With `None`, only webhook deliveries
+    /// trigger a check.
+    pub check_every: Option<std::time::Duration>,
+    /// How many pay periods before the current one to watch
+    pub periods_back: usize,
+}
+
+impl LateEditConfig {
+    /// Check hourly and on webhooks, watching the current and the two
+    /// previous pay periods, with links that stay valid for a week
+    pub fn new(
+        recipient: impl Into<String>,
+        base_url: impl Into<String>,
+        secret: impl Into<String>,
+    ) -> Self {
+        Self {
+            recipient: recipient.into(),
+            base_url: base_url.into(),
+            secret: secret.into(),
+            link_ttl: Duration::days(7),
+            check_every: Some(std::time::Duration::from_secs(60 * 60)),
+            periods_back: 2,
+        }
+    }
+
+    /// Link to the page where the employee confirms an amendment
+    pub fn link(&self, token: &str) -> String {
+        format!(
+            "{}/timesheet-amend?token={}",
+            self.base_url.trim_end_matches('/'),
+            token
+        )
+    }
+
+    /// The pay periods to watch, newest first
+    pub fn periods(&self, current: PayPeriod) -> Vec<PayPeriod> {
+        std::iter::successors(Some(current), |period| Some(period.previous()))
+            .take(self.periods_back + 1)
+            .collect()
+    }
+}
+
+/// A Notion page edited after its pay period was submitted
+#[derive(Clone, Debug, Serialize)]
+pub struct LateEdit {
+    pub page_id: String,
+    pub url: String,
+    pub edited_at: DateTime<Utc>,
+}
+
+/// How a submitted pay period differs from Notion now
+#[derive(Clone, Debug, Serialize)]
+pub struct Drift {
+    /// The latest submission payroll received for the period
+    pub submission_id: String,
+    pub period: (NaiveDate, NaiveDate),
+    pub submitted_at: DateTime<Utc>,
+    pub late_edits: Vec<LateEdit>,
+    /// What an amendment would change, see [`crate::helpers::amendment::changes_text`].
+    /// `None` when the entries still match, e.g. when only other properties
+    /// were edited.
+    pub changes: Option<String>,
+}
+
+impl Drift {
+    pub fn needs_amendment(&self) -> bool {
+        self.changes.is_some()
+    }
+}
+
+/// Pages edited after `rendered_at`, when the submitted entries were read.
+/// Notion only reports edit times to the minute, so edits in that minute
+/// count too.
+pub fn late_edits(pages: &[Page], rendered_at: DateTime<Utc>) -> Vec<LateEdit> {
+    let since = rendered_at
+        .duration_trunc(Duration::minutes(1))
+        .unwrap_or(rendered_at);
+
+    pages
+        .iter()
+        .filter_map(|page| {
+            let edited_at = page.last_edited_time.parse::<DateTime<Utc>>().ok()?;
+            (edited_at >= since).then(|| LateEdit {
+                page_id: page.id.clone(),
+                url: page.url.clone(),
+                edited_at,
+            })
+        })
+        .collect()
+}
+
+pub fn alert_subject(period: &PayPeriod) -> String {
+    format!(
+        "Timesheet for {} to {} changed after it was sent",
+        period.start.format("%-m/%-d"),
+        period.end.format("%-m/%-d")
+    )
+}
+
+pub fn alert_text(drift: &Drift, amend_url: &str) -> String {
+    let pages = drift
+        .late_edits
+        .iter()
+        .map(|edit| {
+            format!(
+                "- {} (edited {})",
+                edit.url,
+                edit.edited_at
+                    .with_timezone(&Local)
+                    .format("%b %-d at %H:%M")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let pages = if pages.is_empty() {
+        "Shifts were deleted or moved out of the period.".to_string()
+    } else {
+        format!("Edited shifts:\n{}", pages)
+    };
+
+    format!(
+        "Your timesheet for {} was sent on {}, but has changed in Notion since.\n\n\
+         {}\n\n{}\n\n\
+         To send payroll a revised timesheet with these changes: {}\n\n\
+         If the edits were a mistake, undo them in Notion and ignore this email.",
+        PayPeriod::from(drift.period),
+        drift
+            .submitted_at
+            .with_timezone(&Local)
+            .format("%b %-d, %Y at %H:%M"),
+        pages,
+        drift.changes.as_deref().unwrap_or_default(),
+        amend_url
+    )
+}
+
+/// Page where the employee confirms sending an amendment. The link only
+/// shows this form, so mail scanners that follow links can't send anything.
+pub fn amend_page(token: &str, subject: &str, changes: &str) -> String {
+    page(
+        subject,
+        &format!(
+            "<p>A timesheet marked {} will be sent with these changes:</p>\
+             <pre>{}</pre>\
+             <form method=\"post\" action=\"timesheet-amend\">\
+             <input type=\"hidden\" name=\"token\" value=\"{}\">\
+             <button type=\"submit\">Send revised timesheet</button></form>",
+            REVISED,
+            escape_html(changes),
+            escape_html(token)
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A Notion shift page last edited at `last_edited_time`
+    fn shift_page(id: &str, last_edited_time: &str) -> Page {
+        let property = |kind: &str, value: serde_json::Value| {
+            let mut property = json!({ "id": "x", "type": kind });
+            property[kind] = value;
+            property
+        };
+        serde_json::from_value(json!({
+            "object": "page",
+            "id": id,
+            "created_time": "2026-10-12T16:00:00.000Z",
+            "last_edited_time": last_edited_time,
+            "url": format!("https://www.notion.so/{}", id),
+            "properties": {
+                "start and end": property("date", json!({
+                    "start": "2026-10-12T09:00:00.000-07:00",
+                    "end": "2026-10-12T12:00:00.000-07:00",
+                    "time_zone": null,
+                })),
+                "Billable Hours": property("formula", json!({ "type": "number", "number": 3.0 })),
+                "Workplace": property("select", json!({ "id": "w", "name": "SASI", "color": "" })),
+                "Duration": property("formula", json!({ "type": "number", "number": 3.0 })),
+                "notes": property("rich_text", json!([])),
+            },
+        }))
+        .unwrap()
+    }
+
+    fn edited_ids(pages: &[Page], rendered_at: &str) -> Vec<String> {
+        late_edits(pages, rendered_at.parse().unwrap())
+            .into_iter()
+            .map(|edit| edit.page_id)
+            .collect()
+    }
+
+    #[test]
+    fn counts_edits_earlier_in_the_minute_of_rendering() {
+        // Notion rounds this edit down to 17:43:00, though it may have been
+        // made after the entries were read at 17:43:30
+        let pages = [shift_page("same-minute", "2026-10-12T17:43:00.000Z")];
+        assert_eq!(edited_ids(&pages, "2026-10-12T17:43:30Z"), ["same-minute"]);
+    }
+
+    #[test]
+    fn ignores_edits_from_an_earlier_minute() {
+        let pages = [
+            shift_page("before", "2026-10-12T17:42:00.000Z"),
+            shift_page("after", "2026-10-12T17:44:00.000Z"),
+        ];
+        assert_eq!(edited_ids(&pages, "2026-10-12T17:43:30Z"), ["after"]);
+        assert_eq!(
+            edited_ids(&pages, "2026-10-12T17:42:59Z"),
+            ["before", "after"]
+        );
+    }
+
+    #[test]
+    fn skips_pages_with_an_unreadable_edit_time() {
+        let pages = [
+            shift_page("garbled", "yesterday"),
+            shift_page("edited", "2026-10-12T18:00:00.000Z"),
+        ];
+        let edits = late_edits(&pages, "2026-10-12T17:43:30Z".parse().unwrap());
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].page_id, "edited");
+        assert_eq!(edits[0].url, "https://www.notion.so/edited");
+    }
+
+    #[test]
+    fn watches_the_current_and_previous_periods_newest_first() {
+        let mut config = LateEditConfig::new("alex@example.com", "https://example.com", "secret");
+        let current = PayPeriod::containing(NaiveDate::from_ymd_opt(2026, 1, 12).unwrap());
+
+        let periods = config.periods(current);
+        assert_eq!(periods.len(), 3);
+        assert_eq!(periods[0], current);
+        assert_eq!(periods[1], current.previous());
+        assert_eq!(periods[2], current.previous().previous());
+        assert_eq!(
+            periods[2].start,
+            NaiveDate::from_ymd_opt(2025, 12, 9).unwrap()
+        );
+
+        config.periods_back = 0;
+        assert_eq!(config.periods(current), [current]);
+    }
+}
@@ -1,3 +1,14 @@
 pub mod notion;
 pub mod pdf;
 pub mod email;
+pub mod export;
+pub mod idempotency;
+pub mod amendment;
+pub mod approval;
+pub mod archive;
+pub mod delivery;
+pub mod late_edit;
+pub mod period;
+pub mod preview;
+pub mod schedule;
+pub mod token;
@@ -1,8 +1,9 @@
+use chrono::NaiveDate;
 use reqwest::{header, Client};
 use std::error::Error;
 use tracing::{error, info};
 
-use crate::models;
+use crate::{helpers::period::PayPeriod, models};
 
 pub fn notion_client_init(key: String) -> Result<Client, Box<dyn Error>> {
     info!("Initializing Notion client");
@@ -46,9 +47,18 @@ pub fn notion_client_init(key: String) -> Result<Client, Box<dyn Error>> {
 pub async fn fetch_data(
     client: &Client,
     db_id: &String,
+) -> Result<models::notion::NotionResponse, Box<dyn Error>> {
+    fetch_period_data(client, db_id, PayPeriod::current().into()).await
+}
+
+/// Fetch the entries for a given pay period
+pub async fn fetch_period_data(
+    client: &Client,
+    db_id: &String,
+    period: (NaiveDate, NaiveDate),
 ) -> Result<models::notion::NotionResponse, Box<dyn Error>> {
     info!("Building filters for database query");
-    let filters = utils::build_filters();
+    let filters = utils::build_period_filters(period);
 
     let url = format!("https://api.notion.com/v1/databases/{db_id}/query");
     info!("Fetching data from Notion database: {}", db_id);
@@ -151,74 +161,32 @@ pub async fn retrive_db(
 }
 
 pub mod utils {
-    use chrono::{Datelike, Local, NaiveDate};
+    use chrono::NaiveDate;
     use tracing::info;
 
-    pub fn get_current_pay_period() -> (NaiveDate, NaiveDate) {
-        let mut current_period: (NaiveDate, NaiveDate) =
-            (NaiveDate::default(), NaiveDate::default());
-
-        let period_window = (9, 23);
-        let now = Local::now().date_naive();
-        let day = now.day();
-
-        info!("Calculating pay period for current date: {}", now);
-
-        if day <= period_window.0 {
-            current_period.0 = now
-                .with_day(period_window.1 + 1)
-                .unwrap()
-                .with_month(if now.month() == 1 {
-                    12
-                } else {
-                    now.month() - 1
-                })
-                .unwrap();
-            current_period.1 = now.with_day(period_window.0 - 1).unwrap();
-            info!(
-                "Period calculated (early month): {} to {}",
-                current_period.0, current_period.1
-            );
-        } else if day >= period_window.1 {
-            current_period.0 = now.with_day(period_window.1 + 1).unwrap();
-            current_period.1 = now
-                .with_day(period_window.0 - 1)
-                .unwrap()
-                .with_month(if now.month() == 12 {
-                    1
-                } else {
-                    now.month() + 1
-                })
-                .unwrap();
-            info!(
-                "Period calculated (late month): {} to {}",
-                current_period.0, current_period.1
-            );
-        } else {
-            current_period.0 = now.with_day(period_window.0).unwrap();
-            current_period.1 = now.with_day(period_window.1).unwrap();
-            info!(
-                "Period calculated (mid month): {} to {}",
-                current_period.0, current_period.1
-            );
-        }
+    use crate::helpers::period::PayPeriod;
 
-        current_period
+    /// Browser link to a Notion database
+    pub fn database_url(db_id: &str) -> String {
+        format!("https://www.notion.so/{}", db_id.replace('-', ""))
     }
 
     pub fn build_filters() -> String {
+        build_period_filters(PayPeriod::current().into())
+    }
+
+    pub fn build_period_filters(pay_period: (NaiveDate, NaiveDate)) -> String {
         let date_property_name = "start and end";
-        let current_pay_period = get_current_pay_period();
 
         info!(
             "Building filters for pay period: {} to {}",
-            current_pay_period.0, current_pay_period.1
+            pay_period.0, pay_period.1
         );
 
         let filter_string = format!(
             r#"{{"filter": {{"or": [ {{"property": "notes","rich_text": {{"contains": "\\ TODO"}} }},{{"and": [{{"property": "{date_property_name}","date": {{"on_or_after": "{pay_period_start}"}}}},{{"property": "{date_property_name}","date": {{"on_or_before": "{pay_period_end}"}}}} ]}} ]}}, "sorts": [{{"property": "{date_property_name}", "direction": "ascending"}}] }}"#,
-            pay_period_start = current_pay_period.0,
-            pay_period_end = current_pay_period.1
+            pay_period_start = pay_period.0,
+            pay_period_end = pay_period.1
         );
 
         info!(
@@ -1,100 +1,193 @@
-use chrono::{DateTime, Datelike};
-use lopdf::{dictionary, Document, Object, StringFormat};
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+use image::{ImageFormat, ImageReader};
+use lopdf::{dictionary, Document, Object, ObjectId, StringFormat};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Cursor};
 use std::path::Path;
 use tracing::{error, info};
 
 use crate::models::notion::Page;
 
+mod fields;
+mod fingerprint;
+mod merge;
+mod metadata;
+mod notes;
+mod signing;
+mod standalone;
+mod template;
+#[cfg(test)]
+mod testing;
+
+pub use fingerprint::{FingerprintDiff, FingerprintField, TemplateFingerprint};
+pub use merge::merge_timesheets;
+pub use notes::NotesPlacement;
+pub use signing::{sign_pdf, verify_signature, PadesConfig, SignatureInfo};
+pub use template::{
+    inspect_template, list_form_fields, FormFieldInfo, RowGroup, TemplateMapping, TemplateReport,
+};
+
 fn load_pdf<P: AsRef<Path>>(path: P) -> Result<Document, lopdf::Error> {
     let file = File::open(path)?;
     let reader = BufReader::new(file);
     Document::load_from(reader)
 }
 
-pub fn create_sasi_timesheet(data: TimesheetData) -> Result<Vec<u8>, String> {
-    let template_path = "templates/sasi.pdf";
-    let mut output_buffer: Vec<u8> = Vec::new();
+/// Options controlling how a timesheet PDF is generated
+#[derive(Clone, Default)]
+pub struct PdfConfig {
+    pub mapping: TemplateMapping,
+    pub signature: Option<SignatureConfig>,
+    /// Digitally sign the output with a PKCS#12 certificate
+    pub pades: Option<PadesConfig>,
+    /// Attach JSON/CSV copies of the data and fill the document metadata
+    pub embed_data: bool,
+    pub renderer: Renderer,
+    /// Where entry notes go when the template has no notes field
+    pub notes: NotesPlacement,
+    /// Fixed values for other template fields, keyed by full field name, such
+    /// as a "Final timesheet" checkbox or a position type dropdown. Checkboxes
+    /// take `true`/`false`, radio buttons and choices take an option name.
+    pub extra_fields: BTreeMap<String, String>,
+    /// Text stamped in red across the top of the first page, such as `REVISED`
+    pub banner: Option<String>,
+}
 
-    let field_identifiers = (
-        "Month Day",
-        "Start Time",
-        "Finish Time",
-        "Hours to be Paid",
-        "Total hours",
-    );
+/// How the timesheet PDF is produced
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Renderer {
+    /// Fill the form fields of the template in `PdfConfig::mapping`
+    #[default]
+    AcroForm,
+    /// Draw a printable timesheet from scratch, no template needed
+    Standalone,
+}
+
+/// A drawn signature stamped onto the generated timesheet
+#[derive(Clone)]
+pub struct SignatureConfig {
+    /// Path to a PNG or JPEG image of the signature
+    pub image_path: String,
+    pub placement: SignaturePlacement,
+    /// Text field to fill with the signing date. When unset, the date is drawn
+    /// underneath the signature image instead.
+    pub date_field: Option<String>,
+    pub date_format: String,
+}
+
+/// Where on the template the signature image is placed
+#[derive(Clone)]
+pub enum SignaturePlacement {
+    /// Use the rectangle of the first form field whose name starts with this prefix
+    Field(String),
+    /// An explicit rectangle `[x1, y1, x2, y2]` on a 1-based page number
+    Rect { page: u32, rect: [f32; 4] },
+}
+
+/// Generate a timesheet PDF with the renderer selected in the config
+pub fn render_timesheet(data: TimesheetData, config: &PdfConfig) -> Result<Vec<u8>, String> {
+    match config.renderer {
+        Renderer::AcroForm => create_sasi_timesheet(data, config),
+        Renderer::Standalone => create_standalone_timesheet(data, config),
+    }
+}
+
+pub fn create_sasi_timesheet(data: TimesheetData, config: &PdfConfig) -> Result<Vec<u8>, String> {
+    let mapping = &config.mapping;
+    let mut output_buffer: Vec<u8> = Vec::new();
 
-    match load_pdf(template_path) {
+    match load_pdf(&mapping.template_path) {
         Ok(mut doc) => {
             info!("Loaded PDF with {} page(s)", doc.get_pages().len());
 
-            let field_refs = {
-                let catalog = doc.catalog().unwrap();
-                let acroform_ref = catalog.get(b"AcroForm").unwrap().as_reference().unwrap();
-                let acroform = doc.get_dictionary(acroform_ref).unwrap();
+            mapping.validate()?;
+            fingerprint::check_fingerprint(&doc, mapping)?;
 
-                if let Ok(Object::Array(fields)) = acroform.get(b"Fields") {
-                    info!("Found {} form fields", fields.len());
+            let field_refs = form_field_refs(&doc);
+            let form_fields = form_fields(&doc);
 
-                    fields
-                        .iter()
-                        .map(|field_ref| field_ref.as_reference().unwrap())
-                        .collect::<Vec<_>>()
-                } else {
-                    Vec::new()
-                }
-            };
+            let template_rows = fields_with_prefix(&form_fields, &mapping.hours).count();
+            if data.entries.len() > template_rows {
+                error!(
+                    "Timesheet has {} entries but the template only has {} rows",
+                    data.entries.len(),
+                    template_rows
+                );
+                return Err(format!(
+                    "Timesheet has {} entries but the template only has {} rows",
+                    data.entries.len(),
+                    template_rows
+                ));
+            }
 
             let mut processed_entries = 0;
 
-            for field_ref in field_refs.iter() {
-                if let Ok(field_dict) = doc.get_dictionary_mut(*field_ref) {
-                    if let Ok(Object::String(name_bytes, _)) = field_dict.get(b"T") {
-                        let field_name = String::from_utf8_lossy(name_bytes.as_slice());
-                        info!("Processing form field: {}", field_name);
+            for (field_ref, field_name) in &form_fields {
+                info!("Processing form field: {}", field_name);
 
-                        if field_name.starts_with(field_identifiers.4) {
-                            let value = data.total_hours.to_string();
-                            update_field_appearance(field_dict, &value);
-                            break;
-                        }
+                if field_name.starts_with(&mapping.total) {
+                    let value = data.total_hours.to_string();
+                    fields::fill_field(&mut doc, *field_ref, &value)?;
+                    break;
+                }
 
-                        if processed_entries >= data.entries.len() {
-                            continue;
-                        }
+                if processed_entries >= data.entries.len() {
+                    continue;
+                }
 
-                        let mut value = String::new();
-
-                        match field_name {
-                            _ if field_name.starts_with(field_identifiers.0) => {
-                                if field_name.ends_with("_2") {
-                                    value = data.entries[processed_entries].day.to_string();
-                                } else {
-                                    value = data.entries[processed_entries].month.to_string();
-                                }
-                            }
-                            _ if field_name.starts_with(field_identifiers.1) => {
-                                value = data.entries[processed_entries].start.clone();
-                            }
-                            _ if field_name.starts_with(field_identifiers.2) => {
-                                value = data.entries[processed_entries].end.clone();
-                            }
-                            _ if field_name.starts_with(field_identifiers.3) => {
-                                value = data.entries[processed_entries].paid_hours.to_string();
-                                processed_entries += 1
-                            }
-
-                            std::borrow::Cow::Borrowed(_) => {}
-                            std::borrow::Cow::Owned(_) => {}
-                        }
+                let mut value = String::new();
 
-                        if !value.is_empty() {
-                            update_field_appearance(field_dict, &value);
+                match field_name {
+                    _ if field_name.starts_with(&mapping.date) => {
+                        if field_name.ends_with(&mapping.day_suffix) {
+                            value = data.entries[processed_entries].day.to_string();
+                        } else {
+                            value = data.entries[processed_entries].month.to_string();
                         }
                     }
+                    _ if field_name.starts_with(&mapping.start) => {
+                        value = data.entries[processed_entries].start.clone();
+                    }
+                    _ if field_name.starts_with(&mapping.finish) => {
+                        value = data.entries[processed_entries].end.clone();
+                    }
+                    _ if field_name.starts_with(&mapping.hours) => {
+                        value = data.entries[processed_entries].paid_hours.to_string();
+                        processed_entries += 1
+                    }
+
+                    _ => {}
                 }
+
+                if !value.is_empty() {
+                    fields::fill_field(&mut doc, *field_ref, &value)?;
+                }
+            }
+
+            notes::place_notes(&mut doc, &form_fields, &data, config)?;
+
+            for (name, value) in &config.extra_fields {
+                let field_ref = fields::find_field(&doc, &field_refs, name).ok_or_else(|| {
+                    error!("Field '{}' not found in template", name);
+                    format!("Field '{}' not found in template", name)
+                })?;
+                fields::fill_field(&mut doc, field_ref, value)
+                    .map_err(|e| format!("Failed to fill field '{}': {}", name, e))?;
+            }
+
+            if let Some(signature) = &config.signature {
+                stamp_signature(&mut doc, &form_fields, signature)?;
+            }
+
+            if let Some(banner) = &config.banner {
+                stamp_banner(&mut doc, banner)?;
+            }
+
+            if config.embed_data {
+                metadata::embed_timesheet_data(&mut doc, &data)?;
             }
 
             match doc.save_to(&mut output_buffer) {
@@ -102,9 +195,18 @@ pub fn create_sasi_timesheet(data: TimesheetData) -> Result<Vec<u8>, String> {
                     "Successfully converted PDF to bytes, size: {} bytes",
                     output_buffer.len()
                 ),
-                Err(e) => error!("Failed to convert PDF to bytes: {}", e),
+                Err(e) => {
+                    error!("Failed to convert PDF to bytes: {}", e);
+                    return Err(format!("Failed to convert PDF to bytes: {}", e));
+                }
+            }
+
+            if let Some(pades) = &config.pades {
+                output_buffer = sign_pdf(output_buffer, pades)?;
             }
 
+            verify_timesheet_pdf(&output_buffer, &data, mapping)?;
+
             Ok(output_buffer)
         }
         Err(e) => {
@@ -114,13 +216,807 @@ pub fn create_sasi_timesheet(data: TimesheetData) -> Result<Vec<u8>, String> {
     }
 }
 
-fn update_field_appearance(field_dict: &mut lopdf::Dictionary, value: &str) {
-    // First: Set the value directly for browser compatibility
-    field_dict.set(
-        b"V",
-        Object::String(value.as_bytes().to_vec(), StringFormat::Literal),
+/// Draw a timesheet without a template. The table paginates on its own, so
+/// there is no row limit.
+pub fn create_standalone_timesheet(
+    data: TimesheetData,
+    config: &PdfConfig,
+) -> Result<Vec<u8>, String> {
+    let mut doc = standalone::build_standalone_timesheet(&data, config)?;
+    let page_count = doc.get_pages().len();
+
+    if let Some(banner) = &config.banner {
+        stamp_banner(&mut doc, banner)?;
+    }
+
+    if config.embed_data {
+        metadata::embed_timesheet_data(&mut doc, &data)?;
+    }
+
+    let mut output_buffer: Vec<u8> = Vec::new();
+    match doc.save_to(&mut output_buffer) {
+        Ok(_) => info!(
+            "Successfully converted PDF to bytes, size: {} bytes",
+            output_buffer.len()
+        ),
+        Err(e) => {
+            error!("Failed to convert PDF to bytes: {}", e);
+            return Err(format!("Failed to convert PDF to bytes: {}", e));
+        }
+    }
+
+    if let Some(pades) = &config.pades {
+        output_buffer = sign_pdf(output_buffer, pades)?;
+    }
+
+    // There are no fields to read back, so only check the output parses whole
+    let reloaded = Document::load_mem(&output_buffer).map_err(|e| {
+        error!("Generated PDF could not be parsed: {}", e);
+        format!("Generated PDF could not be parsed: {}", e)
+    })?;
+    if reloaded.get_pages().len() != page_count {
+        error!("Generated PDF lost pages when saved");
+        return Err("Generated PDF lost pages when saved".to_string());
+    }
+
+    Ok(output_buffer)
+}
+
+/// Re-parse a generated timesheet and check that every row and the total
+/// match the data it was generated from
+fn verify_timesheet_pdf(
+    pdf: &[u8],
+    data: &TimesheetData,
+    mapping: &TemplateMapping,
+) -> Result<(), String> {
+    let doc = Document::load_mem(pdf).map_err(|e| {
+        error!("Generated PDF could not be parsed: {}", e);
+        format!("Generated PDF could not be parsed: {}", e)
+    })?;
+
+    let (rows, total) = read_form_rows(&doc, mapping);
+
+    if rows.len() != data.entries.len() {
+        error!(
+            "Read-back found {} rows, expected {}",
+            rows.len(),
+            data.entries.len()
+        );
+        return Err(format!(
+            "Generated PDF has {} filled rows, expected {}",
+            rows.len(),
+            data.entries.len()
+        ));
+    }
+
+    let columns = [
+        "Month",
+        "Day",
+        "Start Time",
+        "Finish Time",
+        "Hours to be Paid",
+    ];
+
+    for (index, (row, entry)) in rows.iter().zip(&data.entries).enumerate() {
+        let expected = [
+            entry.month.to_string(),
+            entry.day.to_string(),
+            entry.start.clone(),
+            entry.end.clone(),
+            entry.paid_hours.to_string(),
+        ];
+
+        for (column, name) in columns.iter().enumerate() {
+            let (found, expected) = (&row.values[column], &expected[column]);
+            if found != expected {
+                let field = &row.fields[column];
+                error!(
+                    "Read-back mismatch in row {} {} (field '{}'): expected '{}', found '{}'",
+                    index + 1,
+                    name,
+                    field,
+                    expected,
+                    found
+                );
+                return Err(format!(
+                    "Generated PDF row {} {} (field '{}') is '{}', expected '{}'",
+                    index + 1,
+                    name,
+                    field,
+                    found,
+                    expected
+                ));
+            }
+        }
+    }
+
+    if let Some(notes) = read_form_notes(&doc, mapping) {
+        for (index, entry) in data.entries.iter().enumerate() {
+            let expected = notes::note_field_value(entry.notes.as_deref());
+            let found = notes.get(index).map(String::as_str).unwrap_or_default();
+            if found != expected {
+                error!(
+                    "Read-back mismatch in row {} notes: expected '{}', found '{}'",
+                    index + 1,
+                    expected,
+                    found
+                );
+                return Err(format!(
+                    "Generated PDF row {} notes are '{}', expected '{}'",
+                    index + 1,
+                    found,
+                    expected
+                ));
+            }
+        }
+    }
+
+    let expected_total = data.total_hours.to_string();
+    if total.as_deref() != Some(expected_total.as_str()) {
+        error!(
+            "Read-back total mismatch: expected '{}', found {:?}",
+            expected_total, total
+        );
+        return Err(format!(
+            "Generated PDF total is {:?}, expected '{}'",
+            total, expected_total
+        ));
+    }
+
+    info!("Verified {} rows and total in generated PDF", rows.len());
+    Ok(())
+}
+
+/// Rebuild timesheet data from a previously filled SASI PDF, using the same
+/// field mapping as `create_sasi_timesheet`
+pub fn import_sasi_timesheet(
+    pdf: &[u8],
+    mapping: &TemplateMapping,
+) -> Result<TimesheetData, String> {
+    let doc = Document::load_mem(pdf).map_err(|e| {
+        error!("Failed to load PDF for import: {}", e);
+        format!("Failed to load PDF: {:?}", e)
+    })?;
+
+    timesheet_from_form(&doc, mapping)
+}
+
+/// Same as [`import_sasi_timesheet`], reading the PDF from disk
+pub fn import_sasi_timesheet_file<P: AsRef<Path>>(
+    path: P,
+    mapping: &TemplateMapping,
+) -> Result<TimesheetData, String> {
+    info!("Importing timesheet from: {}", path.as_ref().display());
+
+    match load_pdf(path) {
+        Ok(doc) => timesheet_from_form(&doc, mapping),
+        Err(e) => {
+            error!("Failed to load PDF for import: {}", e);
+            Err(format!("Failed to load PDF: {:?}", e))
+        }
+    }
+}
+
+fn timesheet_from_form(doc: &Document, mapping: &TemplateMapping) -> Result<TimesheetData, String> {
+    mapping.validate()?;
+    fingerprint::check_fingerprint(doc, mapping)?;
+
+    let (rows, total) = read_form_rows(doc, mapping);
+    let notes = read_form_notes(doc, mapping).unwrap_or_default();
+    info!("Found {} filled rows to import", rows.len());
+
+    let mut entries = Vec::new();
+
+    for (index, row) in rows.iter().enumerate() {
+        entries.push(TimesheetEntry {
+            month: parse_cell(index, "month", &row.values[0])?,
+            day: parse_cell(index, "day", &row.values[1])?,
+            start: row.values[2].trim().to_string(),
+            end: row.values[3].trim().to_string(),
+            paid_hours: parse_cell(index, "hours", &row.values[4])?,
+            notes: notes
+                .get(index)
+                .map(|notes| notes.trim().to_string())
+                .filter(|notes| !notes.is_empty()),
+        });
+    }
+
+    let data = TimesheetData::try_from(entries)?;
+
+    // The form's own total has to agree with its rows, or the hours can't
+    // be trusted. Allow for rounding when the rows are summed.
+    match total.as_deref().map(|total| total.trim().parse::<f64>()) {
+        Some(Ok(total)) if (total - data.total_hours).abs() > 1e-6 => {
+            error!(
+                "Imported total {} does not match sum of rows {}",
+                total, data.total_hours
+            );
+            return Err(format!(
+                "Total hours on the form ({}) do not match the sum of its rows ({})",
+                total, data.total_hours
+            ));
+        }
+        Some(Err(_)) => {
+            error!("Imported total {:?} is not a number", total);
+            return Err(format!(
+                "Total hours on the form '{}' is not a number",
+                total.unwrap_or_default()
+            ));
+        }
+        _ => {}
+    }
+
+    info!(
+        "Imported timesheet with {} entries, {} hours",
+        data.entries.len(),
+        data.total_hours
     );
 
+    Ok(data)
+}
+
+fn parse_cell<T: std::str::FromStr>(row: usize, column: &str, value: &str) -> Result<T, String> {
+    value.trim().parse::<T>().map_err(|_| {
+        error!(
+            "Row {} has an invalid {} value '{}'",
+            row + 1,
+            column,
+            value
+        );
+        format!(
+            "Row {} has an invalid {} value '{}'",
+            row + 1,
+            column,
+            value
+        )
+    })
+}
+
+/// Read the filled rows (month, day, start, finish, hours) and the total
+/// back out of a timesheet form, walking fields in the same order they are
+/// filled. Rows with no values are skipped.
+fn read_form_rows(doc: &Document, mapping: &TemplateMapping) -> (Vec<FormRow>, Option<String>) {
+    let mut rows = Vec::new();
+    let mut row = FormRow::default();
+    let mut total = None;
+
+    for (field_ref, field_name) in form_fields(doc) {
+        let Ok(field_dict) = doc.get_dictionary(field_ref) else {
+            continue;
+        };
+        let value = field_dict
+            .get(b"V")
+            .and_then(lopdf::decode_text_string)
+            .unwrap_or_default();
+
+        let column = match &field_name {
+            _ if field_name.starts_with(&mapping.total) => {
+                total = Some(value).filter(|value| !value.is_empty());
+                break;
+            }
+            _ if field_name.starts_with(&mapping.date) => {
+                if field_name.ends_with(&mapping.day_suffix) {
+                    1
+                } else {
+                    0
+                }
+            }
+            _ if field_name.starts_with(&mapping.start) => 2,
+            _ if field_name.starts_with(&mapping.finish) => 3,
+            _ if field_name.starts_with(&mapping.hours) => 4,
+            _ => continue,
+        };
+        row.values[column] = value;
+        row.fields[column] = field_name;
+
+        if column == 4 {
+            let finished = std::mem::take(&mut row);
+            if finished.values.iter().any(|cell| !cell.is_empty()) {
+                rows.push(finished);
+            }
+        }
+    }
+
+    (rows, total)
+}
+
+/// A filled row of a timesheet form, in the order month, day, start, finish
+/// and hours
+#[derive(Default)]
+struct FormRow {
+    values: [String; 5],
+    /// Qualified name of the field each value was read from
+    fields: [String; 5],
+}
+
+/// Values of the notes fields in form order, or `None` when the mapping or
+/// template has no notes column
+fn read_form_notes(doc: &Document, mapping: &TemplateMapping) -> Option<Vec<String>> {
+    let prefix = mapping.notes.as_deref()?;
+
+    let form_fields = form_fields(doc);
+    let notes = fields_with_prefix(&form_fields, prefix)
+        .filter_map(|field_ref| doc.get_dictionary(field_ref).ok())
+        .map(|field_dict| {
+            field_dict
+                .get(b"V")
+                .and_then(lopdf::decode_text_string)
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+
+    Some(notes).filter(|notes| !notes.is_empty())
+}
+
+fn form_field_refs(doc: &Document) -> Vec<ObjectId> {
+    let fields = doc
+        .catalog()
+        .and_then(|catalog| catalog.get(b"AcroForm"))
+        .and_then(|acroform| doc.dereference(acroform))
+        .and_then(|(_, acroform)| acroform.as_dict())
+        .and_then(|acroform| acroform.get(b"Fields"))
+        .and_then(|fields| doc.dereference(fields))
+        .and_then(|(_, fields)| fields.as_array());
+
+    match fields {
+        Ok(fields) => {
+            info!("Found {} form fields", fields.len());
+
+            fields
+                .iter()
+                .filter_map(|field_ref| field_ref.as_reference().ok())
+                .collect()
+        }
+        Err(e) => {
+            error!("Template has no AcroForm fields: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+fn stamp_signature(
+    doc: &mut Document,
+    form_fields: &[(ObjectId, String)],
+    signature: &SignatureConfig,
+) -> Result<(), String> {
+    info!("Stamping signature from: {}", signature.image_path);
+
+    // A signature field gets the image as its own appearance, since drawing
+    // it on the page would leave the widget covering it
+    let (target, rect) = match &signature.placement {
+        SignaturePlacement::Field(prefix) => {
+            let field_ref = fields_with_prefix(form_fields, prefix)
+                .find(|id| doc.get_dictionary(*id).is_ok())
+                .ok_or(format!(
+                    "Signature field '{}' not found in template",
+                    prefix
+                ))?;
+            let widget_ref = doc
+                .get_dictionary(field_ref)
+                .map(|field_dict| field_kids(doc, field_dict))
+                .ok()
+                .and_then(|kids| kids.first().copied())
+                .unwrap_or(field_ref);
+
+            let rect = doc
+                .get_dictionary(widget_ref)
+                .ok()
+                .and_then(field_rect)
+                .ok_or(format!("Signature field '{}' has no Rect", prefix))?;
+            (SignatureTarget::Widget(widget_ref), rect)
+        }
+        SignaturePlacement::Rect { page, rect } => {
+            let page_id = *doc
+                .get_pages()
+                .get(page)
+                .ok_or(format!("Signature page {} does not exist", page))?;
+            (SignatureTarget::Page(page_id), *rect)
+        }
+    };
+
+    let date_ref = match &signature.date_field {
+        Some(prefix) => Some(fields_with_prefix(form_fields, prefix).next().ok_or(format!(
+            "Signature date field '{}' not found in template",
+            prefix
+        ))?),
+        None => None,
+    };
+    let date_height = if date_ref.is_some() { 0.0 } else { 10.0 };
+
+    // Fit the image inside the rectangle, keeping its aspect ratio and
+    // leaving room for the date line when it is drawn here. Checked before
+    // anything is written, so a failure leaves the date field empty.
+    let box_width = rect[2] - rect[0];
+    let box_height = rect[3] - rect[1] - date_height;
+    if box_width <= 0.0 || box_height <= 0.0 {
+        return Err(format!(
+            "Signature area {:?} is too small to hold the signature{}",
+            rect,
+            if date_height > 0.0 { " and its date" } else { "" }
+        ));
+    }
+
+    let image_bytes = std::fs::read(&signature.image_path)
+        .map_err(|e| format!("Failed to read signature image: {}", e))?;
+    let (image_stream, image_width, image_height) = signature_xobject(doc, image_bytes)?;
+    let image_id = doc.add_object(image_stream);
+
+    let date = Local::now()
+        .date_naive()
+        .format(&signature.date_format)
+        .to_string();
+    if let Some(date_ref) = date_ref {
+        fields::fill_field(doc, date_ref, &date)?;
+    }
+
+    // Drawn relative to the lower-left corner of the rectangle
+    let scale = (box_width / image_width).min(box_height / image_height);
+    let draw_width = image_width * scale;
+    let draw_height = image_height * scale;
+    let x = (box_width - draw_width) / 2.0;
+    let y = date_height + (box_height - draw_height) / 2.0;
+
+    let mut content = format!(
+        "q\n{:.2} 0 0 {:.2} {:.2} {:.2} cm\n/SigImg Do\nQ\n",
+        draw_width, draw_height, x, y
+    );
+    let mut resources = dictionary! {
+        b"XObject" => dictionary! {
+            b"SigImg" => Object::Reference(image_id),
+        },
+    };
+
+    if date_height > 0.0 {
+        content.push_str(&format!(
+            "BT\n/SigHelv 8 Tf\n0 g\n2 2 Td\n({}) Tj\nET\n",
+            date.replace("(", "\\(").replace(")", "\\)")
+        ));
+
+        let font_id = doc.add_object(dictionary! {
+            b"Type" => Object::Name(b"Font".to_vec()),
+            b"Subtype" => Object::Name(b"Type1".to_vec()),
+            b"BaseFont" => Object::Name(b"Helvetica".to_vec()),
+        });
+        resources.set(
+            "Font",
+            dictionary! {
+                b"SigHelv" => Object::Reference(font_id),
+            },
+        );
+    }
+
+    match target {
+        SignatureTarget::Widget(widget_ref) => {
+            let appearance = lopdf::Stream::new(
+                dictionary! {
+                    b"Type" => Object::Name(b"XObject".to_vec()),
+                    b"Subtype" => Object::Name(b"Form".to_vec()),
+                    b"BBox" => Object::Array(vec![
+                        Object::Integer(0),
+                        Object::Integer(0),
+                        Object::Real(box_width),
+                        Object::Real(rect[3] - rect[1]),
+                    ]),
+                    b"Resources" => resources,
+                },
+                content.into_bytes(),
+            );
+            let appearance_id = doc.add_object(appearance);
+            let widget = doc
+                .get_dictionary_mut(widget_ref)
+                .map_err(|e| format!("Failed to get signature widget: {}", e))?;
+            widget.set(
+                "AP",
+                dictionary! {
+                    b"N" => Object::Reference(appearance_id),
+                },
+            );
+        }
+        SignatureTarget::Page(page_id) => {
+            let page_resources = doc
+                .get_or_create_resources(page_id)
+                .and_then(Object::as_dict_mut)
+                .map_err(|e| format!("Failed to get page resources for signature: {}", e))?;
+            for (category, entries) in resources.iter() {
+                let Ok(entries) = entries.as_dict() else {
+                    continue;
+                };
+                if !page_resources.has(category) {
+                    page_resources.set(category.clone(), dictionary! {});
+                }
+                if let Ok(Object::Dictionary(existing)) = page_resources.get_mut(category) {
+                    existing.extend(entries);
+                }
+            }
+
+            let content = format!(
+                "\nq\n1 0 0 1 {:.2} {:.2} cm\n{}Q\n",
+                rect[0], rect[1], content
+            );
+            doc.add_page_contents(page_id, content.into_bytes())
+                .map_err(|e| format!("Failed to draw signature on page: {}", e))?;
+        }
+    }
+
+    info!(
+        "Signature stamped at [{:.1}, {:.1}] size {:.1}x{:.1}, dated {}",
+        rect[0] + x,
+        rect[1] + y,
+        draw_width,
+        draw_height,
+        date
+    );
+
+    Ok(())
+}
+
+/// Where a signature image is drawn
+enum SignatureTarget {
+    /// Into the appearance stream of a signature field's widget
+    Widget(ObjectId),
+    /// Onto the content of a page
+    Page(ObjectId),
+}
+
+/// Draw a line of bold red text at the top right of the first page
+fn stamp_banner(doc: &mut Document, banner: &str) -> Result<(), String> {
+    let page_id = *doc
+        .get_pages()
+        .values()
+        .next()
+        .ok_or("PDF has no pages to stamp".to_string())?;
+    let media_box = doc
+        .get_dictionary(page_id)
+        .ok()
+        .and_then(|page| page.get(b"MediaBox").ok())
+        .and_then(|media_box| media_box.as_array().ok())
+        .map(|media_box| {
+            media_box
+                .iter()
+                .filter_map(|value| value.as_float().ok())
+                .collect::<Vec<f32>>()
+        })
+        .filter(|media_box| media_box.len() == 4)
+        .unwrap_or(vec![0.0, 0.0, 612.0, 792.0]);
+
+    // Helvetica-Bold capitals average about 0.7 em wide
+    let size = 18.0;
+    let width = banner.chars().count() as f32 * size * 0.7;
+    let x = (media_box[2] - 36.0 - width).max(media_box[0] + 36.0);
+    let y = media_box[3] - 36.0;
+    let content = format!(
+        "\nq\nBT\n/BannerHelvB {} Tf\n0.8 0 0 rg\n{:.2} {:.2} Td\n({}) Tj\nET\nQ\n",
+        size,
+        x,
+        y,
+        banner.replace('\\', "\\\\").replace('(', "\\(").replace(')', "\\)")
+    );
+
+    let font_id = doc.add_object(dictionary! {
+        b"Type" => Object::Name(b"Font".to_vec()),
+        b"Subtype" => Object::Name(b"Type1".to_vec()),
+        b"BaseFont" => Object::Name(b"Helvetica-Bold".to_vec()),
+    });
+    let resources = doc
+        .get_or_create_resources(page_id)
+        .and_then(Object::as_dict_mut)
+        .map_err(|e| format!("Failed to get page resources for banner: {}", e))?;
+    if !resources.has(b"Font") {
+        resources.set("Font", dictionary! {});
+    }
+    if let Ok(Object::Dictionary(fonts)) = resources.get_mut(b"Font") {
+        fonts.set("BannerHelvB", Object::Reference(font_id));
+    }
+
+    doc.add_page_contents(page_id, content.into_bytes())
+        .map_err(|e| format!("Failed to draw banner on page: {}", e))?;
+    info!("Stamped banner '{}' on the first page", banner);
+    Ok(())
+}
+
+/// Build an image XObject for the signature. JPEGs are embedded as-is, PNGs
+/// are decoded and their alpha channel kept as a soft mask.
+fn signature_xobject(
+    doc: &mut Document,
+    bytes: Vec<u8>,
+) -> Result<(lopdf::Stream, f32, f32), String> {
+    let reader = ImageReader::new(Cursor::new(&bytes))
+        .with_guessed_format()
+        .map_err(|e| format!("Failed to read signature image: {}", e))?;
+    let format = reader.format();
+    let image = reader
+        .decode()
+        .map_err(|e| format!("Failed to decode signature image: {}", e))?;
+    let (width, height) = (image.width(), image.height());
+
+    let mut image_dict = dictionary! {
+        b"Type" => Object::Name(b"XObject".to_vec()),
+        b"Subtype" => Object::Name(b"Image".to_vec()),
+        b"Width" => Object::Integer(width as i64),
+        b"Height" => Object::Integer(height as i64),
+        b"BitsPerComponent" => Object::Integer(8),
+    };
+
+    let stream = match format {
+        Some(ImageFormat::Jpeg) => {
+            let color_space: &[u8] = if image.color().has_color() {
+                b"DeviceRGB"
+            } else {
+                b"DeviceGray"
+            };
+            image_dict.set("ColorSpace", Object::Name(color_space.to_vec()));
+            image_dict.set("Filter", Object::Name(b"DCTDecode".to_vec()));
+            lopdf::Stream::new(image_dict, bytes)
+        }
+        Some(ImageFormat::Png) => {
+            if image.color().has_alpha() {
+                let alpha = image
+                    .to_rgba8()
+                    .pixels()
+                    .map(|pixel| pixel.0[3])
+                    .collect::<Vec<u8>>();
+                let mut mask = lopdf::Stream::new(
+                    dictionary! {
+                        b"Type" => Object::Name(b"XObject".to_vec()),
+                        b"Subtype" => Object::Name(b"Image".to_vec()),
+                        b"Width" => Object::Integer(width as i64),
+                        b"Height" => Object::Integer(height as i64),
+                        b"BitsPerComponent" => Object::Integer(8),
+                        b"ColorSpace" => Object::Name(b"DeviceGray".to_vec()),
+                    },
+                    alpha,
+                );
+                let _ = mask.compress();
+                image_dict.set("SMask", Object::Reference(doc.add_object(mask)));
+            }
+
+            image_dict.set("ColorSpace", Object::Name(b"DeviceRGB".to_vec()));
+            let mut stream = lopdf::Stream::new(image_dict, image.to_rgb8().into_raw());
+            let _ = stream.compress();
+            stream
+        }
+        _ => return Err("Signature image must be a PNG or JPEG".to_string()),
+    };
+
+    Ok((stream, width as f32, height as f32))
+}
+
+/// Terminal form fields in form order, with their fully qualified names:
+/// parent names joined by `.`, as listed by [`list_form_fields`]
+fn form_fields(doc: &Document) -> Vec<(ObjectId, String)> {
+    let mut fields = Vec::new();
+    for field_ref in form_field_refs(doc) {
+        collect_form_fields(doc, field_ref, None, &mut fields);
+    }
+    fields
+}
+
+fn collect_form_fields(
+    doc: &Document,
+    field_ref: ObjectId,
+    parent_name: Option<&str>,
+    fields: &mut Vec<(ObjectId, String)>,
+) {
+    let Ok(field_dict) = doc.get_dictionary(field_ref) else {
+        return;
+    };
+
+    let partial_name = field_dict
+        .get(b"T")
+        .and_then(lopdf::decode_text_string)
+        .ok();
+    let name = match (parent_name, partial_name) {
+        (Some(parent), Some(partial)) => format!("{}.{}", parent, partial),
+        (None, Some(partial)) => partial,
+        (Some(parent), None) => parent.to_string(),
+        (None, None) => String::new(),
+    };
+
+    // Kids with their own names are child fields; kids without are widgets
+    let child_fields = field_kids(doc, field_dict)
+        .into_iter()
+        .filter(|kid| {
+            doc.get_dictionary(*kid)
+                .is_ok_and(|kid_dict| kid_dict.has(b"T"))
+        })
+        .collect::<Vec<_>>();
+    if child_fields.is_empty() {
+        fields.push((field_ref, name));
+        return;
+    }
+
+    for kid in child_fields {
+        collect_form_fields(doc, kid, Some(&name), fields);
+    }
+}
+
+fn field_kids(doc: &Document, field_dict: &lopdf::Dictionary) -> Vec<ObjectId> {
+    field_dict
+        .get(b"Kids")
+        .and_then(|kids| doc.dereference(kids))
+        .and_then(|(_, kids)| kids.as_array())
+        .map(|kids| {
+            kids.iter()
+                .filter_map(|kid| kid.as_reference().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Fields whose qualified name starts with `prefix`, in form order
+fn fields_with_prefix<'a>(
+    form_fields: &'a [(ObjectId, String)],
+    prefix: &'a str,
+) -> impl Iterator<Item = ObjectId> + 'a {
+    form_fields
+        .iter()
+        .filter(move |(_, name)| name.starts_with(prefix))
+        .map(|(field_ref, _)| *field_ref)
+}
+
+fn field_rect(field_dict: &lopdf::Dictionary) -> Option<[f32; 4]> {
+    let rect = field_dict.get(b"Rect").and_then(Object::as_array).ok()?;
+    if rect.len() != 4 {
+        return None;
+    }
+
+    let mut values = [0.0; 4];
+    for (value, object) in values.iter_mut().zip(rect) {
+        *value = object.as_float().ok()?;
+    }
+
+    // Normalise so that [x1, y1] is the lower-left corner
+    Some([
+        values[0].min(values[2]),
+        values[1].min(values[3]),
+        values[0].max(values[2]),
+        values[1].max(values[3]),
+    ])
+}
+
+/// Find the page a widget lives on, either from its `/P` entry or by
+/// searching each page's `/Annots`. `name` is only used in the error.
+fn field_page(
+    doc: &Document,
+    name: &str,
+    field_ref: ObjectId,
+    field_dict: &lopdf::Dictionary,
+) -> Result<ObjectId, String> {
+    if let Ok(page_id) = field_dict.get(b"P").and_then(Object::as_reference) {
+        return Ok(page_id);
+    }
+
+    doc.get_pages()
+        .into_values()
+        .find(|page_id| {
+            doc.get_dictionary(*page_id)
+                .ok()
+                .and_then(|page| page.get(b"Annots").ok())
+                .and_then(|annots| match annots {
+                    Object::Reference(id) => doc.get_object(*id).and_then(Object::as_array).ok(),
+                    other => other.as_array().ok(),
+                })
+                .is_some_and(|annots| {
+                    annots
+                        .iter()
+                        .any(|annot| annot.as_reference().is_ok_and(|id| id == field_ref))
+                })
+        })
+        .ok_or_else(|| format!("Could not find the page containing field '{}'", name))
+}
+
+fn update_field_appearance(doc: &mut Document, field_ref: ObjectId, value: &str) {
+    // Appearance streams must be indirect objects, so reserve one up front
+    let ap_id = doc.new_object_id();
+
+    let Ok(field_dict) = doc.get_dictionary_mut(field_ref) else {
+        error!("Form field {:?} is not a dictionary", field_ref);
+        return;
+    };
+
+    // First: Set the value directly for browser compatibility
+    field_dict.set(b"V", lopdf::text_string(value));
+
     // Remove any existing appearance stream to start clean
     field_dict.remove(b"AP");
 
@@ -193,12 +1089,10 @@ fn update_field_appearance(field_dict: &mut lopdf::Dictionary, value: &str) {
 
     // Set up the appearance dictionary
     let mut ap_dict = dictionary! {};
-    ap_dict.set(b"N", Object::Stream(ap_stream));
+    ap_dict.set(b"N", Object::Reference(ap_id));
     field_dict.set(b"AP", Object::Dictionary(ap_dict));
 
     // Ensure field is set to display as intended
-    // For text fields, we want to control these key attributes:
-    field_dict.set(b"FT", Object::Name(b"Tx".to_vec())); // Ensure it's a text field
     field_dict.set(
         b"DA",
         Object::String(b"/Helv 10 Tf 0 g".to_vec(), StringFormat::Literal),
@@ -220,22 +1114,108 @@ fn update_field_appearance(field_dict: &mut lopdf::Dictionary, value: &str) {
     // Setting common bits for visibility and printing
     field_dict.set(b"F", Object::Integer(4)); // Print bit set, others cleared
 
-    // Set specific field flags
-    let field_flags = 0; // No special flags, basic text field
-    field_dict.set(b"Ff", Object::Integer(field_flags));
+    doc.objects.insert(ap_id, Object::Stream(ap_stream));
 }
 
+/// A pay period's worth of shifts.
+///
+/// The JSON form is flat and stable: `employee`, `period_start`,
+/// `period_end` (ISO dates), `total_hours` and `entries`. Only `entries` is
+/// required on input; a missing total is computed, and a total that doesn't
+/// match the entries is rejected.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "TimesheetDataJson", into = "TimesheetDataJson")]
 pub struct TimesheetData {
     pub entries: Vec<TimesheetEntry>,
     pub total_hours: f64,
+    pub employee: Option<String>,
+    /// First and last day of the pay period
+    pub period: Option<(NaiveDate, NaiveDate)>,
 }
 
+impl TimesheetData {
+    /// Full date of an entry, taking the year from the pay period. Periods
+    /// spanning New Year put December entries in the first year.
+    pub fn entry_date(&self, entry: &TimesheetEntry) -> Option<NaiveDate> {
+        let (start, end) = self.period?;
+        let year = if entry.month >= start.month() {
+            start.year()
+        } else {
+            end.year()
+        };
+        NaiveDate::from_ymd_opt(year, entry.month, entry.day)
+    }
+
+    /// Hours per week, keyed by the Monday starting each week. Empty when the
+    /// pay period is unknown, since entries have no year without it.
+    pub fn weekly_hours(&self) -> Vec<(NaiveDate, f64)> {
+        let mut weeks: BTreeMap<NaiveDate, f64> = BTreeMap::new();
+        for entry in &self.entries {
+            if let Some(date) = self.entry_date(entry) {
+                let monday = date.week(chrono::Weekday::Mon).first_day();
+                *weeks.entry(monday).or_default() += entry.paid_hours;
+            }
+        }
+        weeks.into_iter().collect()
+    }
+
+    /// Things worth a second look before the timesheet is submitted. None of
+    /// these stop generation.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let mut seen = Vec::new();
+
+        for entry in &self.entries {
+            let label = format!("{}/{} {}", entry.month, entry.day, entry.start);
+
+            if entry.paid_hours <= 0.0 {
+                warnings.push(format!("Shift on {} has no paid hours", label));
+            }
+            if entry.start.trim().is_empty() || entry.end.trim().is_empty() {
+                warnings.push(format!(
+                    "Shift on {}/{} is missing a start or finish time",
+                    entry.month, entry.day
+                ));
+            }
+            if let Some((start, end)) = self.period {
+                match self.entry_date(entry) {
+                    Some(date) if date < start || date > end => warnings.push(format!(
+                        "Shift on {} is outside the pay period {} to {}",
+                        date, start, end
+                    )),
+                    Some(_) => {}
+                    None => warnings.push(format!(
+                        "Shift on {}/{} is not a valid date",
+                        entry.month, entry.day
+                    )),
+                }
+            }
+
+            let key = (entry.month, entry.day, entry.start.as_str());
+            if seen.contains(&key) {
+                warnings.push(format!("Shift on {} is listed more than once", label));
+            } else {
+                seen.push(key);
+            }
+        }
+
+        if self.entries.is_empty() {
+            warnings.push("No shifts were recorded for this pay period".to_string());
+        }
+        warnings
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct TimesheetEntry {
     pub month: u32,
     pub day: u32,
     pub start: String,
     pub end: String,
     pub paid_hours: f64,
+    /// Plain text of the entry's notes, if any
+    #[serde(default)]
+    pub notes: Option<String>,
 }
 
 impl TryFrom<Page> for TimesheetEntry {
@@ -278,12 +1258,16 @@ impl TryFrom<Page> for TimesheetEntry {
             .number
             .ok_or("Missing Hours property")?;
 
+        let notes = page.properties.notes.plain_text();
+        let notes = Some(notes.trim().to_string()).filter(|notes| !notes.is_empty());
+
         Ok(TimesheetEntry {
             month,
             day,
             start,
             end,
             paid_hours,
+            notes,
         })
     }
 }
@@ -292,10 +1276,6 @@ impl TryFrom<Vec<Page>> for TimesheetData {
     type Error = String;
 
     fn try_from(pages: Vec<Page>) -> Result<Self, Self::Error> {
-        if pages.len() > 16 {
-            return Err("Exceeds max entry length 16".to_string());
-        }
-
         let mut entries = Vec::new();
         let mut total_hours: f64 = 0.0;
 
@@ -307,7 +1287,9 @@ impl TryFrom<Vec<Page>> for TimesheetData {
 
         Ok(TimesheetData {
             entries,
-            total_hours: total_hours.into(),
+            total_hours,
+            employee: None,
+            period: None,
         })
     }
 }
@@ -316,10 +1298,6 @@ impl TryFrom<Vec<TimesheetEntry>> for TimesheetData {
     type Error = String;
 
     fn try_from(entries: Vec<TimesheetEntry>) -> Result<Self, Self::Error> {
-        if entries.len() > 16 {
-            return Err("Exceeds max entry length 16".to_string());
-        }
-
         let mut total_hours = 0.0;
 
         for entry in &entries {
@@ -329,6 +1307,297 @@ impl TryFrom<Vec<TimesheetEntry>> for TimesheetData {
         Ok(TimesheetData {
             entries,
             total_hours,
+            employee: None,
+            period: None,
         })
     }
 }
+
+/// Wire format of [`TimesheetData`]
+#[derive(Serialize, Deserialize)]
+struct TimesheetDataJson {
+    #[serde(default)]
+    employee: Option<String>,
+    #[serde(default)]
+    period_start: Option<NaiveDate>,
+    #[serde(default)]
+    period_end: Option<NaiveDate>,
+    #[serde(default)]
+    total_hours: Option<f64>,
+    entries: Vec<TimesheetEntry>,
+}
+
+impl TryFrom<TimesheetDataJson> for TimesheetData {
+    type Error = String;
+
+    fn try_from(json: TimesheetDataJson) -> Result<Self, Self::Error> {
+        for (index, entry) in json.entries.iter().enumerate() {
+            if !(1..=12).contains(&entry.month) || !(1..=31).contains(&entry.day) {
+                return Err(format!(
+                    "Entry {} has an invalid date {}/{}",
+                    index + 1,
+                    entry.month,
+                    entry.day
+                ));
+            }
+            if !entry.paid_hours.is_finite() || entry.paid_hours < 0.0 {
+                return Err(format!(
+                    "Entry {} has invalid paid hours {}",
+                    index + 1,
+                    entry.paid_hours
+                ));
+            }
+        }
+
+        let period = match (json.period_start, json.period_end) {
+            (Some(start), Some(end)) if start <= end => Some((start, end)),
+            (Some(start), Some(end)) => {
+                return Err(format!("Pay period starts {} after it ends {}", start, end))
+            }
+            (None, None) => None,
+            _ => return Err("period_start and period_end must be given together".to_string()),
+        };
+
+        let mut data = TimesheetData::try_from(json.entries)?;
+        // Allow for rounding in totals computed by other tools
+        let mismatch = json
+            .total_hours
+            .filter(|total| (total - data.total_hours).abs() > 0.01);
+        if let Some(total) = mismatch {
+            return Err(format!(
+                "total_hours {} does not match the sum of entries {}",
+                total, data.total_hours
+            ));
+        }
+        data.employee = json.employee;
+        data.period = period;
+
+        // With a period, the year is known, so 2/31 or 2/29 in a common
+        // year can be caught
+        if let Some(index) = data
+            .entries
+            .iter()
+            .position(|entry| period.is_some() && data.entry_date(entry).is_none())
+        {
+            let entry = &data.entries[index];
+            return Err(format!(
+                "Entry {} has an invalid date {}/{}",
+                index + 1,
+                entry.month,
+                entry.day
+            ));
+        }
+
+        Ok(data)
+    }
+}
+
+impl From<TimesheetData> for TimesheetDataJson {
+    fn from(data: TimesheetData) -> Self {
+        Self {
+            employee: data.employee,
+            period_start: data.period.map(|period| period.0),
+            period_end: data.period.map(|period| period.1),
+            total_hours: Some(data.total_hours),
+            entries: data.entries,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{sasi_template, save, TempPdf, TestForm};
+    use super::*;
+
+    /// A one-row form filled in the default SASI layout, with `total` as
+    /// its total hours
+    fn filled_form(hours: &str, total: &str) -> Vec<u8> {
+        let mut form = TestForm::new();
+        let fields = [
+            ("Month Day1", "10"),
+            ("Month Day1_2", "12"),
+            ("Start Time1", "09:00"),
+            ("Finish Time1", "12:00"),
+            ("Hours to be Paid1", hours),
+            ("Total hours", total),
+        ];
+        for (column, (name, value)) in fields.into_iter().enumerate() {
+            let x = 50 + column as i64 * 90;
+            form.text_field(name, value, [x, 700, x + 80, 718]);
+        }
+        form.bytes()
+    }
+
+    #[test]
+    fn imports_a_form_whose_total_matches() {
+        let data =
+            import_sasi_timesheet(&filled_form("3", "3.0"), &TemplateMapping::default()).unwrap();
+        assert_eq!(data.entries.len(), 1);
+        assert_eq!(data.entries[0].day, 12);
+        assert_eq!(data.total_hours, 3.0);
+    }
+
+    #[test]
+    fn refuses_a_form_whose_total_does_not_add_up() {
+        let mapping = TemplateMapping::default();
+        let error = import_sasi_timesheet(&filled_form("3", "4"), &mapping).unwrap_err();
+        assert!(error.contains("do not match"), "{}", error);
+
+        let error = import_sasi_timesheet(&filled_form("3", "three"), &mapping).unwrap_err();
+        assert!(error.contains("is not a number"), "{}", error);
+    }
+
+    fn json_entry(month: u32, day: u32) -> TimesheetEntry {
+        TimesheetEntry {
+            month,
+            day,
+            start: "09:00".to_string(),
+            end: "12:00".to_string(),
+            paid_hours: 3.0,
+            notes: None,
+        }
+    }
+
+    #[test]
+    fn rejects_json_dates_that_do_not_exist() {
+        let json = |period: Option<(&str, &str)>, entry: TimesheetEntry| TimesheetDataJson {
+            employee: None,
+            period_start: period.map(|period| period.0.parse().unwrap()),
+            period_end: period.map(|period| period.1.parse().unwrap()),
+            total_hours: None,
+            entries: vec![entry],
+        };
+        let february = Some(("2026-02-09", "2026-02-23"));
+
+        let error = TimesheetData::try_from(json(february, json_entry(2, 31))).unwrap_err();
+        assert_eq!(error, "Entry 1 has an invalid date 2/31");
+        assert!(TimesheetData::try_from(json(february, json_entry(2, 29))).is_err());
+        assert!(TimesheetData::try_from(json(february, json_entry(2, 20))).is_ok());
+        // Without a period there is no year to check against
+        assert!(TimesheetData::try_from(json(None, json_entry(2, 29))).is_ok());
+    }
+
+    /// A page with a signature field at `signature_rect` and a date field,
+    /// plus a PNG signature image in the temp folder
+    fn signature_form(signature_rect: [i64; 4]) -> (Document, ObjectId, SignatureConfig) {
+        let mut form = TestForm::new();
+        form.field(
+            "Employee Signature",
+            dictionary! { "FT" => "Sig" },
+            signature_rect,
+        );
+        let date_id = form.text_field("Date", "", [300, 100, 400, 120]);
+        let doc = form.finish();
+
+        let image_path =
+            std::env::temp_dir().join(format!("signature-{}.png", uuid::Uuid::new_v4()));
+        image::RgbaImage::from_pixel(4, 2, image::Rgba([0, 0, 0, 255]))
+            .save(&image_path)
+            .unwrap();
+        let signature = SignatureConfig {
+            image_path: image_path.to_string_lossy().into_owned(),
+            placement: SignaturePlacement::Field("Employee Signature".to_string()),
+            date_field: Some("Date".to_string()),
+            date_format: "%Y-%m-%d".to_string(),
+        };
+        (doc, date_id, signature)
+    }
+
+    #[test]
+    fn stamps_a_signature_field_through_its_appearance() {
+        let (mut doc, date_id, signature) = signature_form([100, 100, 200, 140]);
+        let form_fields = form_fields(&doc);
+        let result = stamp_signature(&mut doc, &form_fields, &signature);
+        let _ = std::fs::remove_file(&signature.image_path);
+        result.unwrap();
+
+        let signature_id = form_fields[0].0;
+        let appearance_id = doc
+            .get_dictionary(signature_id)
+            .and_then(|widget| widget.get(b"AP"))
+            .and_then(Object::as_dict)
+            .and_then(|appearance| appearance.get(b"N"))
+            .and_then(Object::as_reference)
+            .unwrap();
+        let appearance = doc.get_object(appearance_id).and_then(Object::as_stream).unwrap();
+        assert_eq!(appearance.dict.get(b"Subtype").unwrap().as_name().unwrap(), b"Form");
+        assert!(appearance
+            .dict
+            .get(b"Resources")
+            .and_then(Object::as_dict)
+            .and_then(|resources| resources.get(b"XObject"))
+            .and_then(Object::as_dict)
+            .is_ok_and(|images| images.has(b"SigImg")));
+
+        // Nothing drawn on the page, where the widget would cover it
+        let page_id = *doc.get_pages().get(&1).unwrap();
+        assert!(!doc.get_dictionary(page_id).unwrap().has(b"Contents"));
+        assert!(doc.get_dictionary(date_id).unwrap().has(b"V"));
+    }
+
+    #[test]
+    fn leaves_the_date_empty_when_the_signature_does_not_fit() {
+        let (mut doc, date_id, signature) = signature_form([100, 100, 200, 100]);
+        let form_fields = form_fields(&doc);
+        let result = stamp_signature(&mut doc, &form_fields, &signature);
+        let _ = std::fs::remove_file(&signature.image_path);
+
+        let error = result.unwrap_err();
+        assert!(error.contains("too small"), "{}", error);
+        assert!(!doc.get_dictionary(date_id).unwrap().has(b"V"));
+    }
+
+    #[test]
+    fn verification_names_a_field_that_reads_back_wrong() {
+        let template = TempPdf::new(sasi_template(2).finish());
+        let mapping = TemplateMapping {
+            template_path: template.path(),
+            ..TemplateMapping::default()
+        };
+        let config = PdfConfig {
+            mapping: mapping.clone(),
+            ..PdfConfig::default()
+        };
+        let data = TimesheetData::try_from(vec![json_entry(10, 12), json_entry(10, 13)]).unwrap();
+        let pdf = create_sasi_timesheet(data.clone(), &config).unwrap();
+        assert!(verify_timesheet_pdf(&pdf, &data, &mapping).is_ok());
+
+        // Change what the second row's start time says
+        let mut doc = Document::load_mem(&pdf).unwrap();
+        let form_fields = form_fields(&doc);
+        let start = fields_with_prefix(&form_fields, "Start Time2").next().unwrap();
+        doc.get_dictionary_mut(start)
+            .unwrap()
+            .set("V", Object::string_literal("10:00"));
+        let corrupted = save(&mut doc);
+
+        let error = verify_timesheet_pdf(&corrupted, &data, &mapping).unwrap_err();
+        assert_eq!(
+            error,
+            "Generated PDF row 2 Start Time (field 'Start Time2') is '10:00', expected '09:00'"
+        );
+    }
+
+    #[test]
+    fn finds_a_field_page_by_p_or_annots_and_names_a_field_on_neither() {
+        let mut form = TestForm::new();
+        let listed = form.text_field("Hours to be Paid1", "", [450, 680, 540, 698]);
+        let mut doc = form.finish();
+        let page_id = doc.get_pages()[&1];
+
+        // Without /P, the page listing the widget in /Annots is found
+        doc.get_dictionary_mut(listed).unwrap().remove(b"P");
+        let field_dict = doc.get_dictionary(listed).unwrap().clone();
+        assert_eq!(
+            field_page(&doc, "Hours to be Paid1", listed, &field_dict),
+            Ok(page_id)
+        );
+
+        let orphan = doc.add_object(dictionary! { "T" => Object::string_literal("Notes1") });
+        let field_dict = doc.get_dictionary(orphan).unwrap().clone();
+        assert_eq!(
+            field_page(&doc, "Notes1", orphan, &field_dict),
+            Err("Could not find the page containing field 'Notes1'".to_string())
+        );
+    }
+}
@@ -0,0 +1,501 @@
+use lopdf::{text_string, Document, Object, ObjectId};
+use tracing::{error, info};
+
+use super::update_field_appearance;
+
+/// Field flag bits (`/Ff`) from the PDF spec
+const FLAG_RADIO: i64 = 1 << 15;
+const FLAG_PUSHBUTTON: i64 = 1 << 16;
+const FLAG_COMBO: i64 = 1 << 17;
+const FLAG_EDIT: i64 = 1 << 18;
+
+/// Values that turn a checkbox on or off, besides its own on-state name
+const CHECKED: [&str; 5] = ["true", "yes", "on", "x", "1"];
+const UNCHECKED: [&str; 5] = ["false", "no", "off", "", "0"];
+
+/// Fill a form field according to its type: text fields get a value and a
+/// drawn appearance, buttons are switched to one of their appearance states
+/// and choice fields are set to a matching option
+pub(super) fn fill_field(
+    doc: &mut Document,
+    field_ref: ObjectId,
+    value: &str,
+) -> Result<(), String> {
+    let field_dict = doc
+        .get_dictionary(field_ref)
+        .map_err(|e| format!("Form field {:?} is not a dictionary: {}", field_ref, e))?;
+    let field_type = inherited(doc, field_dict, b"FT")
+        .and_then(|field_type| field_type.as_name().ok())
+        .map(|field_type| field_type.to_vec());
+    let flags = inherited(doc, field_dict, b"Ff")
+        .and_then(|flags| flags.as_i64().ok())
+        .unwrap_or(0);
+
+    match field_type.as_deref() {
+        Some(b"Btn") if flags & FLAG_PUSHBUTTON != 0 => {
+            Err("Push buttons cannot be filled".to_string())
+        }
+        Some(b"Btn") if flags & FLAG_RADIO != 0 => fill_radio(doc, field_ref, value),
+        Some(b"Btn") => fill_checkbox(doc, field_ref, value),
+        Some(b"Ch") => fill_choice(doc, field_ref, value, flags),
+        Some(b"Sig") => Err("Signature fields cannot be filled with text".to_string()),
+        _ => {
+            update_field_appearance(doc, field_ref, value);
+            Ok(())
+        }
+    }
+}
+
+/// Look a field attribute up on the field or, failing that, its ancestors
+pub(super) fn inherited<'a>(
+    doc: &'a Document,
+    field_dict: &'a lopdf::Dictionary,
+    key: &[u8],
+) -> Option<&'a Object> {
+    let mut current = field_dict;
+    loop {
+        if let Ok(value) = current.get(key) {
+            return Some(value);
+        }
+        let parent = current.get(b"Parent").and_then(Object::as_reference).ok()?;
+        current = doc.get_dictionary(parent).ok()?;
+    }
+}
+
+/// The field itself when it is also its widget, otherwise its widget kids
+fn widgets(doc: &Document, field_ref: ObjectId) -> Vec<ObjectId> {
+    let kids = doc
+        .get_dictionary(field_ref)
+        .and_then(|field| field.get(b"Kids"))
+        .and_then(|kids| doc.dereference(kids))
+        .and_then(|(_, kids)| kids.as_array())
+        .map(|kids| {
+            kids.iter()
+                .filter_map(|kid| kid.as_reference().ok())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    if kids.is_empty() {
+        vec![field_ref]
+    } else {
+        kids
+    }
+}
+
+/// Names of a widget's "on" appearance states, from the keys of `/AP /N`
+fn on_states(doc: &Document, widget_ref: ObjectId) -> Vec<Vec<u8>> {
+    doc.get_dictionary(widget_ref)
+        .and_then(|widget| widget.get(b"AP"))
+        .and_then(|ap| doc.dereference(ap))
+        .and_then(|(_, ap)| ap.as_dict())
+        .and_then(|ap| ap.get(b"N"))
+        .and_then(|normal| doc.dereference(normal))
+        .and_then(|(_, normal)| normal.as_dict())
+        .map(|normal| {
+            normal
+                .iter()
+                .map(|(state, _)| state.clone())
+                .filter(|state| state.as_slice() != b"Off")
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Set the field value and turn on only the widgets whose on-state is `state`
+fn set_button_state(
+    doc: &mut Document,
+    field_ref: ObjectId,
+    widgets: &[(ObjectId, Vec<Vec<u8>>)],
+    state: Option<&[u8]>,
+) -> Result<(), String> {
+    let value = state.unwrap_or(b"Off").to_vec();
+    doc.get_dictionary_mut(field_ref)
+        .map_err(|e| e.to_string())?
+        .set("V", Object::Name(value));
+
+    for (widget_ref, states) in widgets {
+        let appearance = match state {
+            Some(state) if states.iter().any(|on| on.as_slice() == state) => state.to_vec(),
+            _ => b"Off".to_vec(),
+        };
+        doc.get_dictionary_mut(*widget_ref)
+            .map_err(|e| e.to_string())?
+            .set("AS", Object::Name(appearance));
+    }
+    Ok(())
+}
+
+fn fill_checkbox(doc: &mut Document, field_ref: ObjectId, value: &str) -> Result<(), String> {
+    let widgets = widgets(doc, field_ref)
+        .into_iter()
+        .map(|widget| (widget, on_states(doc, widget)))
+        .collect::<Vec<_>>();
+    let on_state = widgets
+        .iter()
+        .flat_map(|(_, states)| states.first())
+        .next()
+        .cloned()
+        .unwrap_or(b"Yes".to_vec());
+
+    let value = value.trim();
+    let checked = if value.as_bytes() == on_state.as_slice()
+        || CHECKED.contains(&value.to_lowercase().as_str())
+    {
+        true
+    } else if UNCHECKED.contains(&value.to_lowercase().as_str()) {
+        false
+    } else {
+        error!("Checkbox value '{}' is neither on nor off", value);
+        return Err(format!(
+            "Checkbox value '{}' is not one of {:?}, {:?} or '{}'",
+            value,
+            CHECKED,
+            UNCHECKED,
+            String::from_utf8_lossy(&on_state)
+        ));
+    };
+
+    set_button_state(
+        doc,
+        field_ref,
+        &widgets,
+        checked.then_some(on_state.as_slice()),
+    )?;
+    info!(
+        "Set checkbox {:?} to {}",
+        field_ref,
+        if checked { "on" } else { "off" }
+    );
+    Ok(())
+}
+
+/// Select the radio button whose on-state name, or export value from
+/// `/Opt`, matches the value
+fn fill_radio(doc: &mut Document, field_ref: ObjectId, value: &str) -> Result<(), String> {
+    let widgets = widgets(doc, field_ref)
+        .into_iter()
+        .map(|widget| (widget, on_states(doc, widget)))
+        .collect::<Vec<_>>();
+    let export_values = option_pairs(doc, field_ref)
+        .into_iter()
+        .map(|(export, _)| export)
+        .collect::<Vec<_>>();
+
+    let value = value.trim();
+
+    // With /Opt, kid N's export value is entry N and its state name may be an index
+    let by_export = export_values
+        .iter()
+        .position(|export| export.eq_ignore_ascii_case(value))
+        .and_then(|index| widgets.get(index))
+        .and_then(|(_, states)| states.first());
+    let by_state = widgets
+        .iter()
+        .flat_map(|(_, states)| states)
+        .find(|state| String::from_utf8_lossy(state).eq_ignore_ascii_case(value));
+
+    // Options come first, so a "No" or "0" option can still be selected
+    let state = by_export.or(by_state).cloned();
+    if state.is_none() && UNCHECKED.contains(&value.to_lowercase().as_str()) {
+        return set_button_state(doc, field_ref, &widgets, None);
+    }
+    let Some(state) = state else {
+        let choices = widgets
+            .iter()
+            .flat_map(|(_, states)| states)
+            .map(|state| String::from_utf8_lossy(state).into_owned())
+            .chain(export_values)
+            .collect::<Vec<_>>();
+        error!("Radio value '{}' is not one of {:?}", value, choices);
+        return Err(format!(
+            "Radio value '{}' is not one of {:?}",
+            value, choices
+        ));
+    };
+
+    set_button_state(doc, field_ref, &widgets, Some(&state))?;
+    info!(
+        "Selected radio option {} on {:?}",
+        String::from_utf8_lossy(&state),
+        field_ref
+    );
+    Ok(())
+}
+
+/// `/Opt` entries as (export value, display text)
+fn option_pairs(doc: &Document, field_ref: ObjectId) -> Vec<(String, String)> {
+    let Some(options) = doc
+        .get_dictionary(field_ref)
+        .ok()
+        .and_then(|field| inherited(doc, field, b"Opt"))
+        .and_then(|options| doc.dereference(options).ok())
+        .and_then(|(_, options)| options.as_array().ok())
+    else {
+        return Vec::new();
+    };
+
+    options
+        .iter()
+        .filter_map(|option| match option {
+            Object::Array(pair) if pair.len() == 2 => Some((
+                lopdf::decode_text_string(&pair[0]).ok()?,
+                lopdf::decode_text_string(&pair[1]).ok()?,
+            )),
+            option => {
+                let text = lopdf::decode_text_string(option).ok()?;
+                Some((text.clone(), text))
+            }
+        })
+        .collect()
+}
+
+fn fill_choice(
+    doc: &mut Document,
+    field_ref: ObjectId,
+    value: &str,
+    flags: i64,
+) -> Result<(), String> {
+    let options = option_pairs(doc, field_ref);
+    let value = value.trim();
+
+    let selected = options.iter().position(|(export, display)| {
+        export.eq_ignore_ascii_case(value) || display.eq_ignore_ascii_case(value)
+    });
+
+    let (export, display) = match selected {
+        Some(index) => options[index].clone(),
+        // Editable combo boxes accept any text
+        None if flags & FLAG_COMBO != 0 && flags & FLAG_EDIT != 0 => {
+            (value.to_string(), value.to_string())
+        }
+        None => {
+            let choices = options
+                .iter()
+                .map(|(_, display)| display.as_str())
+                .collect::<Vec<_>>();
+            error!("Choice value '{}' is not one of {:?}", value, choices);
+            return Err(format!(
+                "Choice value '{}' is not one of {:?}",
+                value, choices
+            ));
+        }
+    };
+
+    update_field_appearance(doc, field_ref, &display);
+
+    let field_dict = doc
+        .get_dictionary_mut(field_ref)
+        .map_err(|e| e.to_string())?;
+    field_dict.set("V", text_string(&export));
+    match selected {
+        Some(index) => field_dict.set("I", Object::Array(vec![Object::Integer(index as i64)])),
+        None => {
+            field_dict.remove(b"I");
+        }
+    }
+
+    info!("Set choice {:?} to '{}'", field_ref, export);
+    Ok(())
+}
+
+/// Find a field by its fully qualified name, searching nested fields
+pub(super) fn find_field(doc: &Document, field_refs: &[ObjectId], name: &str) -> Option<ObjectId> {
+    field_refs.iter().copied().find_map(|field_ref| {
+        let field_dict = doc.get_dictionary(field_ref).ok()?;
+        let partial = field_dict
+            .get(b"T")
+            .and_then(lopdf::decode_text_string)
+            .ok()?;
+        if partial == name {
+            return Some(field_ref);
+        }
+
+        let rest = name.strip_prefix(&partial)?.strip_prefix('.')?;
+        let kids = field_dict
+            .get(b"Kids")
+            .and_then(|kids| doc.dereference(kids))
+            .and_then(|(_, kids)| kids.as_array())
+            .ok()?
+            .iter()
+            .filter_map(|kid| kid.as_reference().ok())
+            .collect::<Vec<_>>();
+        find_field(doc, &kids, rest)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::pdf::testing::TestForm;
+    use lopdf::{dictionary, Dictionary};
+
+    struct Fixture {
+        doc: Document,
+        checkbox: ObjectId,
+        radio: ObjectId,
+        radio_widgets: [ObjectId; 2],
+        choice: ObjectId,
+        editable: ObjectId,
+    }
+
+    /// Normal appearances for a button widget that turns on as `state`
+    fn states(form: &mut TestForm, state: &str) -> Dictionary {
+        let on = form.stream();
+        let off = form.stream();
+        dictionary! {
+            "AP" => dictionary! {
+                "N" => dictionary! { state => on, "Off" => off },
+            },
+        }
+    }
+
+    /// One checkbox, a radio group whose options have export values in
+    /// `/Opt`, a dropdown and an editable dropdown
+    fn fixture() -> Fixture {
+        let mut form = TestForm::new();
+
+        let mut checkbox = states(&mut form, "Yes");
+        checkbox.set("FT", "Btn");
+        let checkbox = form.field("Final", checkbox, [50, 700, 62, 712]);
+
+        let radio = form.group(
+            "Position",
+            dictionary! {
+                "FT" => "Btn",
+                "Ff" => FLAG_RADIO,
+                "Opt" => vec![Object::string_literal("Student"), Object::string_literal("Staff")],
+            },
+        );
+        let first = states(&mut form, "0");
+        let first = form.kid(radio, None, first, [50, 650, 62, 662]);
+        let second = states(&mut form, "1");
+        let second = form.kid(radio, None, second, [100, 650, 112, 662]);
+
+        let options = vec![
+            Object::Array(vec![
+                Object::string_literal("CS"),
+                Object::string_literal("Computer Science"),
+            ]),
+            Object::Array(vec![
+                Object::string_literal("MATH"),
+                Object::string_literal("Mathematics"),
+            ]),
+        ];
+        let choice = form.field(
+            "Department",
+            dictionary! { "FT" => "Ch", "Ff" => FLAG_COMBO, "Opt" => options.clone() },
+            [50, 600, 200, 620],
+        );
+        let editable = form.field(
+            "Building",
+            dictionary! { "FT" => "Ch", "Ff" => FLAG_COMBO | FLAG_EDIT, "Opt" => options },
+            [50, 550, 200, 570],
+        );
+
+        Fixture {
+            doc: form.finish(),
+            checkbox,
+            radio,
+            radio_widgets: [first, second],
+            choice,
+            editable,
+        }
+    }
+
+    fn name(doc: &Document, id: ObjectId, key: &[u8]) -> String {
+        let value = doc.get_dictionary(id).unwrap().get(key).unwrap();
+        String::from_utf8(value.as_name().unwrap().to_vec()).unwrap()
+    }
+
+    fn text(doc: &Document, id: ObjectId, key: &[u8]) -> String {
+        let value = doc.get_dictionary(id).unwrap().get(key).unwrap();
+        lopdf::decode_text_string(value).unwrap()
+    }
+
+    #[test]
+    fn fills_a_checkbox() {
+        let Fixture { mut doc, checkbox, .. } = fixture();
+
+        fill_field(&mut doc, checkbox, "true").unwrap();
+        assert_eq!(name(&doc, checkbox, b"V"), "Yes");
+        assert_eq!(name(&doc, checkbox, b"AS"), "Yes");
+
+        fill_field(&mut doc, checkbox, "no").unwrap();
+        assert_eq!(name(&doc, checkbox, b"V"), "Off");
+        assert_eq!(name(&doc, checkbox, b"AS"), "Off");
+
+        // The widget's own on-state name works too
+        fill_field(&mut doc, checkbox, "Yes").unwrap();
+        assert_eq!(name(&doc, checkbox, b"AS"), "Yes");
+
+        let error = fill_field(&mut doc, checkbox, "maybe").unwrap_err();
+        assert!(error.starts_with("Checkbox value 'maybe' is not one of"), "{}", error);
+        assert_eq!(name(&doc, checkbox, b"AS"), "Yes");
+    }
+
+    #[test]
+    fn selects_a_radio_button_by_export_value_or_state() {
+        let Fixture {
+            mut doc,
+            radio,
+            radio_widgets: [student, staff],
+            ..
+        } = fixture();
+
+        // "Staff" is the second /Opt entry, so it turns on the second widget
+        fill_field(&mut doc, radio, "staff").unwrap();
+        assert_eq!(name(&doc, radio, b"V"), "1");
+        assert!(!doc.get_dictionary(staff).unwrap().has(b"V"));
+        assert_eq!(name(&doc, student, b"AS"), "Off");
+        assert_eq!(name(&doc, staff, b"AS"), "1");
+
+        // "0" names the first option here rather than meaning off
+        fill_field(&mut doc, radio, "0").unwrap();
+        assert_eq!(name(&doc, radio, b"V"), "0");
+        assert_eq!(name(&doc, student, b"AS"), "0");
+        assert_eq!(name(&doc, staff, b"AS"), "Off");
+
+        fill_field(&mut doc, radio, "off").unwrap();
+        assert_eq!(name(&doc, radio, b"V"), "Off");
+        assert_eq!(name(&doc, student, b"AS"), "Off");
+
+        let error = fill_field(&mut doc, radio, "Faculty").unwrap_err();
+        assert_eq!(
+            error,
+            r#"Radio value 'Faculty' is not one of ["0", "1", "Student", "Staff"]"#
+        );
+    }
+
+    #[test]
+    fn sets_a_choice_to_the_option_export_value() {
+        let Fixture {
+            mut doc,
+            choice,
+            editable,
+            ..
+        } = fixture();
+
+        fill_field(&mut doc, choice, "Mathematics").unwrap();
+        assert_eq!(text(&doc, choice, b"V"), "MATH");
+        assert_eq!(
+            doc.get_dictionary(choice).unwrap().get(b"I").unwrap(),
+            &Object::Array(vec![Object::Integer(1)])
+        );
+
+        fill_field(&mut doc, choice, "cs").unwrap();
+        assert_eq!(text(&doc, choice, b"V"), "CS");
+
+        let error = fill_field(&mut doc, choice, "Physics").unwrap_err();
+        assert_eq!(
+            error,
+            r#"Choice value 'Physics' is not one of ["Computer Science", "Mathematics"]"#
+        );
+        assert_eq!(text(&doc, choice, b"V"), "CS");
+
+        // Editable dropdowns take any text, with no option index
+        fill_field(&mut doc, editable, "Building D").unwrap();
+        assert_eq!(text(&doc, editable, b"V"), "Building D");
+        assert!(!doc.get_dictionary(editable).unwrap().has(b"I"));
+    }
+}
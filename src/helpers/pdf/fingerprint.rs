@@ -0,0 +1,290 @@
+use lopdf::Document;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use tracing::{error, info};
+
+use super::{list_form_fields, TemplateMapping};
+
+/// Rects may drift by this many points before a field counts as moved
+const RECT_TOLERANCE: f32 = 0.5;
+
+/// The field set of a template at the time its mapping was written
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TemplateFingerprint {
+    /// SHA-256 over the sorted field list, for a quick comparison in logs
+    pub digest: String,
+    pub fields: Vec<FingerprintField>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FingerprintField {
+    pub name: String,
+    pub field_type: Option<String>,
+    pub page: Option<u32>,
+    pub rect: Option<[f32; 4]>,
+}
+
+/// Differences between a recorded fingerprint and the current template
+#[derive(Debug, Default, Serialize)]
+pub struct FingerprintDiff {
+    pub added: Vec<FingerprintField>,
+    pub removed: Vec<FingerprintField>,
+    /// Fields whose page, Rect or type changed, as (recorded, current)
+    pub changed: Vec<(FingerprintField, FingerprintField)>,
+}
+
+impl TemplateFingerprint {
+    /// Fingerprint the form fields in a loaded template. Signature fields are
+    /// left out, since signing a filled timesheet adds one.
+    pub fn of(doc: &Document) -> Self {
+        let mut fields = list_form_fields(doc)
+            .into_iter()
+            .filter(|field| field.field_type.as_deref() != Some("Sig"))
+            .map(|field| FingerprintField {
+                name: field.name,
+                field_type: field.field_type,
+                page: field.page,
+                rect: field.rect,
+            })
+            .collect::<Vec<_>>();
+        fields.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut hasher = Sha256::new();
+        for field in &fields {
+            hasher.update(field.to_string().as_bytes());
+            hasher.update(b"\n");
+        }
+        let digest = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        Self { digest, fields }
+    }
+
+    /// Compare a recorded fingerprint (`self`) with a newer one
+    pub fn diff(&self, current: &TemplateFingerprint) -> FingerprintDiff {
+        let recorded = self
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), field))
+            .collect::<BTreeMap<_, _>>();
+        let current = current
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), field))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut diff = FingerprintDiff::default();
+        for (name, field) in &recorded {
+            match current.get(name) {
+                None => diff.removed.push((*field).clone()),
+                Some(now) if !field.matches(now) => {
+                    diff.changed.push(((*field).clone(), (*now).clone()))
+                }
+                Some(_) => {}
+            }
+        }
+        for (name, field) in &current {
+            if !recorded.contains_key(name) {
+                diff.added.push((*field).clone());
+            }
+        }
+        diff
+    }
+}
+
+impl FingerprintField {
+    fn matches(&self, other: &FingerprintField) -> bool {
+        let rects_match = match (self.rect, other.rect) {
+            (Some(a), Some(b)) => a
+                .iter()
+                .zip(b)
+                .all(|(a, b)| (a - b).abs() <= RECT_TOLERANCE),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        self.field_type == other.field_type && self.page == other.page && rects_match
+    }
+}
+
+impl fmt::Display for FingerprintField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}, page {})",
+            self.name,
+            self.field_type.as_deref().unwrap_or("?"),
+            self.page
+                .map(|page| page.to_string())
+                .unwrap_or("?".to_string())
+        )?;
+        if let Some([x1, y1, x2, y2]) = self.rect {
+            write!(f, " [{:.1} {:.1} {:.1} {:.1}]", x1, y1, x2, y2)?;
+        }
+        Ok(())
+    }
+}
+
+impl FingerprintDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for FingerprintDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for field in &self.added {
+            writeln!(f, "  + added   {}", field)?;
+        }
+        for field in &self.removed {
+            writeln!(f, "  - removed {}", field)?;
+        }
+        for (recorded, current) in &self.changed {
+            writeln!(f, "  ~ moved   {}", recorded)?;
+            writeln!(f, "         -> {}", current)?;
+        }
+        Ok(())
+    }
+}
+
+/// Fail if the template no longer matches the fingerprint recorded in its
+/// mapping. Mappings without a fingerprint are accepted as-is.
+pub(super) fn check_fingerprint(doc: &Document, mapping: &TemplateMapping) -> Result<(), String> {
+    let Some(recorded) = &mapping.fingerprint else {
+        return Ok(());
+    };
+
+    let current = TemplateFingerprint::of(doc);
+    if current.digest == recorded.digest {
+        info!("Template matches fingerprint {}", recorded.digest);
+        return Ok(());
+    }
+
+    // Digests also change on sub-tolerance Rect drift, so check field by field
+    let diff = recorded.diff(&current);
+    if diff.is_empty() {
+        info!("Template fields match fingerprint within tolerance");
+        return Ok(());
+    }
+
+    error!(
+        "Template {} has changed since its mapping was made:\n{}",
+        mapping.template_path, diff
+    );
+    Err(format!(
+        "Template {} has changed since its mapping was made ({} added, {} removed, {} moved):\n{}",
+        mapping.template_path,
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len(),
+        diff
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::pdf::testing::{sasi_template, save, TempPdf, TestForm};
+    use crate::helpers::pdf::{
+        create_sasi_timesheet, form_fields, PdfConfig, TimesheetData, TimesheetEntry,
+    };
+
+    /// A form of text fields, each at its own Rect
+    fn form(fields: &[(&str, [i64; 4])]) -> Document {
+        let mut form = TestForm::new();
+        for (name, rect) in fields {
+            form.text_field(name, "", *rect);
+        }
+        form.finish()
+    }
+
+    const HOURS: (&str, [i64; 4]) = ("Hours to be Paid1", [50, 700, 150, 720]);
+    const TOTAL: (&str, [i64; 4]) = ("Total hours", [50, 100, 150, 120]);
+
+    #[test]
+    fn is_stable_across_saves() {
+        let mut doc = sasi_template(3).finish();
+        let fingerprint = TemplateFingerprint::of(&doc);
+
+        let mut reloaded = Document::load_mem(&save(&mut doc)).unwrap();
+        let reloaded = Document::load_mem(&save(&mut reloaded)).unwrap();
+        assert_eq!(TemplateFingerprint::of(&reloaded).digest, fingerprint.digest);
+    }
+
+    #[test]
+    fn changes_when_a_field_is_renamed_moved_or_removed() {
+        let recorded = TemplateFingerprint::of(&form(&[HOURS, TOTAL]));
+
+        let renamed = TemplateFingerprint::of(&form(&[("Hours Paid1", HOURS.1), TOTAL]));
+        let diff = recorded.diff(&renamed);
+        assert_ne!(renamed.digest, recorded.digest);
+        assert_eq!(diff.added[0].name, "Hours Paid1");
+        assert_eq!(diff.removed[0].name, "Hours to be Paid1");
+        assert!(diff.changed.is_empty());
+
+        let moved = TemplateFingerprint::of(&form(&[(HOURS.0, [50, 650, 150, 670]), TOTAL]));
+        let diff = recorded.diff(&moved);
+        assert_ne!(moved.digest, recorded.digest);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].1.rect, Some([50.0, 650.0, 150.0, 670.0]));
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+
+        let removed = TemplateFingerprint::of(&form(&[TOTAL]));
+        let diff = recorded.diff(&removed);
+        assert_ne!(removed.digest, recorded.digest);
+        assert_eq!(diff.removed[0].name, "Hours to be Paid1");
+        assert!(diff.added.is_empty() && diff.changed.is_empty());
+    }
+
+    #[test]
+    fn fails_to_fill_a_template_that_no_longer_matches() {
+        // The mapping was made for two rows, and the revised form has one
+        let template = TempPdf::new(sasi_template(1).finish());
+        let config = PdfConfig {
+            mapping: TemplateMapping {
+                template_path: template.path(),
+                fingerprint: Some(TemplateFingerprint::of(&sasi_template(2).finish())),
+                ..TemplateMapping::default()
+            },
+            ..PdfConfig::default()
+        };
+        let data = TimesheetData::try_from(vec![TimesheetEntry {
+            month: 10,
+            day: 12,
+            start: "09:00".to_string(),
+            end: "12:00".to_string(),
+            paid_hours: 3.0,
+            notes: None,
+        }])
+        .unwrap();
+
+        let error = create_sasi_timesheet(data, &config).unwrap_err();
+        assert!(
+            error.contains("has changed since its mapping was made (0 added, 5 removed, 0 moved)"),
+            "{}",
+            error
+        );
+        assert!(error.contains("- removed Start Time2 (Tx, page 1)"), "{}", error);
+    }
+
+    #[test]
+    fn accepts_a_rect_that_drifted_within_tolerance() {
+        let recorded = TemplateFingerprint::of(&form(&[HOURS, TOTAL]));
+        let mut drifted = form(&[HOURS, TOTAL]);
+        let (hours_ref, _) = form_fields(&drifted)[0];
+        drifted
+            .get_dictionary_mut(hours_ref)
+            .unwrap()
+            .set("Rect", vec![50.2.into(), 700.into(), 150.into(), 720.into()]);
+        assert_ne!(TemplateFingerprint::of(&drifted).digest, recorded.digest);
+
+        let mapping = TemplateMapping {
+            fingerprint: Some(recorded),
+            ..TemplateMapping::default()
+        };
+        assert!(check_fingerprint(&drifted, &mapping).is_ok());
+    }
+}
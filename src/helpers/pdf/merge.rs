@@ -0,0 +1,358 @@
+use lopdf::{dictionary, Bookmark, Document, Object, ObjectId};
+use tracing::{error, info};
+
+use super::{render_timesheet, PdfConfig, TimesheetData};
+
+/// Page attributes a page can inherit from its parent in the page tree
+const INHERITED_PAGE_KEYS: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Render several timesheets, each with its own config, and merge them into
+/// one PDF with a bookmark per timesheet.
+///
+/// Each timesheet's form fields are moved under a parent field named
+/// `Timesheet1`, `Timesheet2`, ... so identical field names from the same
+/// template don't collide. Digital signatures in the configs are skipped,
+/// since merging would invalidate them; sign the merged output with
+/// [`super::sign_pdf`] instead.
+pub fn merge_timesheets(timesheets: Vec<(TimesheetData, PdfConfig)>) -> Result<Vec<u8>, String> {
+    if timesheets.is_empty() {
+        return Err("No timesheets to merge".to_string());
+    }
+    info!("Merging {} timesheets", timesheets.len());
+
+    let mut merged = Document::with_version("1.5");
+    let pages_id = merged.new_object_id();
+    let mut page_ids = Vec::new();
+    let mut form_fields = Vec::new();
+    let mut acroform_defaults: Option<lopdf::Dictionary> = None;
+    let mut embedded_files = Vec::new();
+    let mut associated_files = Vec::new();
+
+    for (index, (data, mut config)) in timesheets.into_iter().enumerate() {
+        let number = index + 1;
+        let title = bookmark_title(number, &data);
+
+        if config.pades.take().is_some() {
+            info!(
+                "Skipping digital signature for timesheet {} in merge",
+                number
+            );
+        }
+        let pdf = render_timesheet(data, &config)?;
+
+        let mut doc = Document::load_mem(&pdf).map_err(|e| {
+            error!("Rendered timesheet {} could not be parsed: {}", number, e);
+            format!("Rendered timesheet {} could not be parsed: {}", number, e)
+        })?;
+        doc.renumber_objects_with(merged.max_id + 1);
+        merged.max_id = doc.max_id;
+
+        // Flatten the page tree, copying anything a page inherited from it
+        let doc_pages = doc.get_pages().into_values().collect::<Vec<_>>();
+        for page_id in &doc_pages {
+            let inherited = inherited_page_attributes(&doc, *page_id);
+            let page = doc
+                .get_dictionary_mut(*page_id)
+                .map_err(|e| format!("Page is not a dictionary: {}", e))?;
+            for (key, value) in inherited {
+                page.set(key, value);
+            }
+            page.set("Parent", Object::Reference(pages_id));
+        }
+
+        let catalog = doc
+            .catalog()
+            .map_err(|e| format!("Rendered timesheet {} has no catalog: {}", number, e))?
+            .clone();
+
+        // Group the fields under one parent so their full names are unique
+        let acroform = catalog
+            .get(b"AcroForm")
+            .and_then(|acroform| doc.dereference(acroform))
+            .and_then(|(_, acroform)| acroform.as_dict())
+            .ok()
+            .cloned();
+        if let Some(acroform) = acroform {
+            let fields = acroform
+                .get(b"Fields")
+                .and_then(|fields| doc.dereference(fields))
+                .and_then(|(_, fields)| fields.as_array())
+                .map(|fields| {
+                    fields
+                        .iter()
+                        .filter_map(|field| field.as_reference().ok())
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+
+            if !fields.is_empty() {
+                merged.max_id += 1;
+                let parent_id = (merged.max_id, 0);
+                for field_id in &fields {
+                    if let Ok(field) = doc.get_dictionary_mut(*field_id) {
+                        field.set("Parent", Object::Reference(parent_id));
+                    }
+                }
+                doc.objects.insert(
+                    parent_id,
+                    Object::Dictionary(dictionary! {
+                        b"T" => Object::string_literal(format!("Timesheet{}", number)),
+                        b"Kids" => Object::Array(fields.into_iter().map(Object::Reference).collect()),
+                    }),
+                );
+                form_fields.push(parent_id);
+            }
+
+            if acroform_defaults.is_none() {
+                acroform_defaults = Some(acroform);
+            }
+        }
+
+        // Keep embedded data files, prefixing names with the timesheet number
+        let names = catalog
+            .get(b"Names")
+            .and_then(|names| doc.dereference(names))
+            .and_then(|(_, names)| names.as_dict())
+            .and_then(|names| names.get(b"EmbeddedFiles"))
+            .and_then(|files| doc.dereference(files))
+            .and_then(|(_, files)| files.as_dict())
+            .and_then(|files| files.get(b"Names"))
+            .and_then(Object::as_array)
+            .cloned()
+            .unwrap_or_default();
+        for pair in names.chunks(2) {
+            if let [Object::String(name, _), spec] = pair {
+                let name = format!("{}-{}", number, String::from_utf8_lossy(name));
+                embedded_files.push((name, spec.clone()));
+            }
+        }
+        if let Ok(files) = catalog.get(b"AF").and_then(Object::as_array) {
+            associated_files.extend(files.iter().cloned());
+        }
+
+        let first_page = *doc_pages
+            .first()
+            .ok_or(format!("Rendered timesheet {} has no pages", number))?;
+        merged.add_bookmark(Bookmark::new(title, [0.0, 0.0, 0.0], 0, first_page), None);
+
+        merged.objects.extend(doc.objects);
+        page_ids.extend(doc_pages);
+    }
+
+    merged.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            b"Type" => Object::Name(b"Pages".to_vec()),
+            b"Kids" => Object::Array(page_ids.iter().copied().map(Object::Reference).collect()),
+            b"Count" => Object::Integer(page_ids.len() as i64),
+        }),
+    );
+
+    let mut catalog = dictionary! {
+        b"Type" => Object::Name(b"Catalog".to_vec()),
+        b"Pages" => Object::Reference(pages_id),
+        b"PageMode" => Object::Name(b"UseOutlines".to_vec()),
+    };
+
+    if !form_fields.is_empty() {
+        let mut acroform = acroform_defaults.unwrap_or_default();
+        acroform.set(
+            "Fields",
+            Object::Array(form_fields.iter().copied().map(Object::Reference).collect()),
+        );
+        catalog.set("AcroForm", merged.add_object(acroform));
+    }
+
+    if !embedded_files.is_empty() {
+        // Name trees must be sorted by key
+        embedded_files.sort_by(|a, b| a.0.cmp(&b.0));
+        let names = embedded_files
+            .into_iter()
+            .flat_map(|(name, spec)| [Object::string_literal(name), spec])
+            .collect();
+        catalog.set(
+            "Names",
+            dictionary! { b"EmbeddedFiles" => dictionary! { b"Names" => Object::Array(names) } },
+        );
+        catalog.set("AF", Object::Array(associated_files));
+    }
+
+    if let Some(outline_id) = merged.build_outline() {
+        catalog.set("Outlines", Object::Reference(outline_id));
+    }
+
+    let catalog_id: ObjectId = merged.add_object(catalog);
+    merged.trailer.set("Root", Object::Reference(catalog_id));
+
+    // Drop the old catalogs, page trees and document info
+    merged.prune_objects();
+
+    let mut output_buffer: Vec<u8> = Vec::new();
+    match merged.save_to(&mut output_buffer) {
+        Ok(_) => info!(
+            "Merged timesheet PDF has {} pages, size: {} bytes",
+            page_ids.len(),
+            output_buffer.len()
+        ),
+        Err(e) => {
+            error!("Failed to convert merged PDF to bytes: {}", e);
+            return Err(format!("Failed to convert merged PDF to bytes: {}", e));
+        }
+    }
+
+    let reloaded = Document::load_mem(&output_buffer).map_err(|e| {
+        error!("Merged PDF could not be parsed: {}", e);
+        format!("Merged PDF could not be parsed: {}", e)
+    })?;
+    if reloaded.get_pages().len() != page_ids.len() {
+        error!("Merged PDF lost pages when saved");
+        return Err("Merged PDF lost pages when saved".to_string());
+    }
+
+    Ok(output_buffer)
+}
+
+fn bookmark_title(number: usize, data: &TimesheetData) -> String {
+    let mut title = match &data.employee {
+        Some(employee) => employee.clone(),
+        None => format!("Timesheet {}", number),
+    };
+    if let Some((start, end)) = data.period {
+        title.push_str(&format!(
+            " ({} to {})",
+            start.format("%b %-d"),
+            end.format("%b %-d, %Y")
+        ));
+    }
+    title
+}
+
+/// Attributes a page inherits from its ancestors and does not set itself
+fn inherited_page_attributes(doc: &Document, page_id: ObjectId) -> Vec<(Vec<u8>, Object)> {
+    let Ok(page) = doc.get_dictionary(page_id) else {
+        return Vec::new();
+    };
+
+    let mut inherited = Vec::new();
+    let mut parent = page.get(b"Parent").and_then(Object::as_reference).ok();
+    while let Some(parent_id) = parent {
+        let Ok(node) = doc.get_dictionary(parent_id) else {
+            break;
+        };
+        for key in INHERITED_PAGE_KEYS {
+            let already_set = page.has(key) || inherited.iter().any(|(k, _)| k == key);
+            if let (false, Ok(value)) = (already_set, node.get(key)) {
+                inherited.push((key.to_vec(), value.clone()));
+            }
+        }
+        parent = node.get(b"Parent").and_then(Object::as_reference).ok();
+    }
+    inherited
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::pdf::testing::{sasi_template, TempPdf};
+    use crate::helpers::pdf::{form_fields, TimesheetEntry};
+    use chrono::NaiveDate;
+    use std::collections::HashSet;
+
+    fn timesheet(employee: &str, hours: f64) -> TimesheetData {
+        let mut data = TimesheetData::try_from(vec![TimesheetEntry {
+            month: 10,
+            day: 12,
+            start: "09:00".to_string(),
+            end: "12:00".to_string(),
+            paid_hours: hours,
+            notes: None,
+        }])
+        .unwrap();
+        data.employee = Some(employee.to_string());
+        data.period = Some((
+            NaiveDate::from_ymd_opt(2026, 10, 9).unwrap(),
+            NaiveDate::from_ymd_opt(2026, 10, 23).unwrap(),
+        ));
+        data
+    }
+
+    /// The title and target page of each top-level bookmark, in order
+    fn bookmarks(doc: &Document) -> Vec<(String, ObjectId)> {
+        let mut bookmarks = Vec::new();
+        let mut item = doc
+            .catalog()
+            .and_then(|catalog| catalog.get(b"Outlines"))
+            .and_then(|outlines| doc.dereference(outlines))
+            .and_then(|(_, outlines)| outlines.as_dict())
+            .and_then(|outlines| outlines.get(b"First"))
+            .and_then(Object::as_reference)
+            .ok();
+        while let Some(item_id) = item {
+            let item_dict = doc.get_dictionary(item_id).unwrap();
+            let title = item_dict
+                .get(b"Title")
+                .and_then(lopdf::decode_text_string)
+                .unwrap();
+            let page = item_dict
+                .get(b"A")
+                .and_then(|action| doc.dereference(action))
+                .and_then(|(_, action)| action.as_dict())
+                .and_then(|action| action.get(b"D"))
+                .and_then(Object::as_array)
+                .and_then(|destination| destination[0].as_reference())
+                .unwrap();
+            bookmarks.push((title, page));
+            item = item_dict.get(b"Next").and_then(Object::as_reference).ok();
+        }
+        bookmarks
+    }
+
+    #[test]
+    fn merges_timesheets_with_unique_fields_and_a_bookmark_each() {
+        let template = TempPdf::new(sasi_template(2).finish());
+        let mut config = PdfConfig::default();
+        config.mapping.template_path = template.path();
+
+        let merged = merge_timesheets(vec![
+            (timesheet("Ada", 3.0), config.clone()),
+            (timesheet("Grace", 5.5), config),
+        ])
+        .unwrap();
+        let doc = Document::load_mem(&merged).unwrap();
+
+        let pages = doc.get_pages();
+        assert_eq!(pages.len(), 2);
+
+        let fields = form_fields(&doc);
+        let names = fields
+            .iter()
+            .map(|(_, name)| name.as_str())
+            .collect::<HashSet<_>>();
+        assert_eq!(names.len(), fields.len(), "field names are unique");
+        assert_eq!(fields.len(), 2 * 11);
+        assert!(names.contains("Timesheet1.Hours to be Paid1"));
+        assert!(names.contains("Timesheet2.Hours to be Paid1"));
+
+        // Each copy keeps its own values
+        let value = |name: &str| {
+            let (field_ref, _) = fields.iter().find(|(_, n)| n == name).unwrap();
+            let field = doc.get_dictionary(*field_ref).unwrap();
+            lopdf::decode_text_string(field.get(b"V").unwrap()).unwrap()
+        };
+        assert_eq!(value("Timesheet1.Total hours"), "3");
+        assert_eq!(value("Timesheet2.Total hours"), "5.5");
+
+        assert_eq!(
+            bookmarks(&doc),
+            [
+                ("Ada (Oct 9 to Oct 23, 2026)".to_string(), pages[&1]),
+                ("Grace (Oct 9 to Oct 23, 2026)".to_string(), pages[&2]),
+            ]
+        );
+    }
+
+    #[test]
+    fn refuses_an_empty_merge() {
+        assert!(merge_timesheets(Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,340 @@
+use chrono::Local;
+use lopdf::{dictionary, text_string, Document, Object, ObjectId, Stream, StringFormat};
+use tracing::info;
+
+use super::TimesheetData;
+use crate::helpers::export::timesheet_csv;
+
+const GENERATOR: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
+/// Attach JSON and CSV copies of the timesheet as embedded files and record
+/// the employee, period and total in the document info and XMP metadata
+pub(super) fn embed_timesheet_data(doc: &mut Document, data: &TimesheetData) -> Result<(), String> {
+    let catalog_id = doc
+        .trailer
+        .get(b"Root")
+        .and_then(Object::as_reference)
+        .map_err(|_| "PDF has no catalog".to_string())?;
+
+    let json = serde_json::to_vec_pretty(&timesheet_json(data)?)
+        .map_err(|e| format!("Failed to serialize timesheet data: {}", e))?;
+    let csv = timesheet_csv(data)?;
+
+    let csv_spec = add_embedded_file(doc, "timesheet.csv", "text/csv", csv);
+    let json_spec = add_embedded_file(doc, "timesheet.json", "application/json", json);
+
+    let names_id = doc
+        .get_dictionary(catalog_id)
+        .and_then(|catalog| catalog.get(b"Names"))
+        .and_then(Object::as_reference)
+        .ok();
+    let names = match names_id {
+        Some(id) => doc.get_dictionary(id),
+        None => doc
+            .get_dictionary(catalog_id)
+            .and_then(|catalog| catalog.get(b"Names"))
+            .and_then(Object::as_dict),
+    };
+    let existing_tree = names
+        .ok()
+        .and_then(|names| names.get(b"EmbeddedFiles").ok());
+
+    // Keep the template's own attachments. Name trees must be sorted by key,
+    // so the merged tree is written out as a single sorted leaf.
+    let mut files = Vec::new();
+    if let Some(tree) = existing_tree {
+        collect_names(doc, tree, &mut files);
+    }
+    // An earlier copy of the timesheet data is replaced, not kept alongside
+    let (replaced, mut files): (Vec<_>, Vec<_>) = files
+        .into_iter()
+        .partition(|(name, _)| name == b"timesheet.csv" || name == b"timesheet.json");
+    files.push((b"timesheet.csv".to_vec(), Object::Reference(csv_spec)));
+    files.push((b"timesheet.json".to_vec(), Object::Reference(json_spec)));
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    let embedded_files = dictionary! {
+        b"Names" => Object::Array(
+            files
+                .into_iter()
+                .flat_map(|(name, spec)| [Object::String(name, StringFormat::Literal), spec])
+                .collect(),
+        ),
+    };
+
+    let names = match names_id {
+        Some(id) => doc.get_dictionary_mut(id),
+        None => {
+            let catalog = doc
+                .get_dictionary_mut(catalog_id)
+                .map_err(|e| e.to_string())?;
+            if !catalog.has(b"Names") {
+                catalog.set("Names", dictionary! {});
+            }
+            catalog.get_mut(b"Names").and_then(Object::as_dict_mut)
+        }
+    }
+    .map_err(|e| format!("Catalog Names is not a dictionary: {}", e))?;
+    names.set("EmbeddedFiles", embedded_files);
+
+    let metadata_id = doc.add_object(xmp_metadata(data));
+    let catalog = doc
+        .get_dictionary_mut(catalog_id)
+        .map_err(|e| e.to_string())?;
+    let mut associated_files = catalog
+        .get(b"AF")
+        .and_then(Object::as_array)
+        .cloned()
+        .unwrap_or_default();
+    associated_files.retain(|file| !replaced.iter().any(|(_, spec)| spec == file));
+    associated_files.push(Object::Reference(csv_spec));
+    associated_files.push(Object::Reference(json_spec));
+    catalog.set("AF", Object::Array(associated_files));
+    catalog.set("Metadata", Object::Reference(metadata_id));
+
+    let info_id = doc.add_object(document_info(data));
+    doc.trailer.set("Info", Object::Reference(info_id));
+
+    info!(
+        "Embedded timesheet data and metadata ({} entries, {} hours)",
+        data.entries.len(),
+        data.total_hours
+    );
+
+    Ok(())
+}
+
+/// Gather the key and value pairs of a name tree, following `/Kids` down
+/// to the leaves
+fn collect_names(doc: &Document, node: &Object, names: &mut Vec<(Vec<u8>, Object)>) {
+    let Ok((_, node)) = doc.dereference(node) else {
+        return;
+    };
+    let Ok(node) = node.as_dict() else {
+        return;
+    };
+
+    if let Ok(pairs) = node.get(b"Names").and_then(Object::as_array) {
+        for pair in pairs.chunks_exact(2) {
+            if let Ok(name) = pair[0].as_str() {
+                names.push((name.to_vec(), pair[1].clone()));
+            }
+        }
+    }
+    if let Ok(kids) = node.get(b"Kids").and_then(Object::as_array) {
+        for kid in kids {
+            collect_names(doc, kid, names);
+        }
+    }
+}
+
+fn add_embedded_file(
+    doc: &mut Document,
+    filename: &str,
+    mime_type: &str,
+    content: Vec<u8>,
+) -> ObjectId {
+    let size = content.len() as i64;
+    let mut file_stream = Stream::new(
+        dictionary! {
+            b"Type" => Object::Name(b"EmbeddedFile".to_vec()),
+            b"Subtype" => Object::Name(mime_type.as_bytes().to_vec()),
+            b"Params" => dictionary! {
+                b"Size" => Object::Integer(size),
+                b"ModDate" => Object::from(Local::now()),
+            },
+        },
+        content,
+    );
+    let _ = file_stream.compress();
+    let file_id = doc.add_object(file_stream);
+
+    doc.add_object(dictionary! {
+        b"Type" => Object::Name(b"Filespec".to_vec()),
+        b"F" => Object::string_literal(filename),
+        b"UF" => Object::string_literal(filename),
+        b"Desc" => Object::string_literal("Machine-readable copy of this timesheet"),
+        b"AFRelationship" => Object::Name(b"Data".to_vec()),
+        b"EF" => dictionary! { b"F" => Object::Reference(file_id) },
+    })
+}
+
+fn timesheet_json(data: &TimesheetData) -> Result<serde_json::Value, String> {
+    let mut json = serde_json::to_value(data)
+        .map_err(|e| format!("Failed to serialize timesheet data: {}", e))?;
+    if let Some(object) = json.as_object_mut() {
+        object.insert("generator".to_string(), GENERATOR.into());
+    }
+    Ok(json)
+}
+
+fn title(data: &TimesheetData) -> String {
+    match data.period {
+        Some((start, end)) => format!("Timesheet {} to {}", start, end),
+        None => "Timesheet".to_string(),
+    }
+}
+
+fn document_info(data: &TimesheetData) -> lopdf::Dictionary {
+    let mut info = dictionary! {
+        b"Title" => Object::string_literal(title(data)),
+        b"Subject" => Object::string_literal(format!("Total hours: {}", data.total_hours)),
+        b"Creator" => Object::string_literal(GENERATOR),
+        b"Producer" => Object::string_literal(GENERATOR),
+        b"CreationDate" => Object::from(Local::now()),
+        b"TotalHours" => Object::string_literal(data.total_hours.to_string()),
+        b"GeneratorVersion" => Object::string_literal(env!("CARGO_PKG_VERSION")),
+    };
+
+    if let Some(employee) = &data.employee {
+        info.set("Author", text_string(employee));
+        info.set("Employee", text_string(employee));
+    }
+    if let Some((start, end)) = data.period {
+        info.set("PayPeriodStart", Object::string_literal(start.to_string()));
+        info.set("PayPeriodEnd", Object::string_literal(end.to_string()));
+    }
+
+    info
+}
+
+fn xmp_metadata(data: &TimesheetData) -> Stream {
+    let escape = |value: &str| {
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+    let employee = data.employee.as_deref().map(escape).unwrap_or_default();
+    let (period_start, period_end) = data
+        .period
+        .map(|(start, end)| (start.to_string(), end.to_string()))
+        .unwrap_or_default();
+
+    let xmp = format!(
+        r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:pdf="http://ns.adobe.com/pdf/1.3/"
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:ufvts="https://github.com/devnull03/ufv-timesheet-util/ns/1.0/">
+   <dc:title><rdf:Alt><rdf:li xml:lang="x-default">{title}</rdf:li></rdf:Alt></dc:title>
+   <dc:creator><rdf:Seq><rdf:li>{employee}</rdf:li></rdf:Seq></dc:creator>
+   <pdf:Producer>{generator}</pdf:Producer>
+   <xmp:CreatorTool>{generator}</xmp:CreatorTool>
+   <xmp:CreateDate>{created}</xmp:CreateDate>
+   <ufvts:Employee>{employee}</ufvts:Employee>
+   <ufvts:PayPeriodStart>{period_start}</ufvts:PayPeriodStart>
+   <ufvts:PayPeriodEnd>{period_end}</ufvts:PayPeriodEnd>
+   <ufvts:TotalHours>{total_hours}</ufvts:TotalHours>
+   <ufvts:EntryCount>{entry_count}</ufvts:EntryCount>
+   <ufvts:GeneratorVersion>{version}</ufvts:GeneratorVersion>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#,
+        title = escape(&title(data)),
+        employee = employee,
+        generator = GENERATOR,
+        created = Local::now().to_rfc3339(),
+        period_start = period_start,
+        period_end = period_end,
+        total_hours = data.total_hours,
+        entry_count = data.entries.len(),
+        version = env!("CARGO_PKG_VERSION"),
+    );
+
+    // XMP is left uncompressed so it stays readable by tools that scan for it
+    Stream::new(
+        dictionary! {
+            b"Type" => Object::Name(b"Metadata".to_vec()),
+            b"Subtype" => Object::Name(b"XML".to_vec()),
+        },
+        xmp.into_bytes(),
+    )
+    .with_compression(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::pdf::testing::{save, TestForm};
+    use crate::helpers::pdf::TimesheetEntry;
+
+    /// Name and content of every file in the embedded files name tree
+    fn embedded_files(pdf: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let doc = Document::load_mem(pdf).unwrap();
+        let tree = doc
+            .catalog()
+            .and_then(|catalog| catalog.get(b"Names"))
+            .and_then(|names| doc.dereference(names))
+            .and_then(|(_, names)| names.as_dict())
+            .and_then(|names| names.get(b"EmbeddedFiles"))
+            .unwrap();
+        let mut names = Vec::new();
+        collect_names(&doc, tree, &mut names);
+
+        names
+            .into_iter()
+            .map(|(name, spec)| {
+                let file_id = doc
+                    .dereference(&spec)
+                    .and_then(|(_, spec)| spec.as_dict())
+                    .and_then(|spec| spec.get(b"EF"))
+                    .and_then(Object::as_dict)
+                    .and_then(|files| files.get(b"F"))
+                    .and_then(Object::as_reference)
+                    .unwrap();
+                let stream = doc.get_object(file_id).and_then(Object::as_stream).unwrap();
+                let content = stream
+                    .decompressed_content()
+                    .unwrap_or_else(|_| stream.content.clone());
+                (String::from_utf8(name).unwrap(), content)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keeps_attachments_already_in_the_template() {
+        let mut doc = TestForm::new().finish();
+        let policy =
+            add_embedded_file(&mut doc, "policy.txt", "text/plain", b"Be on time".to_vec());
+        let names_id = doc.add_object(dictionary! {
+            b"EmbeddedFiles" => dictionary! {
+                b"Names" => vec![Object::string_literal("policy.txt"), policy.into()],
+            },
+        });
+        doc.catalog_mut().unwrap().set("Names", names_id);
+        doc.catalog_mut().unwrap().set("AF", vec![policy.into()]);
+
+        let data = TimesheetData::try_from(vec![TimesheetEntry {
+            month: 10,
+            day: 12,
+            start: "09:00".to_string(),
+            end: "12:00".to_string(),
+            paid_hours: 3.0,
+            notes: None,
+        }])
+        .unwrap();
+        embed_timesheet_data(&mut doc, &data).unwrap();
+        // Embedding again replaces the timesheet files rather than adding more
+        embed_timesheet_data(&mut doc, &data).unwrap();
+        let pdf = save(&mut doc);
+
+        let files = embedded_files(&pdf);
+        let names = files
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["policy.txt", "timesheet.csv", "timesheet.json"]);
+        assert_eq!(files[0].1, b"Be on time");
+        let json: serde_json::Value = serde_json::from_slice(&files[2].1).unwrap();
+        assert_eq!(json["total_hours"], 3.0);
+
+        let doc = Document::load_mem(&pdf).unwrap();
+        let associated_files = doc.catalog().and_then(|catalog| catalog.get(b"AF"));
+        assert_eq!(associated_files.and_then(Object::as_array).unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,270 @@
+use lopdf::{dictionary, text_string, Document, Object, ObjectId, Stream};
+use tracing::{error, info};
+
+use super::standalone::{text, wrap_text};
+use super::{
+    field_page, field_rect, fields, fields_with_prefix, PdfConfig, TimesheetData, TimesheetEntry,
+};
+
+const MARGIN: f32 = 50.0;
+const LINE_HEIGHT: f32 = 13.0;
+const NOTE_LINE_CHARS: usize = 95;
+
+/// Where entry notes go when the template has no notes field
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NotesPlacement {
+    /// A comment icon next to each row's hours field, with the notes as its text
+    #[default]
+    Annotations,
+    /// Extra page(s) at the end listing the notes for each date
+    Appendix,
+}
+
+/// Notes flattened onto one line, as written into a notes form field
+pub(super) fn note_field_value(notes: Option<&str>) -> String {
+    notes
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Put each entry's notes into the template's notes fields, or into
+/// annotations or an appendix when the template has none
+pub(super) fn place_notes(
+    doc: &mut Document,
+    form_fields: &[(ObjectId, String)],
+    data: &TimesheetData,
+    config: &PdfConfig,
+) -> Result<(), String> {
+    let noted = data
+        .entries
+        .iter()
+        .filter(|entry| entry.notes.is_some())
+        .count();
+    if noted == 0 {
+        return Ok(());
+    }
+
+    let note_fields = match &config.mapping.notes {
+        Some(prefix) => fields_with_prefix(form_fields, prefix).collect(),
+        None => Vec::new(),
+    };
+
+    if !note_fields.is_empty() {
+        if note_fields.len() < data.entries.len() {
+            error!(
+                "Template has {} notes fields for {} entries",
+                note_fields.len(),
+                data.entries.len()
+            );
+            return Err(format!(
+                "Template has {} notes fields for {} entries",
+                note_fields.len(),
+                data.entries.len()
+            ));
+        }
+
+        for (entry, field_ref) in data.entries.iter().zip(note_fields) {
+            let value = note_field_value(entry.notes.as_deref());
+            if !value.is_empty() {
+                fields::fill_field(doc, field_ref, &value)?;
+            }
+        }
+        info!("Filled {} notes fields", noted);
+        return Ok(());
+    }
+
+    match config.notes {
+        NotesPlacement::Annotations => add_note_annotations(doc, form_fields, data, config),
+        NotesPlacement::Appendix => add_notes_appendix(doc, data),
+    }
+}
+
+/// Short label for an entry, e.g. `Oct 12, 09:00-12:00`
+fn entry_label(data: &TimesheetData, entry: &TimesheetEntry) -> String {
+    let date = match data.entry_date(entry) {
+        Some(date) => date.format("%b %-d").to_string(),
+        None => format!("{}/{}", entry.month, entry.day),
+    };
+    format!("{}, {}-{}", date, entry.start, entry.end)
+}
+
+/// Attach a text annotation to the right of each row's hours field
+fn add_note_annotations(
+    doc: &mut Document,
+    form_fields: &[(ObjectId, String)],
+    data: &TimesheetData,
+    config: &PdfConfig,
+) -> Result<(), String> {
+    let hours_fields = form_fields
+        .iter()
+        .filter(|(_, name)| name.starts_with(&config.mapping.hours));
+
+    let mut added = 0;
+    for (entry, (field_ref, name)) in data.entries.iter().zip(hours_fields) {
+        let Some(notes) = &entry.notes else {
+            continue;
+        };
+
+        let field_ref = *field_ref;
+        let field_dict = doc
+            .get_dictionary(field_ref)
+            .map_err(|e| format!("Hours field '{}' is not a dictionary: {}", name, e))?;
+        let rect = field_rect(field_dict)
+            .ok_or_else(|| format!("Hours field '{}' has no Rect", name))?;
+        let page_id = field_page(doc, name, field_ref, field_dict)?;
+
+        let annotation_id = doc.add_object(dictionary! {
+            b"Type" => Object::Name(b"Annot".to_vec()),
+            b"Subtype" => Object::Name(b"Text".to_vec()),
+            b"Rect" => Object::Array(vec![
+                Object::Real(rect[2] + 2.0),
+                Object::Real(rect[3] - 14.0),
+                Object::Real(rect[2] + 16.0),
+                Object::Real(rect[3]),
+            ]),
+            b"Contents" => text_string(notes),
+            b"T" => text_string(&entry_label(data, entry)),
+            b"Name" => Object::Name(b"Comment".to_vec()),
+            // Print the icon so paper copies show there is a note
+            b"F" => Object::Integer(4),
+            b"Open" => Object::Boolean(false),
+            b"P" => Object::Reference(page_id),
+        });
+
+        let annots_id = doc
+            .get_dictionary(page_id)
+            .and_then(|page| page.get(b"Annots"))
+            .and_then(Object::as_reference)
+            .ok();
+        let annots = match annots_id {
+            Some(id) => doc.get_object_mut(id),
+            None => {
+                let page = doc.get_dictionary_mut(page_id).map_err(|e| e.to_string())?;
+                if !page.has(b"Annots") {
+                    page.set("Annots", Object::Array(vec![]));
+                }
+                page.get_mut(b"Annots")
+            }
+        }
+        .map_err(|e| e.to_string())?;
+        annots
+            .as_array_mut()
+            .map_err(|_| "Page Annots is not an array".to_string())?
+            .push(Object::Reference(annotation_id));
+
+        added += 1;
+    }
+
+    info!("Added {} note annotations", added);
+    Ok(())
+}
+
+/// Append page(s) listing every entry that has notes
+fn add_notes_appendix(doc: &mut Document, data: &TimesheetData) -> Result<(), String> {
+    let pages_id = doc
+        .catalog()
+        .and_then(|catalog| catalog.get(b"Pages"))
+        .and_then(Object::as_reference)
+        .map_err(|_| "PDF has no page tree".to_string())?;
+
+    // Match the size of the first page
+    let media_box = doc
+        .get_pages()
+        .values()
+        .next()
+        .and_then(|page_id| doc.get_dictionary(*page_id).ok())
+        .and_then(|page| page.get(b"MediaBox").ok())
+        .cloned();
+    let page_height = media_box
+        .as_ref()
+        .and_then(|media_box| media_box.as_array().ok())
+        .and_then(|media_box| media_box.get(3))
+        .and_then(|top| top.as_float().ok())
+        .unwrap_or(792.0);
+
+    let mut lines = Vec::new();
+    for entry in &data.entries {
+        let Some(notes) = &entry.notes else {
+            continue;
+        };
+        lines.push(("F2", entry_label(data, entry)));
+        for line in wrap_text(notes, NOTE_LINE_CHARS) {
+            lines.push(("F1", line));
+        }
+        lines.push(("F1", String::new()));
+    }
+
+    let font_id = doc.add_object(dictionary! {
+        b"Type" => Object::Name(b"Font".to_vec()),
+        b"Subtype" => Object::Name(b"Type1".to_vec()),
+        b"BaseFont" => Object::Name(b"Helvetica".to_vec()),
+        b"Encoding" => Object::Name(b"WinAnsiEncoding".to_vec()),
+    });
+    let bold_font_id = doc.add_object(dictionary! {
+        b"Type" => Object::Name(b"Font".to_vec()),
+        b"Subtype" => Object::Name(b"Type1".to_vec()),
+        b"BaseFont" => Object::Name(b"Helvetica-Bold".to_vec()),
+        b"Encoding" => Object::Name(b"WinAnsiEncoding".to_vec()),
+    });
+
+    let lines_per_page = ((page_height - 2.0 * MARGIN - 30.0) / LINE_HEIGHT) as usize;
+    let mut page_ids = Vec::new();
+
+    for (index, chunk) in lines.chunks(lines_per_page.max(1)).enumerate() {
+        let mut content = String::new();
+        let heading = if index == 0 {
+            "Notes"
+        } else {
+            "Notes (continued)"
+        };
+        text(
+            &mut content,
+            "F2",
+            14.0,
+            MARGIN,
+            page_height - MARGIN,
+            heading,
+        );
+
+        let mut y = page_height - MARGIN - 30.0;
+        for (font, line) in chunk {
+            if !line.is_empty() {
+                text(&mut content, font, 10.0, MARGIN, y, line);
+            }
+            y -= LINE_HEIGHT;
+        }
+
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.into_bytes()));
+        let mut page = dictionary! {
+            b"Type" => Object::Name(b"Page".to_vec()),
+            b"Parent" => Object::Reference(pages_id),
+            b"Contents" => Object::Reference(content_id),
+            b"Resources" => dictionary! {
+                b"Font" => dictionary! {
+                    b"F1" => Object::Reference(font_id),
+                    b"F2" => Object::Reference(bold_font_id),
+                },
+            },
+        };
+        if let Some(media_box) = &media_box {
+            page.set("MediaBox", media_box.clone());
+        }
+        page_ids.push(doc.add_object(page));
+    }
+
+    let pages = doc
+        .get_dictionary_mut(pages_id)
+        .map_err(|e| format!("Page tree is not a dictionary: {}", e))?;
+    let count = pages.get(b"Count").and_then(Object::as_i64).unwrap_or(0);
+    pages.set("Count", Object::Integer(count + page_ids.len() as i64));
+    pages
+        .get_mut(b"Kids")
+        .and_then(Object::as_array_mut)
+        .map_err(|_| "Page tree Kids is not an array".to_string())?
+        .extend(page_ids.iter().copied().map(Object::Reference));
+
+    info!("Added {} notes appendix page(s)", page_ids.len());
+    Ok(())
+}
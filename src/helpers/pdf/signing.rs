@@ -0,0 +1,604 @@
+use chrono::Local;
+use cms::{
+    builder::{SignedDataBuilder, SignerInfoBuilder},
+    cert::{CertificateChoices, IssuerAndSerialNumber},
+    content_info::ContentInfo,
+    signed_data::{EncapsulatedContentInfo, SignedData, SignerIdentifier},
+};
+use const_oid::db::{rfc5911, rfc5912};
+use lopdf::{dictionary, Document, IncrementalDocument, Object, ObjectId, StringFormat};
+use p12_keystore::KeyStore;
+use rsa::{
+    pkcs1v15::{Signature, SigningKey, VerifyingKey},
+    pkcs8::{DecodePrivateKey, DecodePublicKey},
+    signature::Verifier,
+    RsaPrivateKey, RsaPublicKey,
+};
+use sha2::{Digest, Sha256};
+use tracing::{error, info};
+use x509_cert::{
+    attr::{Attribute, AttributeValue},
+    der::{asn1::OctetString, Decode, Encode, SliceReader},
+    spki::AlgorithmIdentifierOwned,
+    Certificate,
+};
+
+/// Bytes reserved for the CMS signature inside `/Contents`
+const SIGNATURE_SIZE: usize = 8192;
+
+/// Stand-in for ByteRange values until the final offsets are known
+const BYTE_RANGE_PLACEHOLDER: i64 = 9_999_999_999;
+
+/// A PKCS#12 keystore used to digitally sign generated timesheets
+#[derive(Clone)]
+pub struct PadesConfig {
+    pub keystore_path: String,
+    pub password: String,
+    pub reason: Option<String>,
+    pub location: Option<String>,
+}
+
+/// Result of checking the signature on a timesheet
+#[derive(Debug)]
+pub struct SignatureInfo {
+    /// Subject of the signing certificate
+    pub signer: String,
+    /// The `/M` signing time recorded in the signature dictionary
+    pub signed_at: Option<String>,
+    /// False when bytes were appended after the signature (e.g. a later
+    /// incremental update), meaning only an earlier revision is covered
+    pub covers_whole_document: bool,
+}
+
+/// Apply a detached CAdES (PAdES B-B) signature to a PDF as an incremental update.
+///
+/// Only RSA keys are supported.
+pub fn sign_pdf(pdf: Vec<u8>, config: &PadesConfig) -> Result<Vec<u8>, String> {
+    info!("Signing timesheet with keystore: {}", config.keystore_path);
+
+    let (signing_key, chain) = load_keystore(config)?;
+    let certificate = chain
+        .first()
+        .cloned()
+        .ok_or("Keystore does not contain a certificate".to_string())?;
+
+    let doc = Document::load_mem(&pdf).map_err(|e| format!("Failed to parse PDF: {}", e))?;
+    let mut incremental = IncrementalDocument::create_from(pdf, doc);
+
+    let signer_name = certificate.tbs_certificate.subject.to_string();
+    let mut signature_dict = dictionary! {
+        b"Type" => Object::Name(b"Sig".to_vec()),
+        b"Filter" => Object::Name(b"Adobe.PPKLite".to_vec()),
+        b"SubFilter" => Object::Name(b"ETSI.CAdES.detached".to_vec()),
+        b"ByteRange" => Object::Array(vec![
+            Object::Integer(0),
+            Object::Integer(BYTE_RANGE_PLACEHOLDER),
+            Object::Integer(BYTE_RANGE_PLACEHOLDER),
+            Object::Integer(BYTE_RANGE_PLACEHOLDER),
+        ]),
+        b"Contents" => Object::String(vec![0; SIGNATURE_SIZE], StringFormat::Hexadecimal),
+        b"M" => Object::from(Local::now()),
+        b"Name" => Object::string_literal(signer_name.clone()),
+    };
+    if let Some(reason) = &config.reason {
+        signature_dict.set("Reason", Object::string_literal(reason.clone()));
+    }
+    if let Some(location) = &config.location {
+        signature_dict.set("Location", Object::string_literal(location.clone()));
+    }
+
+    add_signature_field(&mut incremental, signature_dict)?;
+
+    let mut output = Vec::new();
+    incremental
+        .save_to(&mut output)
+        .map_err(|e| format!("Failed to write signed PDF: {}", e))?;
+
+    let (contents_start, contents_end) = find_contents_placeholder(&output)?;
+    let byte_range = [0, contents_start, contents_end, output.len() - contents_end];
+    write_byte_range(&mut output, &byte_range)?;
+
+    let digest = Sha256::new()
+        .chain_update(&output[..contents_start])
+        .chain_update(&output[contents_end..])
+        .finalize();
+
+    let cms = build_cms_signature(&signing_key, &chain, &digest)?;
+    let hex = cms
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<String>();
+    if hex.len() > SIGNATURE_SIZE * 2 {
+        error!(
+            "CMS signature is {} bytes, too large for placeholder",
+            cms.len()
+        );
+        return Err("CMS signature does not fit in the reserved space".to_string());
+    }
+    output[contents_start + 1..contents_start + 1 + hex.len()].copy_from_slice(hex.as_bytes());
+
+    info!(
+        "Signed timesheet as '{}', signature size: {} bytes",
+        signer_name,
+        cms.len()
+    );
+
+    Ok(output)
+}
+
+/// Check the most recent signature on a PDF against the bytes it covers.
+///
+/// This confirms the document is unchanged since signing and reports who signed
+/// it; it does not check the certificate against a trust store.
+pub fn verify_signature(pdf: &[u8]) -> Result<SignatureInfo, String> {
+    let doc = Document::load_mem(pdf).map_err(|e| format!("Failed to parse PDF: {}", e))?;
+
+    let signature_dict = doc
+        .objects
+        .values()
+        .filter_map(|object| object.as_dict().ok())
+        .filter(|dict| dict.has(b"ByteRange") && dict.has(b"Contents"))
+        .max_by_key(|dict| byte_range(dict).map(|range| range[2]).unwrap_or(0))
+        .ok_or("PDF is not signed".to_string())?;
+
+    let range = byte_range(signature_dict)?;
+    if range[0] != 0
+        || range[1] > range[2]
+        || range[2]
+            .checked_add(range[3])
+            .is_none_or(|end| end > pdf.len())
+    {
+        return Err("Signature ByteRange is outside the document".to_string());
+    }
+
+    let digest = Sha256::new()
+        .chain_update(&pdf[range[0]..range[1]])
+        .chain_update(&pdf[range[2]..range[2] + range[3]])
+        .finalize();
+
+    let contents = signature_dict
+        .get(b"Contents")
+        .and_then(Object::as_str)
+        .map_err(|_| "Signature has no Contents".to_string())?;
+    // The only bytes left out may be the `/Contents` hex string itself
+    if decode_hex_string(&pdf[range[1]..range[2]]).as_deref() != Some(contents) {
+        return Err("Signature ByteRange does not exclude exactly its Contents".to_string());
+    }
+    let mut reader = SliceReader::new(contents).map_err(|e| e.to_string())?;
+    let content_info =
+        ContentInfo::decode(&mut reader).map_err(|e| format!("Invalid CMS signature: {}", e))?;
+    let signed_data = content_info
+        .content
+        .decode_as::<SignedData>()
+        .map_err(|e| format!("Invalid CMS SignedData: {}", e))?;
+
+    let signer_info = signed_data
+        .signer_infos
+        .0
+        .iter()
+        .next()
+        .ok_or("Signature has no signer".to_string())?;
+    let signed_attrs = signer_info
+        .signed_attrs
+        .as_ref()
+        .ok_or("Signature has no signed attributes".to_string())?;
+
+    let message_digest = signed_attrs
+        .iter()
+        .find(|attr| attr.oid == rfc5911::ID_MESSAGE_DIGEST)
+        .and_then(|attr| attr.values.iter().next())
+        .and_then(|value| value.decode_as::<OctetString>().ok())
+        .ok_or("Signature has no message digest".to_string())?;
+    if message_digest.as_bytes() != digest.as_slice() {
+        return Err("Document has been modified since it was signed".to_string());
+    }
+
+    let certificate = signed_data
+        .certificates
+        .as_ref()
+        .and_then(|certs| {
+            certs.0.iter().find_map(|choice| match choice {
+                CertificateChoices::Certificate(cert) => match &signer_info.sid {
+                    SignerIdentifier::IssuerAndSerialNumber(id)
+                        if id.issuer != cert.tbs_certificate.issuer
+                            || id.serial_number != cert.tbs_certificate.serial_number =>
+                    {
+                        None
+                    }
+                    _ => Some(cert),
+                },
+                _ => None,
+            })
+        })
+        .ok_or("Signing certificate not included in signature".to_string())?;
+
+    let public_key = certificate
+        .tbs_certificate
+        .subject_public_key_info
+        .to_der()
+        .map_err(|e| e.to_string())
+        .and_then(|der| RsaPublicKey::from_public_key_der(&der).map_err(|e| e.to_string()))
+        .map_err(|e| format!("Unsupported signing key: {}", e))?;
+    let signature = Signature::try_from(signer_info.signature.as_bytes())
+        .map_err(|e| format!("Invalid signature value: {}", e))?;
+    let signed_attrs_der = signed_attrs.to_der().map_err(|e| e.to_string())?;
+
+    VerifyingKey::<Sha256>::new(public_key)
+        .verify(&signed_attrs_der, &signature)
+        .map_err(|_| "Signature does not match the signing certificate".to_string())?;
+
+    let info = SignatureInfo {
+        signer: certificate.tbs_certificate.subject.to_string(),
+        signed_at: signature_dict
+            .get(b"M")
+            .and_then(Object::as_str)
+            .ok()
+            .map(|m| String::from_utf8_lossy(m).into_owned()),
+        covers_whole_document: range[2] + range[3] == pdf.len(),
+    };
+
+    info!(
+        "Verified signature by '{}', covers whole document: {}",
+        info.signer, info.covers_whole_document
+    );
+
+    Ok(info)
+}
+
+fn load_keystore(config: &PadesConfig) -> Result<(SigningKey<Sha256>, Vec<Certificate>), String> {
+    let keystore_bytes = std::fs::read(&config.keystore_path)
+        .map_err(|e| format!("Failed to read keystore: {}", e))?;
+    let keystore = KeyStore::from_pkcs12(&keystore_bytes, &config.password)
+        .map_err(|e| format!("Failed to open keystore: {}", e))?;
+
+    let (alias, key_chain) = keystore
+        .private_key_chain()
+        .ok_or("Keystore does not contain a private key".to_string())?;
+    info!("Using keystore entry: {}", alias);
+
+    let private_key = RsaPrivateKey::from_pkcs8_der(key_chain.key())
+        .map_err(|e| format!("Unsupported private key (RSA required): {}", e))?;
+
+    let chain = key_chain
+        .chain()
+        .iter()
+        .map(|cert| Certificate::from_der(cert.as_der()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid certificate in keystore: {}", e))?;
+
+    Ok((SigningKey::<Sha256>::new(private_key), chain))
+}
+
+/// Add an invisible signature field on the first page pointing at the
+/// signature dictionary, updating the page and AcroForm in the new revision
+fn add_signature_field(
+    incremental: &mut IncrementalDocument,
+    signature_dict: lopdf::Dictionary,
+) -> Result<(), String> {
+    let prev = incremental.get_prev_documents();
+    let catalog_id = prev
+        .trailer
+        .get(b"Root")
+        .and_then(Object::as_reference)
+        .map_err(|_| "PDF has no catalog".to_string())?;
+    let page_id = *prev
+        .get_pages()
+        .get(&1)
+        .ok_or("PDF has no pages".to_string())?;
+    let acroform_id = prev
+        .catalog()
+        .and_then(|catalog| catalog.get(b"AcroForm"))
+        .and_then(Object::as_reference)
+        .ok();
+    let annots_id = prev
+        .get_dictionary(page_id)
+        .and_then(|page| page.get(b"Annots"))
+        .and_then(Object::as_reference)
+        .ok();
+    let fields_id = prev
+        .catalog()
+        .and_then(|catalog| catalog.get(b"AcroForm"))
+        .and_then(|acroform| prev.dereference(acroform))
+        .and_then(|(_, acroform)| acroform.as_dict())
+        .and_then(|acroform| acroform.get(b"Fields"))
+        .and_then(Object::as_reference)
+        .ok();
+
+    let signature_id = incremental.new_document.add_object(signature_dict);
+    let field_id = incremental.new_document.add_object(dictionary! {
+        b"Type" => Object::Name(b"Annot".to_vec()),
+        b"Subtype" => Object::Name(b"Widget".to_vec()),
+        b"FT" => Object::Name(b"Sig".to_vec()),
+        b"T" => Object::string_literal("Timesheet Signature"),
+        b"V" => Object::Reference(signature_id),
+        b"F" => Object::Integer(132),
+        b"Rect" => Object::Array(vec![
+            Object::Integer(0),
+            Object::Integer(0),
+            Object::Integer(0),
+            Object::Integer(0),
+        ]),
+        b"P" => Object::Reference(page_id),
+    });
+
+    // Page annotations, either inline on the page or in their own array object
+    let annots = match annots_id {
+        Some(id) => clone_object_mut(incremental, id)?,
+        None => {
+            let page = clone_object_mut(incremental, page_id)?
+                .as_dict_mut()
+                .unwrap();
+            if !page.has(b"Annots") {
+                page.set("Annots", Object::Array(vec![]));
+            }
+            page.get_mut(b"Annots").unwrap()
+        }
+    };
+    annots
+        .as_array_mut()
+        .map_err(|_| "Page Annots is not an array".to_string())?
+        .push(Object::Reference(field_id));
+
+    if let Some(id) = fields_id {
+        clone_object_mut(incremental, id)?
+            .as_array_mut()
+            .map_err(|_| "AcroForm Fields is not an array".to_string())?
+            .push(Object::Reference(field_id));
+    }
+
+    // AcroForm, either referenced from the catalog or inline in it
+    let acroform = match acroform_id {
+        Some(id) => clone_object_mut(incremental, id)?,
+        None => {
+            let catalog = clone_object_mut(incremental, catalog_id)?
+                .as_dict_mut()
+                .unwrap();
+            if !catalog.has(b"AcroForm") {
+                catalog.set(
+                    "AcroForm",
+                    dictionary! { b"Fields" => Object::Array(vec![]) },
+                );
+            }
+            catalog.get_mut(b"AcroForm").unwrap()
+        }
+    }
+    .as_dict_mut()
+    .map_err(|_| "AcroForm is not a dictionary".to_string())?;
+
+    // SignaturesExist | AppendOnly
+    acroform.set("SigFlags", Object::Integer(3));
+    if fields_id.is_none() {
+        if !acroform.has(b"Fields") {
+            acroform.set("Fields", Object::Array(vec![]));
+        }
+        acroform
+            .get_mut(b"Fields")
+            .and_then(Object::as_array_mut)
+            .map_err(|_| "AcroForm Fields is not an array".to_string())?
+            .push(Object::Reference(field_id));
+    }
+
+    Ok(())
+}
+
+fn clone_object_mut(
+    incremental: &mut IncrementalDocument,
+    id: ObjectId,
+) -> Result<&mut Object, String> {
+    incremental
+        .opt_clone_object_to_new_document(id)
+        .and_then(|_| incremental.new_document.get_object_mut(id))
+        .map_err(|e| format!("Failed to update object {:?}: {}", id, e))
+}
+
+/// Locate the `<000…>` placeholder written for `/Contents`, returning the
+/// offsets of the opening `<` and just past the closing `>`
+fn find_contents_placeholder(output: &[u8]) -> Result<(usize, usize), String> {
+    let mut placeholder = vec![b'0'; SIGNATURE_SIZE * 2 + 2];
+    placeholder[0] = b'<';
+    placeholder[SIGNATURE_SIZE * 2 + 1] = b'>';
+
+    output
+        .windows(placeholder.len())
+        .rposition(|window| window == placeholder.as_slice())
+        .map(|start| (start, start + placeholder.len()))
+        .ok_or("Signature placeholder not found in output".to_string())
+}
+
+/// Overwrite the placeholder ByteRange in place, padding with spaces so no
+/// offsets in the file move
+fn write_byte_range(output: &mut [u8], byte_range: &[usize; 4]) -> Result<(), String> {
+    let placeholder = format!(
+        "{} {} {}",
+        BYTE_RANGE_PLACEHOLDER, BYTE_RANGE_PLACEHOLDER, BYTE_RANGE_PLACEHOLDER
+    );
+    let start = output
+        .windows(placeholder.len())
+        .rposition(|window| window == placeholder.as_bytes())
+        .ok_or("ByteRange placeholder not found in output".to_string())?;
+
+    let value = format!("{} {} {}", byte_range[1], byte_range[2], byte_range[3]);
+    let padded = format!("{:<width$}", value, width = placeholder.len());
+    output[start..start + placeholder.len()].copy_from_slice(padded.as_bytes());
+
+    Ok(())
+}
+
+/// Decode a `<...>` hex string as written in the file, or `None` if the
+/// bytes are anything else
+fn decode_hex_string(bytes: &[u8]) -> Option<Vec<u8>> {
+    let hex = bytes.strip_prefix(b"<")?.strip_suffix(b">")?;
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    hex.chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+fn byte_range(signature_dict: &lopdf::Dictionary) -> Result<[usize; 4], String> {
+    let values = signature_dict
+        .get(b"ByteRange")
+        .and_then(Object::as_array)
+        .map_err(|_| "Signature has no ByteRange".to_string())?;
+    if values.len() != 4 {
+        return Err("Signature ByteRange must have four entries".to_string());
+    }
+
+    let mut range = [0; 4];
+    for (slot, value) in range.iter_mut().zip(values) {
+        *slot = value
+            .as_i64()
+            .ok()
+            .and_then(|value| usize::try_from(value).ok())
+            .ok_or("Signature ByteRange is not numeric".to_string())?;
+    }
+    Ok(range)
+}
+
+fn build_cms_signature(
+    signing_key: &SigningKey<Sha256>,
+    chain: &[Certificate],
+    digest: &[u8],
+) -> Result<Vec<u8>, String> {
+    let certificate = &chain[0];
+    let content = EncapsulatedContentInfo {
+        econtent_type: rfc5911::ID_DATA,
+        econtent: None,
+    };
+    let digest_algorithm = AlgorithmIdentifierOwned {
+        oid: rfc5912::ID_SHA_256,
+        parameters: None,
+    };
+
+    let mut signer_info = SignerInfoBuilder::new(
+        signing_key,
+        SignerIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
+            issuer: certificate.tbs_certificate.issuer.clone(),
+            serial_number: certificate.tbs_certificate.serial_number.clone(),
+        }),
+        digest_algorithm.clone(),
+        &content,
+        Some(digest),
+    )
+    .map_err(|e| e.to_string())?;
+    signer_info
+        .add_signed_attribute(signing_certificate_attribute(certificate)?)
+        .map_err(|e| e.to_string())?;
+
+    let mut builder = SignedDataBuilder::new(&content);
+    builder
+        .add_digest_algorithm(digest_algorithm)
+        .map_err(|e| e.to_string())?;
+    for cert in chain {
+        builder
+            .add_certificate(CertificateChoices::Certificate(cert.clone()))
+            .map_err(|e| e.to_string())?;
+    }
+
+    builder
+        .add_signer_info::<_, Signature>(signer_info)
+        .and_then(|builder| builder.build())
+        .map_err(|e| format!("Failed to build CMS signature: {}", e))?
+        .to_der()
+        .map_err(|e| format!("Failed to encode CMS signature: {}", e))
+}
+
+/// ESS signing-certificate-v2 attribute binding the certificate to the
+/// signature, required for PAdES baseline signatures
+fn signing_certificate_attribute(certificate: &Certificate) -> Result<Attribute, String> {
+    let cert_hash = Sha256::digest(certificate.to_der().map_err(|e| e.to_string())?);
+
+    // SigningCertificateV2 ::= SEQUENCE { certs SEQUENCE OF ESSCertIDv2 }
+    // ESSCertIDv2 ::= SEQUENCE { certHash OCTET STRING } (hashAlgorithm defaults to SHA-256)
+    let mut value = vec![0x30, 0x26, 0x30, 0x24, 0x30, 0x22, 0x04, 0x20];
+    value.extend_from_slice(&cert_hash);
+
+    let value = AttributeValue::from_der(&value).map_err(|e| e.to_string())?;
+    Ok(Attribute {
+        oid: rfc5911::ID_AA_SIGNING_CERTIFICATE_V_2,
+        values: vec![value]
+            .try_into()
+            .map_err(|e: x509_cert::der::Error| e.to_string())?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::pdf::testing::TestForm;
+
+    fn config() -> PadesConfig {
+        PadesConfig {
+            keystore_path: concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/src/helpers/pdf/testdata/signer.p12"
+            )
+            .to_string(),
+            password: "test".to_string(),
+            reason: Some("Timesheet submission".to_string()),
+            location: None,
+        }
+    }
+
+    /// A one-page PDF with some text on it
+    fn unsigned_pdf() -> Vec<u8> {
+        let mut form = TestForm::new();
+        form.text("Total hours: 7.5");
+        form.bytes()
+    }
+
+    #[test]
+    fn verifies_a_signed_pdf() {
+        let signed = sign_pdf(unsigned_pdf(), &config()).unwrap();
+        let info = verify_signature(&signed).unwrap();
+
+        assert_eq!(info.signer, "CN=Test Signer");
+        assert!(info.signed_at.is_some());
+        assert!(info.covers_whole_document);
+    }
+
+    #[test]
+    fn rejects_a_changed_byte() {
+        let signed = sign_pdf(unsigned_pdf(), &config()).unwrap();
+        let position = signed
+            .windows(4)
+            .position(|window| window == b"7.5)")
+            .unwrap();
+        let mut changed = signed.clone();
+        changed[position] = b'9';
+
+        assert_eq!(
+            verify_signature(&changed).unwrap_err(),
+            "Document has been modified since it was signed"
+        );
+    }
+
+    #[test]
+    fn rejects_a_byte_range_that_skips_more_than_contents() {
+        let signed = sign_pdf(unsigned_pdf(), &config()).unwrap();
+        let doc = Document::load_mem(&signed).unwrap();
+        let range = doc
+            .objects
+            .values()
+            .filter_map(|object| object.as_dict().ok())
+            .find_map(|dict| byte_range(dict).ok())
+            .unwrap();
+
+        // Widen the gap by a byte on each side, keeping the same width
+        let old = format!("{} {} {}", range[1], range[2], range[3]);
+        let new = format!("{} {} {}", range[1] - 1, range[2] + 1, range[3] - 1);
+        let new = format!("{:<width$}", new, width = old.len());
+        let position = signed
+            .windows(old.len())
+            .rposition(|window| window == old.as_bytes())
+            .unwrap();
+        let mut widened = signed.clone();
+        widened[position..position + old.len()].copy_from_slice(new.as_bytes());
+
+        assert_eq!(
+            verify_signature(&widened).unwrap_err(),
+            "Signature ByteRange does not exclude exactly its Contents"
+        );
+    }
+}
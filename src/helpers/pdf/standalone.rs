@@ -0,0 +1,524 @@
+use chrono::{Local, NaiveDate, Weekday};
+use lopdf::{dictionary, Document, Object, ObjectId, Stream};
+use tracing::info;
+
+use super::{signature_xobject, PdfConfig, TimesheetData};
+
+const PAGE_WIDTH: f32 = 612.0;
+const PAGE_HEIGHT: f32 = 792.0;
+const MARGIN: f32 = 50.0;
+const ROW_HEIGHT: f32 = 18.0;
+/// Extra height for each wrapped line of notes under an entry
+const NOTE_LINE_HEIGHT: f32 = 10.0;
+/// Notes wrap at this many characters, about the table width at 8pt
+const NOTE_LINE_CHARS: usize = 100;
+/// Space kept free at the bottom of every page for the footer
+const FOOTER_HEIGHT: f32 = 40.0;
+/// Space needed after the last row for the total and signature lines
+const CLOSING_HEIGHT: f32 = 110.0;
+
+/// Left edge of each table column: date, start, finish, hours
+const COLUMNS: [f32; 4] = [MARGIN, 230.0, 340.0, 450.0];
+
+enum TableRow {
+    Entry {
+        date: String,
+        start: String,
+        end: String,
+        hours: f64,
+        /// Notes, already wrapped to the table width
+        notes: Vec<String>,
+    },
+    Subtotal {
+        label: String,
+        hours: f64,
+    },
+}
+
+impl TableRow {
+    fn height(&self) -> f32 {
+        match self {
+            TableRow::Entry { notes, .. } => ROW_HEIGHT + notes.len() as f32 * NOTE_LINE_HEIGHT,
+            TableRow::Subtotal { .. } => ROW_HEIGHT,
+        }
+    }
+}
+
+/// Draw a timesheet from scratch, without a form template: a header block,
+/// a table of shifts with weekly subtotals, the total and a signature line
+pub(super) fn build_standalone_timesheet(
+    data: &TimesheetData,
+    config: &PdfConfig,
+) -> Result<Document, String> {
+    info!(
+        "Rendering standalone timesheet with {} entries",
+        data.entries.len()
+    );
+
+    let rows = table_rows(data);
+
+    // Lay rows out onto pages before drawing so every footer knows the page count
+    let first_table_top = PAGE_HEIGHT - MARGIN - 110.0;
+    let continued_table_top = PAGE_HEIGHT - MARGIN - 30.0;
+    let bottom = MARGIN + FOOTER_HEIGHT;
+
+    let mut pages: Vec<Vec<&TableRow>> = vec![Vec::new()];
+    let mut y = first_table_top - ROW_HEIGHT;
+    for row in &rows {
+        if y - row.height() < bottom {
+            pages.push(Vec::new());
+            y = continued_table_top - ROW_HEIGHT;
+        }
+        pages.last_mut().unwrap().push(row);
+        y -= row.height();
+    }
+    let closing_on_new_page = y - CLOSING_HEIGHT < bottom;
+    if closing_on_new_page {
+        pages.push(Vec::new());
+    }
+
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let regular_font = doc.add_object(dictionary! {
+        b"Type" => Object::Name(b"Font".to_vec()),
+        b"Subtype" => Object::Name(b"Type1".to_vec()),
+        b"BaseFont" => Object::Name(b"Helvetica".to_vec()),
+        b"Encoding" => Object::Name(b"WinAnsiEncoding".to_vec()),
+    });
+    let bold_font = doc.add_object(dictionary! {
+        b"Type" => Object::Name(b"Font".to_vec()),
+        b"Subtype" => Object::Name(b"Type1".to_vec()),
+        b"BaseFont" => Object::Name(b"Helvetica-Bold".to_vec()),
+        b"Encoding" => Object::Name(b"WinAnsiEncoding".to_vec()),
+    });
+
+    let signature_image = match &config.signature {
+        Some(signature) => {
+            let bytes = std::fs::read(&signature.image_path)
+                .map_err(|e| format!("Failed to read signature image: {}", e))?;
+            let (stream, width, height) = signature_xobject(&mut doc, bytes)?;
+            Some((doc.add_object(stream), width, height))
+        }
+        None => None,
+    };
+
+    let page_count = pages.len();
+    let mut page_ids = Vec::new();
+
+    for (index, page_rows) in pages.iter().enumerate() {
+        let mut content = String::new();
+
+        let mut y = if index == 0 {
+            draw_header(&mut content, data);
+            first_table_top
+        } else {
+            text(
+                &mut content,
+                "F2",
+                11.0,
+                MARGIN,
+                PAGE_HEIGHT - MARGIN,
+                "Timesheet (continued)",
+            );
+            continued_table_top
+        };
+
+        if !page_rows.is_empty() || index + 1 < page_count || !closing_on_new_page {
+            draw_column_headings(&mut content, y);
+            y -= ROW_HEIGHT;
+        }
+
+        for row in page_rows {
+            draw_row(&mut content, row, y);
+            y -= row.height();
+        }
+
+        if index + 1 == page_count {
+            draw_closing(&mut content, data, config, signature_image, y);
+        }
+
+        text(
+            &mut content,
+            "F1",
+            8.0,
+            PAGE_WIDTH - MARGIN - 50.0,
+            MARGIN,
+            &format!("Page {} of {}", index + 1, page_count),
+        );
+
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.into_bytes()));
+        let mut xobjects = dictionary! {};
+        if let Some((image_id, _, _)) = signature_image {
+            xobjects.set("SigImg", Object::Reference(image_id));
+        }
+        let page_id = doc.add_object(dictionary! {
+            b"Type" => Object::Name(b"Page".to_vec()),
+            b"Parent" => Object::Reference(pages_id),
+            b"MediaBox" => Object::Array(vec![
+                Object::Integer(0),
+                Object::Integer(0),
+                Object::Real(PAGE_WIDTH),
+                Object::Real(PAGE_HEIGHT),
+            ]),
+            b"Contents" => Object::Reference(content_id),
+            b"Resources" => dictionary! {
+                b"Font" => dictionary! {
+                    b"F1" => Object::Reference(regular_font),
+                    b"F2" => Object::Reference(bold_font),
+                },
+                b"XObject" => xobjects,
+            },
+        });
+        page_ids.push(page_id);
+    }
+
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            b"Type" => Object::Name(b"Pages".to_vec()),
+            b"Kids" => Object::Array(page_ids.iter().copied().map(Object::Reference).collect()),
+            b"Count" => Object::Integer(page_ids.len() as i64),
+        }),
+    );
+    let catalog_id: ObjectId = doc.add_object(dictionary! {
+        b"Type" => Object::Name(b"Catalog".to_vec()),
+        b"Pages" => Object::Reference(pages_id),
+    });
+    doc.trailer.set("Root", Object::Reference(catalog_id));
+
+    info!("Standalone timesheet laid out on {} page(s)", page_count);
+
+    Ok(doc)
+}
+
+/// Entry rows in order, with a subtotal after each calendar week when the
+/// pay period (and so the year) is known
+fn table_rows(data: &TimesheetData) -> Vec<TableRow> {
+    let weekly_hours = data.weekly_hours();
+    let subtotal = |week_start: NaiveDate| {
+        let hours = weekly_hours
+            .iter()
+            .find(|(start, _)| *start == week_start)
+            .map_or(0.0, |(_, hours)| *hours);
+        week_subtotal(week_start, hours)
+    };
+
+    // Entries posted to the render endpoint can come in any order
+    let mut entries = data
+        .entries
+        .iter()
+        .map(|entry| (data.entry_date(entry), entry))
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|(date, entry)| (*date, entry.month, entry.day));
+
+    let mut rows = Vec::new();
+    let mut current_week = None;
+
+    for (date, entry) in entries {
+        if let Some(date) = date {
+            let week_start = date.week(Weekday::Mon).first_day();
+            if let Some(start) = current_week.filter(|start| *start != week_start) {
+                rows.push(subtotal(start));
+            }
+            current_week = Some(week_start);
+        }
+
+        rows.push(TableRow::Entry {
+            date: match date {
+                Some(date) => date.format("%a %b %-d").to_string(),
+                None => format!("{}/{}", entry.month, entry.day),
+            },
+            start: entry.start.clone(),
+            end: entry.end.clone(),
+            hours: entry.paid_hours,
+            notes: entry
+                .notes
+                .as_deref()
+                .map(|notes| wrap_text(notes, NOTE_LINE_CHARS))
+                .unwrap_or_default(),
+        });
+    }
+
+    if let Some(start) = current_week {
+        rows.push(subtotal(start));
+    }
+
+    rows
+}
+
+fn week_subtotal(week_start: NaiveDate, hours: f64) -> TableRow {
+    TableRow::Subtotal {
+        label: format!("Week of {}", week_start.format("%b %-d")),
+        hours,
+    }
+}
+
+fn draw_header(content: &mut String, data: &TimesheetData) {
+    let top = PAGE_HEIGHT - MARGIN;
+    text(content, "F2", 18.0, MARGIN, top - 10.0, "Timesheet");
+
+    let period = match data.period {
+        Some((start, end)) => format!(
+            "{} to {}",
+            start.format("%B %-d, %Y"),
+            end.format("%B %-d, %Y")
+        ),
+        None => "-".to_string(),
+    };
+    let details = [
+        ("Employee:", data.employee.clone().unwrap_or_default()),
+        ("Pay period:", period),
+        (
+            "Prepared:",
+            Local::now().date_naive().format("%B %-d, %Y").to_string(),
+        ),
+    ];
+
+    for (index, (label, value)) in details.iter().enumerate() {
+        let y = top - 40.0 - index as f32 * 16.0;
+        text(content, "F2", 10.0, MARGIN, y, label);
+        text(content, "F1", 10.0, MARGIN + 70.0, y, value);
+    }
+}
+
+fn draw_column_headings(content: &mut String, y: f32) {
+    let headings = ["Date", "Start", "Finish", "Hours"];
+    for (x, heading) in COLUMNS.iter().zip(headings) {
+        text(content, "F2", 10.0, *x, y - 13.0, heading);
+    }
+    line(
+        content,
+        MARGIN,
+        y - ROW_HEIGHT + 1.0,
+        PAGE_WIDTH - MARGIN,
+        1.0,
+    );
+}
+
+fn draw_row(content: &mut String, row: &TableRow, y: f32) {
+    let baseline = y - 13.0;
+    match row {
+        TableRow::Entry {
+            date,
+            start,
+            end,
+            hours,
+            notes,
+        } => {
+            text(content, "F1", 10.0, COLUMNS[0], baseline, date);
+            text(content, "F1", 10.0, COLUMNS[1], baseline, start);
+            text(content, "F1", 10.0, COLUMNS[2], baseline, end);
+            text(
+                content,
+                "F1",
+                10.0,
+                COLUMNS[3],
+                baseline,
+                &hours.to_string(),
+            );
+            for (index, note) in notes.iter().enumerate() {
+                let note_y = baseline - 11.0 - index as f32 * NOTE_LINE_HEIGHT;
+                text(content, "F1", 8.0, COLUMNS[0] + 10.0, note_y, note);
+            }
+            line(
+                content,
+                MARGIN,
+                y - row.height() + 1.0,
+                PAGE_WIDTH - MARGIN,
+                0.25,
+            );
+        }
+        TableRow::Subtotal { label, hours } => {
+            text(content, "F2", 9.0, COLUMNS[2], baseline, label);
+            text(
+                content,
+                "F2",
+                10.0,
+                COLUMNS[3],
+                baseline,
+                &hours.to_string(),
+            );
+            line(
+                content,
+                MARGIN,
+                y - ROW_HEIGHT + 1.0,
+                PAGE_WIDTH - MARGIN,
+                0.5,
+            );
+        }
+    }
+}
+
+fn draw_closing(
+    content: &mut String,
+    data: &TimesheetData,
+    config: &PdfConfig,
+    signature_image: Option<(ObjectId, f32, f32)>,
+    y: f32,
+) {
+    let total_y = y - 20.0;
+    text(content, "F2", 11.0, COLUMNS[2], total_y, "Total hours");
+    text(
+        content,
+        "F2",
+        11.0,
+        COLUMNS[3],
+        total_y,
+        &data.total_hours.to_string(),
+    );
+
+    let signature_y = total_y - 70.0;
+    let signature_end = MARGIN + 250.0;
+    line(content, MARGIN, signature_y, signature_end, 0.75);
+    text(
+        content,
+        "F1",
+        9.0,
+        MARGIN,
+        signature_y - 12.0,
+        "Employee signature",
+    );
+    line(content, COLUMNS[2], signature_y, PAGE_WIDTH - MARGIN, 0.75);
+    text(content, "F1", 9.0, COLUMNS[2], signature_y - 12.0, "Date");
+
+    if let (Some(signature), Some((_, width, height))) = (&config.signature, signature_image) {
+        // Fit the image in the box above the signature line
+        let box_width = signature_end - MARGIN;
+        let box_height = 45.0;
+        let scale = (box_width / width).min(box_height / height);
+        content.push_str(&format!(
+            "q\n{:.2} 0 0 {:.2} {:.2} {:.2} cm\n/SigImg Do\nQ\n",
+            width * scale,
+            height * scale,
+            MARGIN,
+            signature_y + 2.0
+        ));
+
+        let date = Local::now()
+            .date_naive()
+            .format(&signature.date_format)
+            .to_string();
+        text(content, "F1", 10.0, COLUMNS[2], signature_y + 4.0, &date);
+    }
+}
+
+pub(super) fn text(content: &mut String, font: &str, size: f32, x: f32, y: f32, value: &str) {
+    content.push_str(&format!(
+        "BT\n/{} {} Tf\n0 g\n{:.2} {:.2} Td\n({}) Tj\nET\n",
+        font,
+        size,
+        x,
+        y,
+        escape_text(value)
+    ));
+}
+
+fn line(content: &mut String, x1: f32, y: f32, x2: f32, width: f32) {
+    content.push_str(&format!(
+        "{} w\n{:.2} {:.2} m\n{:.2} {:.2} l\nS\n",
+        width, x1, y, x2, y
+    ));
+}
+
+/// Escape a string for a WinAnsi literal, replacing characters outside Latin-1
+fn escape_text(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            ' '..='~' => escaped.push(c),
+            '\u{a0}'..='\u{ff}' => escaped.push_str(&format!("\\{:03o}", c as u32)),
+            _ => escaped.push('?'),
+        }
+    }
+    escaped
+}
+
+/// Split text into lines of at most `width` characters, breaking at spaces
+/// where possible
+pub(super) fn wrap_text(value: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in value.lines() {
+        let mut current = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word = word.to_string();
+            while word.chars().count() > width {
+                if !current.is_empty() {
+                    lines.push(std::mem::take(&mut current));
+                }
+                let split = word.char_indices().nth(width).unwrap().0;
+                lines.push(word[..split].to_string());
+                word = word[split..].to_string();
+            }
+            if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(&word);
+        }
+        if !current.is_empty() {
+            lines.push(current);
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::pdf::TimesheetEntry;
+
+    fn entry(day: u32, paid_hours: f64) -> TimesheetEntry {
+        TimesheetEntry {
+            month: 10,
+            day,
+            start: "09:00".to_string(),
+            end: "12:00".to_string(),
+            paid_hours,
+            notes: None,
+        }
+    }
+
+    fn labels(rows: &[TableRow]) -> Vec<String> {
+        rows.iter()
+            .map(|row| match row {
+                TableRow::Entry { date, hours, .. } => format!("{} {}", date, hours),
+                TableRow::Subtotal { label, hours } => format!("{} {}", label, hours),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sorts_entries_and_totals_each_week() {
+        let start = NaiveDate::from_ymd_opt(2026, 10, 9).unwrap();
+        let end = NaiveDate::from_ymd_opt(2026, 10, 23).unwrap();
+        let data = TimesheetData {
+            entries: vec![
+                entry(13, 2.0),
+                entry(9, 3.0),
+                entry(12, 4.0),
+                entry(10, 1.5),
+            ],
+            total_hours: 10.5,
+            employee: None,
+            period: Some((start, end)),
+        };
+
+        assert_eq!(
+            labels(&table_rows(&data)),
+            vec![
+                "Fri Oct 9 3",
+                "Sat Oct 10 1.5",
+                "Week of Oct 5 4.5",
+                "Mon Oct 12 4",
+                "Tue Oct 13 2",
+                "Week of Oct 12 6",
+            ]
+        );
+    }
+}
@@ -0,0 +1,443 @@
+use lopdf::{Document, Object, ObjectId};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use tracing::{error, info};
+
+use super::{field_kids, field_rect, fields, form_fields, load_pdf, TemplateFingerprint};
+
+/// Which template fields hold each timesheet value.
+///
+/// Fields are matched by name prefix while walking the form in order, one row
+/// at a time, so a mapping only needs the column names and not every row.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TemplateMapping {
+    pub template_path: String,
+    /// Prefix of the month field; the day shares it with `day_suffix` appended
+    pub date: String,
+    pub day_suffix: String,
+    pub start: String,
+    pub finish: String,
+    pub hours: String,
+    pub total: String,
+    /// Prefix of the per-row notes field, if the template has one
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// Field set of the template when this mapping was made. Generation and
+    /// import refuse to run if the template no longer matches it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<TemplateFingerprint>,
+}
+
+impl Default for TemplateMapping {
+    /// The official UFV SASI timesheet
+    fn default() -> Self {
+        Self {
+            template_path: "templates/sasi.pdf".to_string(),
+            date: "Month Day".to_string(),
+            day_suffix: "_2".to_string(),
+            start: "Start Time".to_string(),
+            finish: "Finish Time".to_string(),
+            hours: "Hours to be Paid".to_string(),
+            total: "Total hours".to_string(),
+            notes: None,
+            fingerprint: None,
+        }
+    }
+}
+
+impl TemplateMapping {
+    /// Load a mapping from a JSON file, e.g. one generated by [`inspect_template`]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path.as_ref()).map_err(|e| {
+            error!("Failed to read template mapping: {}", e);
+            format!("Failed to read template mapping: {}", e)
+        })?;
+
+        let mapping: Self = serde_json::from_str(&text).map_err(|e| {
+            error!("Failed to parse template mapping: {}", e);
+            format!("Failed to parse template mapping: {}", e)
+        })?;
+        mapping.validate()?;
+        Ok(mapping)
+    }
+
+    /// Fail if a column prefix is empty. An empty prefix matches every field,
+    /// so the form would be filled with the wrong values.
+    pub fn validate(&self) -> Result<(), String> {
+        let missing = self.missing_columns();
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        error!("Template mapping has empty columns: {:?}", missing);
+        Err(format!(
+            "Template mapping for {} has no field prefix for: {}",
+            self.template_path,
+            missing.join(", ")
+        ))
+    }
+
+    fn missing_columns(&self) -> Vec<&'static str> {
+        [
+            ("date", &self.date),
+            ("day_suffix", &self.day_suffix),
+            ("start", &self.start),
+            ("finish", &self.finish),
+            ("hours", &self.hours),
+            ("total", &self.total),
+        ]
+        .into_iter()
+        .filter(|(_, prefix)| prefix.trim().is_empty())
+        .map(|(column, _)| column)
+        .collect()
+    }
+
+    /// Record the current field set of `template_path`, e.g. after checking
+    /// that a revised form still lines up with this mapping
+    pub fn with_fingerprint(mut self) -> Result<Self, String> {
+        let doc = load_pdf(&self.template_path).map_err(|e| {
+            error!("Failed to load PDF: {}", e);
+            format!("Failed to load PDF: {:?}", e)
+        })?;
+        self.fingerprint = Some(TemplateFingerprint::of(&doc));
+        Ok(self)
+    }
+}
+
+/// A single AcroForm field as found in a template
+#[derive(Debug, Serialize)]
+pub struct FormFieldInfo {
+    /// Fully qualified name, with parent names joined by `.`
+    pub name: String,
+    /// `Tx`, `Btn`, `Ch` or `Sig`
+    pub field_type: Option<String>,
+    /// 1-based page number of the field's first widget
+    pub page: Option<u32>,
+    pub rect: Option<[f32; 4]>,
+    /// Field flags (`/Ff`)
+    pub flags: i64,
+    /// Annotation flags (`/F`)
+    pub annotation_flags: i64,
+    pub value: Option<String>,
+}
+
+/// Fields that look like the same column repeated once per row
+#[derive(Debug, Serialize)]
+pub struct RowGroup {
+    /// Column name with the row number removed
+    pub column: String,
+    /// Field names in row order
+    pub fields: Vec<String>,
+}
+
+/// Everything known about a template, plus a starting point for its mapping
+#[derive(Debug, Serialize)]
+pub struct TemplateReport {
+    pub fields: Vec<FormFieldInfo>,
+    pub row_groups: Vec<RowGroup>,
+    /// Best guess at a mapping, or `None` if some columns could not be inferred
+    pub mapping: Option<TemplateMapping>,
+    /// Mapping columns no field could be found for
+    pub missing_columns: Vec<String>,
+}
+
+/// List every form field in a template and infer a mapping skeleton from
+/// the field names
+pub fn inspect_template<P: AsRef<Path>>(path: P) -> Result<TemplateReport, String> {
+    let template_path = path.as_ref().to_string_lossy().into_owned();
+    info!("Inspecting template: {}", template_path);
+
+    let doc = load_pdf(&path).map_err(|e| {
+        error!("Failed to load PDF: {}", e);
+        format!("Failed to load PDF: {:?}", e)
+    })?;
+
+    let fields = list_form_fields(&doc);
+    let row_groups = infer_row_groups(&fields);
+    let (mapping, missing_columns) = match infer_mapping(template_path, &fields, &row_groups) {
+        Ok(mut mapping) => {
+            mapping.fingerprint = Some(TemplateFingerprint::of(&doc));
+            (Some(mapping), Vec::new())
+        }
+        Err(missing) => (None, missing.into_iter().map(str::to_string).collect()),
+    };
+
+    info!(
+        "Template has {} fields in {} row groups",
+        fields.len(),
+        row_groups.len()
+    );
+
+    Ok(TemplateReport {
+        fields,
+        row_groups,
+        mapping,
+        missing_columns,
+    })
+}
+
+/// Walk the AcroForm field tree, returning terminal fields in form order.
+/// Names are the same qualified names the mapping prefixes are matched on.
+pub fn list_form_fields(doc: &Document) -> Vec<FormFieldInfo> {
+    let mut widget_pages = HashMap::new();
+    for (page_number, page_id) in doc.get_pages() {
+        let annots = doc
+            .get_dictionary(page_id)
+            .and_then(|page| page.get(b"Annots"))
+            .and_then(|annots| doc.dereference(annots))
+            .and_then(|(_, annots)| annots.as_array());
+
+        if let Ok(annots) = annots {
+            for annot in annots.iter().filter_map(|annot| annot.as_reference().ok()) {
+                widget_pages.insert(annot, page_number);
+            }
+        }
+    }
+
+    form_fields(doc)
+        .into_iter()
+        .filter_map(|(field_ref, name)| field_info(doc, field_ref, name, &widget_pages))
+        .collect()
+}
+
+fn field_info(
+    doc: &Document,
+    field_ref: ObjectId,
+    name: String,
+    widget_pages: &HashMap<ObjectId, u32>,
+) -> Option<FormFieldInfo> {
+    let field_dict = doc.get_dictionary(field_ref).ok()?;
+    let field_type = fields::inherited(doc, field_dict, b"FT")
+        .and_then(|field_type| field_type.as_name().ok())
+        .map(|name| String::from_utf8_lossy(name).into_owned());
+
+    // The first widget decides where the field sits on the page
+    let widget_ref = field_kids(doc, field_dict)
+        .first()
+        .copied()
+        .unwrap_or(field_ref);
+    let widget_dict = doc.get_dictionary(widget_ref).unwrap_or(field_dict);
+    let page = widget_pages.get(&widget_ref).copied().or_else(|| {
+        let page_id = widget_dict.get(b"P").and_then(Object::as_reference).ok()?;
+        doc.get_pages()
+            .into_iter()
+            .find(|(_, id)| *id == page_id)
+            .map(|(number, _)| number)
+    });
+
+    let value = match field_dict.get(b"V") {
+        Ok(Object::Name(name)) => Some(String::from_utf8_lossy(name).into_owned()),
+        Ok(value) => lopdf::decode_text_string(value).ok(),
+        Err(_) => None,
+    };
+
+    Some(FormFieldInfo {
+        name,
+        field_type,
+        page,
+        rect: field_rect(widget_dict),
+        flags: field_dict.get(b"Ff").and_then(Object::as_i64).unwrap_or(0),
+        annotation_flags: widget_dict.get(b"F").and_then(Object::as_i64).unwrap_or(0),
+        value,
+    })
+}
+
+/// Split a field name into its column and row number, e.g. `Start Time3`
+/// becomes (`Start Time`, 3) and `Month Day3_2` becomes (`Month Day_2`, 3).
+/// Names without a number are treated as the first row.
+fn split_row_number(name: &str) -> (String, u32) {
+    let (stem, variant) = match name.rsplit_once('_') {
+        Some((stem, variant))
+            if !variant.is_empty() && variant.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            (stem, format!("_{}", variant))
+        }
+        _ => (name, String::new()),
+    };
+
+    let column = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+    let row = stem[column.len()..].parse().unwrap_or(1);
+
+    (format!("{}{}", column.trim_end(), variant), row)
+}
+
+fn infer_row_groups(fields: &[FormFieldInfo]) -> Vec<RowGroup> {
+    let mut columns: BTreeMap<String, Vec<(u32, String)>> = BTreeMap::new();
+    let mut order = Vec::new();
+
+    for field in fields {
+        let (column, row) = split_row_number(&field.name);
+        if !columns.contains_key(&column) {
+            order.push(column.clone());
+        }
+        columns
+            .entry(column)
+            .or_default()
+            .push((row, field.name.clone()));
+    }
+
+    order
+        .into_iter()
+        .filter_map(|column| {
+            let mut rows = columns.remove(&column)?;
+            if rows.len() < 2 {
+                return None;
+            }
+            rows.sort_by_key(|(row, _)| *row);
+            Some(RowGroup {
+                column,
+                fields: rows.into_iter().map(|(_, name)| name).collect(),
+            })
+        })
+        .collect()
+}
+
+/// Guess each column from the field names, failing with the columns that
+/// could not be found
+fn infer_mapping(
+    template_path: String,
+    fields: &[FormFieldInfo],
+    row_groups: &[RowGroup],
+) -> Result<TemplateMapping, Vec<&'static str>> {
+    let find_column = |keywords: &[&str], with_suffix: bool| {
+        row_groups
+            .iter()
+            .map(|group| group.column.as_str())
+            .filter(|column| split_suffix(column).1.is_empty() != with_suffix)
+            .find(|column| {
+                let lower = column.to_lowercase();
+                keywords.iter().any(|keyword| lower.contains(keyword))
+            })
+            .map(str::to_string)
+    };
+
+    let date = find_column(&["month", "date"], false).unwrap_or_default();
+    let day_suffix = row_groups
+        .iter()
+        .map(|group| split_suffix(&group.column))
+        .find(|(stem, suffix)| !date.is_empty() && *stem == date && !suffix.is_empty())
+        .map(|(_, suffix)| suffix.to_string())
+        .unwrap_or_default();
+
+    let total = fields
+        .iter()
+        .map(|field| field.name.as_str())
+        .find(|name| name.to_lowercase().contains("total"))
+        .map(|name| split_row_number(name).0)
+        .unwrap_or_default();
+
+    let mapping = TemplateMapping {
+        template_path,
+        date,
+        day_suffix,
+        start: find_column(&["start", "time in"], false).unwrap_or_default(),
+        finish: find_column(&["finish", "end", "time out"], false).unwrap_or_default(),
+        hours: find_column(&["hour", "paid"], false)
+            .filter(|column| *column != total)
+            .unwrap_or_default(),
+        total,
+        notes: find_column(&["note", "comment", "remark"], false),
+        fingerprint: None,
+    };
+
+    let missing = mapping.missing_columns();
+    if missing.is_empty() {
+        Ok(mapping)
+    } else {
+        Err(missing)
+    }
+}
+
+/// Split a column name into its stem and any `_N` suffix
+fn split_suffix(column: &str) -> (&str, &str) {
+    match column.rfind('_') {
+        Some(index) if column[index + 1..].chars().all(|c| c.is_ascii_digit()) => {
+            column.split_at(index)
+        }
+        _ => (column, ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::pdf::{
+        create_sasi_timesheet, import_sasi_timesheet, PdfConfig, TimesheetData, TimesheetEntry,
+    };
+    use crate::helpers::pdf::testing::TestForm;
+    use lopdf::dictionary;
+
+    /// A one-page form whose row fields sit under a `Rows` parent, with the
+    /// total at the top level
+    fn nested_template() -> Document {
+        let mut form = TestForm::new();
+        let rows = form.group("Rows", dictionary! {});
+        for row in 1..=2 {
+            for (column, suffix) in [
+                ("Month Day", ""),
+                ("Month Day", "_2"),
+                ("Start Time", ""),
+                ("Finish Time", ""),
+                ("Hours to be Paid", ""),
+            ] {
+                let name = format!("{}{}{}", column, row, suffix);
+                form.kid(
+                    rows,
+                    Some(&name),
+                    dictionary! { "FT" => "Tx" },
+                    [50, 700, 150, 720],
+                );
+            }
+        }
+        form.text_field("Total hours", "", [50, 100, 150, 120]);
+        form.finish()
+    }
+
+    fn entry(day: u32, paid_hours: f64) -> TimesheetEntry {
+        TimesheetEntry {
+            month: 10,
+            day,
+            start: "09:00".to_string(),
+            end: "12:00".to_string(),
+            paid_hours,
+            notes: None,
+        }
+    }
+
+    #[test]
+    fn generated_mapping_fills_nested_fields() {
+        let path = std::env::temp_dir().join(format!("nested-{}.pdf", uuid::Uuid::new_v4()));
+        nested_template().save(&path).unwrap();
+        let report = inspect_template(&path).unwrap();
+
+        assert_eq!(report.fields[0].name, "Rows.Month Day1");
+        let mapping = report.mapping.expect("mapping is inferred");
+        assert_eq!(mapping.start, "Rows.Start Time");
+        assert_eq!(mapping.total, "Total hours");
+
+        // What the template-mapping binary writes loads back as it was
+        let mapping_path = path.with_extension("json");
+        std::fs::write(&mapping_path, serde_json::to_string_pretty(&mapping).unwrap()).unwrap();
+        let loaded = TemplateMapping::load(&mapping_path);
+        std::fs::remove_file(&mapping_path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.start, mapping.start);
+        assert_eq!(
+            loaded.fingerprint.map(|fingerprint| fingerprint.digest),
+            mapping.fingerprint.as_ref().map(|fingerprint| fingerprint.digest.clone())
+        );
+
+        let config = PdfConfig {
+            mapping: mapping.clone(),
+            ..PdfConfig::default()
+        };
+        let data = TimesheetData::try_from(vec![entry(9, 3.0), entry(10, 1.5)]).unwrap();
+        let pdf = create_sasi_timesheet(data.clone(), &config);
+        std::fs::remove_file(&path).unwrap();
+
+        let imported = import_sasi_timesheet(&pdf.unwrap(), &mapping).unwrap();
+        assert_eq!(imported.entries, data.entries);
+        assert_eq!(imported.total_hours, 4.5);
+    }
+}
@@ -0,0 +1,210 @@
+//! Small PDFs built in memory for the tests of this module
+
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId};
+use std::path::PathBuf;
+
+/// A one-page Letter document with an AcroForm, built up field by field.
+/// Every widget sits on the page and lists it as `/P`.
+pub(super) struct TestForm {
+    doc: Document,
+    pages_id: ObjectId,
+    page_id: ObjectId,
+    fields: Vec<Object>,
+    annots: Vec<Object>,
+    contents: Option<ObjectId>,
+}
+
+impl TestForm {
+    pub(super) fn new() -> Self {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let page_id = doc.new_object_id();
+        Self {
+            doc,
+            pages_id,
+            page_id,
+            fields: Vec::new(),
+            annots: Vec::new(),
+            contents: None,
+        }
+    }
+
+    /// Add a top-level field with a merged widget at `rect`
+    pub(super) fn field(&mut self, name: &str, entries: Dictionary, rect: [i64; 4]) -> ObjectId {
+        let field_ref = self.widget(Some(name), entries, rect);
+        self.fields.push(field_ref.into());
+        field_ref
+    }
+
+    /// Add a top-level text field, filled with `value` unless it is empty
+    pub(super) fn text_field(&mut self, name: &str, value: &str, rect: [i64; 4]) -> ObjectId {
+        let mut entries = dictionary! { "FT" => "Tx" };
+        if !value.is_empty() {
+            entries.set("V", Object::string_literal(value));
+        }
+        self.field(name, entries, rect)
+    }
+
+    /// Add a top-level field without a widget of its own, for [`Self::kid`]
+    /// to hang child fields or widgets off
+    pub(super) fn group(&mut self, name: &str, mut entries: Dictionary) -> ObjectId {
+        entries.set("T", Object::string_literal(name));
+        entries.set("Kids", Vec::<Object>::new());
+        let group_ref = self.doc.add_object(entries);
+        self.fields.push(group_ref.into());
+        group_ref
+    }
+
+    /// Add a kid to `parent`: a child field when it has a `name`, otherwise
+    /// another widget of the parent field
+    pub(super) fn kid(
+        &mut self,
+        parent: ObjectId,
+        name: Option<&str>,
+        mut entries: Dictionary,
+        rect: [i64; 4],
+    ) -> ObjectId {
+        entries.set("Parent", parent);
+        let kid_ref = self.widget(name, entries, rect);
+        if let Ok(Object::Array(kids)) = self
+            .doc
+            .get_dictionary_mut(parent)
+            .and_then(|parent| parent.get_mut(b"Kids"))
+        {
+            kids.push(kid_ref.into());
+        }
+        kid_ref
+    }
+
+    /// Add an empty stream, such as an appearance to point a state at
+    pub(super) fn stream(&mut self) -> ObjectId {
+        self.doc
+            .add_object(lopdf::Stream::new(dictionary! {}, Vec::new()))
+    }
+
+    /// Draw a line of Helvetica text near the top of the page
+    pub(super) fn text(&mut self, text: &str) {
+        let content = Content {
+            operations: vec![
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                Operation::new("Td", vec![72.into(), 720.into()]),
+                Operation::new("Tj", vec![Object::string_literal(text)]),
+                Operation::new("ET", vec![]),
+            ],
+        };
+        let stream = lopdf::Stream::new(dictionary! {}, content.encode().unwrap());
+        self.contents = Some(self.doc.add_object(stream));
+    }
+
+    fn widget(&mut self, name: Option<&str>, mut entries: Dictionary, rect: [i64; 4]) -> ObjectId {
+        if let Some(name) = name {
+            entries.set("T", Object::string_literal(name));
+        }
+        entries.set("Type", "Annot");
+        entries.set("Subtype", "Widget");
+        entries.set("Rect", rect.map(Object::Integer).to_vec());
+        entries.set("P", self.page_id);
+        let widget_ref = self.doc.add_object(entries);
+        self.annots.push(widget_ref.into());
+        widget_ref
+    }
+
+    pub(super) fn finish(mut self) -> Document {
+        let mut page = dictionary! {
+            "Type" => "Page",
+            "Parent" => self.pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        };
+        if !self.annots.is_empty() {
+            page.set("Annots", self.annots);
+        }
+        if let Some(contents) = self.contents {
+            let font_id = self.doc.add_object(dictionary! {
+                "Type" => "Font",
+                "Subtype" => "Type1",
+                "BaseFont" => "Helvetica",
+            });
+            page.set("Contents", contents);
+            page.set(
+                "Resources",
+                dictionary! { "Font" => dictionary! { "F1" => font_id } },
+            );
+        }
+        self.doc.objects.insert(self.page_id, Object::Dictionary(page));
+        self.doc.objects.insert(
+            self.pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![self.page_id.into()],
+                "Count" => 1,
+            }),
+        );
+
+        let mut catalog = dictionary! {
+            "Type" => "Catalog",
+            "Pages" => self.pages_id,
+        };
+        if !self.fields.is_empty() {
+            catalog.set("AcroForm", dictionary! { "Fields" => self.fields });
+        }
+        let catalog_id = self.doc.add_object(catalog);
+        self.doc.trailer.set("Root", catalog_id);
+        self.doc
+    }
+
+    pub(super) fn bytes(self) -> Vec<u8> {
+        save(&mut self.finish())
+    }
+}
+
+pub(super) fn save(doc: &mut Document) -> Vec<u8> {
+    let mut pdf = Vec::new();
+    doc.save_to(&mut pdf).unwrap();
+    pdf
+}
+
+/// An empty form in the default SASI layout with `rows` rows, one field per
+/// column of each row and the total underneath
+pub(super) fn sasi_template(rows: u32) -> TestForm {
+    let mut form = TestForm::new();
+    for row in 1..=rows {
+        let y = 700 - row as i64 * 20;
+        let columns = [
+            ("Month Day", ""),
+            ("Month Day", "_2"),
+            ("Start Time", ""),
+            ("Finish Time", ""),
+            ("Hours to be Paid", ""),
+        ];
+        for (column, (prefix, suffix)) in columns.into_iter().enumerate() {
+            let x = 50 + column as i64 * 100;
+            let name = format!("{}{}{}", prefix, row, suffix);
+            form.text_field(&name, "", [x, y, x + 90, y + 18]);
+        }
+    }
+    form.text_field("Total hours", "", [450, 100, 540, 118]);
+    form
+}
+
+/// A PDF written to the temp folder, removed when dropped
+pub(super) struct TempPdf(PathBuf);
+
+impl TempPdf {
+    pub(super) fn new(mut doc: Document) -> Self {
+        let path = std::env::temp_dir().join(format!("form-{}.pdf", uuid::Uuid::new_v4()));
+        doc.save(&path).unwrap();
+        Self(path)
+    }
+
+    pub(super) fn path(&self) -> String {
+        self.0.to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for TempPdf {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
@@ -0,0 +1,136 @@
+use chrono::{DateTime, Datelike, Days, Local, Months, NaiveDate, NaiveTime, TimeZone};
+use std::fmt;
+
+/// First and last days of the two pay periods in a month: the 9th to the
+/// 23rd, and the 24th to the 8th of the next month
+const PERIOD_WINDOW: (u32, u32) = (9, 23);
+
+/// One semi-monthly pay period, from `start` to `end` inclusive
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PayPeriod {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl PayPeriod {
+    /// The pay period a day falls in
+    pub fn containing(date: NaiveDate) -> Self {
+        let (first, last) = PERIOD_WINDOW;
+        let day =
+            |date: NaiveDate, day: u32| date.with_day(day).expect("day exists in every month");
+
+        if date.day() < first {
+            let previous_month = date - Months::new(1);
+            Self {
+                start: day(previous_month, last + 1),
+                end: day(date, first - 1),
+            }
+        } else if date.day() > last {
+            let next_month = date + Months::new(1);
+            Self {
+                start: day(date, last + 1),
+                end: day(next_month, first - 1),
+            }
+        } else {
+            Self {
+                start: day(date, first),
+                end: day(date, last),
+            }
+        }
+    }
+
+    /// The pay period today falls in, local time
+    pub fn current() -> Self {
+        Self::containing(Local::now().date_naive())
+    }
+
+    pub fn previous(&self) -> Self {
+        Self::containing(self.start - Days::new(1))
+    }
+
+    pub fn next(&self) -> Self {
+        Self::containing(self.end + Days::new(1))
+    }
+
+    /// When the period is over: midnight after its last day, local time
+    pub fn closes_at(&self) -> DateTime<Local> {
+        let midnight = (self.end + Days::new(1)).and_time(NaiveTime::MIN);
+        // Where a clock change skips midnight, read it as UTC instead
+        midnight
+            .and_local_timezone(Local)
+            .earliest()
+            .unwrap_or_else(|| Local.from_utc_datetime(&midnight))
+    }
+}
+
+impl From<PayPeriod> for (NaiveDate, NaiveDate) {
+    fn from(period: PayPeriod) -> Self {
+        (period.start, period.end)
+    }
+}
+
+impl From<(NaiveDate, NaiveDate)> for PayPeriod {
+    fn from((start, end): (NaiveDate, NaiveDate)) -> Self {
+        Self { start, end }
+    }
+}
+
+impl fmt::Display for PayPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} to {}",
+            self.start.format("%b %-d, %Y"),
+            self.end.format("%b %-d, %Y")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn period(start: NaiveDate, end: NaiveDate) -> PayPeriod {
+        PayPeriod { start, end }
+    }
+
+    #[test]
+    fn splits_months_at_the_9th_and_24th() {
+        let early = period(date(2026, 9, 24), date(2026, 10, 8));
+        let mid = period(date(2026, 10, 9), date(2026, 10, 23));
+        let late = period(date(2026, 10, 24), date(2026, 11, 8));
+
+        assert_eq!(PayPeriod::containing(date(2026, 10, 8)), early);
+        assert_eq!(PayPeriod::containing(date(2026, 10, 9)), mid);
+        assert_eq!(PayPeriod::containing(date(2026, 10, 23)), mid);
+        assert_eq!(PayPeriod::containing(date(2026, 10, 24)), late);
+    }
+
+    #[test]
+    fn spans_new_year() {
+        let new_year = period(date(2025, 12, 24), date(2026, 1, 8));
+        for day in 1..=8 {
+            assert_eq!(PayPeriod::containing(date(2026, 1, day)), new_year);
+        }
+        assert_eq!(PayPeriod::containing(date(2025, 12, 31)), new_year);
+        assert_eq!(
+            PayPeriod::containing(date(2026, 1, 9)),
+            period(date(2026, 1, 9), date(2026, 1, 23))
+        );
+    }
+
+    #[test]
+    fn steps_between_periods() {
+        let new_year = PayPeriod::containing(date(2026, 1, 1));
+        assert_eq!(
+            new_year.previous(),
+            period(date(2025, 12, 9), date(2025, 12, 23))
+        );
+        assert_eq!(new_year.next(), period(date(2026, 1, 9), date(2026, 1, 23)));
+        assert_eq!(new_year.next().previous(), new_year);
+    }
+}
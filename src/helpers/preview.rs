@@ -0,0 +1,160 @@
+use chrono::{DateTime, Local, NaiveDate, Utc};
+
+use crate::helpers::{
+    approval::{escape_html, page, with_preamble},
+    email::{RenderedTimesheet, TimesheetEmail},
+};
+
+pub const CONFIRM: &str = "confirm";
+
+/// Preview before sending. With this set, the timesheet is first emailed
+/// only to the employee with a confirm link, and the same PDF goes out to
+/// the usual recipients once they confirm.
+#[derive(Clone, Debug)]
+pub struct PreviewConfig {
+    /// Employee's address for previews, e.g. `Alex Doe <alex.doe@student.ufv.ca>`
+    pub recipient: String,
+    /// Public URL the service's router is mounted at, used to build links,
+    /// e.g. `https://example.com/timesheet`
+    pub base_url: String,
+    /// Key for signing confirm links
+    pub secret: String,
+}
+
+impl PreviewConfig {
+    pub fn new(
+        recipient: impl Into<String>,
+        base_url: impl Into<String>,
+        secret: impl Into<String>,
+    ) -> Self {
+        Self {
+            recipient: recipient.into(),
+            base_url: base_url.into(),
+            secret: secret.into(),
+        }
+    }
+
+    /// Link to the page where the employee confirms a preview
+    pub fn link(&self, token: &str) -> String {
+        format!(
+            "{}/timesheet-confirm?token={}",
+            self.base_url.trim_end_matches('/'),
+            token
+        )
+    }
+}
+
+/// A rendered timesheet waiting for the employee to confirm it
+#[derive(Clone, Debug)]
+pub struct PendingPreview {
+    pub timesheet: RenderedTimesheet,
+    /// Idempotency keys to record against the official email once confirmed
+    pub idempotency_keys: Vec<String>,
+    pub period: (NaiveDate, NaiveDate),
+    pub expires_at: DateTime<Utc>,
+}
+
+/// When a preview for a pay period stops being confirmable without a
+/// schedule: midnight after the period's last day, local time
+pub fn expires_at(period_end: NaiveDate) -> DateTime<Utc> {
+    period_end
+        .succ_opt()
+        .and_then(|day| day.and_hms_opt(0, 0, 0))
+        .and_then(|midnight| midnight.and_local_timezone(Local).earliest())
+        .map(|midnight| midnight.with_timezone(&Utc))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+/// The preview email sent to the employee
+pub fn preview_email(
+    timesheet_email: &TimesheetEmail,
+    confirm_url: &str,
+    expires_at: DateTime<Utc>,
+) -> TimesheetEmail {
+    let expires = expires_at
+        .with_timezone(&Local)
+        .format("%b %-d, %Y at %H:%M");
+    let text = format!(
+        "This is a preview. Nothing has been sent to payroll yet.\n\n\
+         Check the attached timesheet, then confirm to send it: {}\n\n\
+         This link expires {}.\n\n",
+        confirm_url, expires
+    );
+    let html = format!(
+        "<p>This is a preview. Nothing has been sent to payroll yet.</p>\n\
+         <p>Check the attached timesheet, then <a href=\"{}\">confirm to send it</a>.</p>\n\
+         <p><small>This link expires {}.</small></p>\n<hr>\n",
+        escape_html(confirm_url),
+        expires
+    );
+
+    with_preamble(
+        timesheet_email,
+        &format!("Preview: {}", timesheet_email.subject),
+        &text,
+        &html,
+    )
+}
+
+/// Page where the employee confirms a preview. The link only shows this
+/// form, so mail scanners that follow links can't send anything.
+pub fn confirm_page(token: &str, subject: &str) -> String {
+    page(
+        subject,
+        &format!(
+            "<p>The attached PDF will be sent exactly as previewed.</p>\
+             <form method=\"post\" action=\"timesheet-confirm\">\
+             <input type=\"hidden\" name=\"token\" value=\"{}\">\
+             <button type=\"submit\">Send timesheet</button></form>",
+            escape_html(token)
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::email::{EmailBody, EmailConfig};
+    use crate::helpers::pdf::{TimesheetData, TimesheetEntry};
+
+    #[test]
+    fn expires_at_midnight_after_the_last_day() {
+        let end = NaiveDate::from_ymd_opt(2026, 10, 23).unwrap();
+        let expires = expires_at(end).with_timezone(&Local);
+        assert_eq!(
+            expires.date_naive(),
+            NaiveDate::from_ymd_opt(2026, 10, 24).unwrap()
+        );
+        assert_eq!(expires.time(), chrono::NaiveTime::MIN);
+    }
+
+    #[test]
+    fn preview_email_leads_with_the_confirm_link() {
+        let config =
+            EmailConfig::new("timesheets@example.com", vec!["payroll@ufv.ca".to_string()]);
+        let data = TimesheetData::try_from(Vec::<TimesheetEntry>::new()).unwrap();
+        let mut timesheet_email = config.render(&data, "").unwrap();
+        timesheet_email.body = EmailBody {
+            html: "<html><body class=\"x\"><p>Timesheet</p></body></html>".to_string(),
+            text: "Timesheet".to_string(),
+        };
+
+        let preview =
+            PreviewConfig::new("ada@student.ufv.ca", "https://example.com/timesheet/", "s");
+        let url = preview.link("abc&def");
+        assert_eq!(url, "https://example.com/timesheet/timesheet-confirm?token=abc&def");
+
+        let expires = "2026-10-24T07:00:00Z".parse().unwrap();
+        let email = preview_email(&timesheet_email, &url, expires);
+        assert_eq!(email.subject, format!("Preview: {}", timesheet_email.subject));
+        assert!(email.body.text.starts_with("This is a preview."));
+        assert!(email.body.text.contains(&format!("confirm to send it: {}\n", url)));
+        assert!(email.body.text.ends_with("\n\nTimesheet"));
+        assert!(email.body.html.starts_with(
+            "<html><body class=\"x\">\n<p>This is a preview. Nothing has been sent to payroll"
+        ));
+        assert!(email.body.html.contains("token=abc&amp;def\">confirm to send it</a>"));
+        assert!(email.body.html.ends_with("<hr>\n<p>Timesheet</p></body></html>"));
+        assert_eq!(email.filename("pdf"), timesheet_email.filename("pdf"));
+    }
+}
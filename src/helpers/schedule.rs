@@ -0,0 +1,273 @@
+use chrono::{DateTime, Duration, Local};
+
+use crate::helpers::period::PayPeriod;
+
+/// Something the scheduler does at a set time in each pay period
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScheduleRule {
+    /// Submit the timesheet this long after the period closes, unless it
+    /// was already submitted
+    AutoSubmit { after_close: Duration },
+    /// Remind the employee this long before the deadline if nothing has
+    /// been submitted for the period
+    Reminder { before_deadline: Duration },
+    /// Warn this long before the period closes if it has no entries
+    EmptyPeriodWarning { before_close: Duration },
+}
+
+impl ScheduleRule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScheduleRule::AutoSubmit { .. } => "auto_submit",
+            ScheduleRule::Reminder { .. } => "reminder",
+            ScheduleRule::EmptyPeriodWarning { .. } => "empty_period_warning",
+        }
+    }
+}
+
+/// Rules run on their own, tied to the pay period calendar
+#[derive(Clone, Debug)]
+pub struct ScheduleConfig {
+    pub rules: Vec<ScheduleRule>,
+    /// How long after a period closes payroll needs the timesheet
+    pub deadline_after_close: Duration,
+    /// Who gets reminders and warnings. When empty, the sender does.
+    pub notify: Vec<String>,
+    /// How often to look for due rules
+    pub check_every: std::time::Duration,
+}
+
+impl ScheduleConfig {
+    /// Submit 12 hours after each period closes, with a 3-day deadline and
+    /// no reminders yet
+    pub fn new() -> Self {
+        Self {
+            rules: vec![ScheduleRule::AutoSubmit {
+                after_close: Duration::hours(12),
+            }],
+            deadline_after_close: Duration::days(3),
+            notify: Vec::new(),
+            check_every: std::time::Duration::from_secs(60),
+        }
+    }
+
+    pub fn deadline(&self, period: &PayPeriod) -> DateTime<Local> {
+        period.closes_at() + self.deadline_after_close
+    }
+
+    /// When a rule fires for a period
+    pub fn fires_at(&self, rule: &ScheduleRule, period: &PayPeriod) -> DateTime<Local> {
+        match rule {
+            ScheduleRule::AutoSubmit { after_close } => period.closes_at() + *after_close,
+            ScheduleRule::Reminder { before_deadline } => self.deadline(period) - *before_deadline,
+            ScheduleRule::EmptyPeriodWarning { before_close } => period.closes_at() - *before_close,
+        }
+    }
+
+    /// Rules that have come due by `now` and aren't yet too late to run, in
+    /// firing order. With `since`, only those that came due after it.
+    ///
+    /// Nothing here knows what already ran, so a rule stays due on every
+    /// check until its deadline; callers skip the ones they have recorded.
+    pub fn due(
+        &self,
+        since: Option<DateTime<Local>>,
+        now: DateTime<Local>,
+    ) -> Vec<(ScheduleRule, PayPeriod)> {
+        let mut due = Vec::new();
+        for period in Self::periods(now) {
+            for rule in &self.rules {
+                let fires_at = self.fires_at(rule, &period);
+                if since.is_none_or(|since| fires_at > since)
+                    && fires_at <= now
+                    && !self.expired(rule, &period, now)
+                {
+                    due.push((fires_at, *rule, period));
+                }
+            }
+        }
+
+        due.sort_by_key(|(fires_at, _, _)| *fires_at);
+        due.into_iter()
+            .map(|(_, rule, period)| (rule, period))
+            .collect()
+    }
+
+    /// Whether a rule is too late to run at `now`: submissions and reminders
+    /// once the period's deadline has passed, warnings once it has closed
+    pub fn expired(&self, rule: &ScheduleRule, period: &PayPeriod, now: DateTime<Local>) -> bool {
+        match rule {
+            ScheduleRule::AutoSubmit { .. } | ScheduleRule::Reminder { .. } => {
+                now >= self.deadline(period)
+            }
+            ScheduleRule::EmptyPeriodWarning { .. } => now >= period.closes_at(),
+        }
+    }
+
+    /// The periods [`Self::due`] looks at. Rules for the next period can
+    /// fire early, and a long deadline reaches a few periods back.
+    fn periods(now: DateTime<Local>) -> Vec<PayPeriod> {
+        std::iter::successors(
+            Some(PayPeriod::containing(now.date_naive()).next()),
+            |period| Some(period.previous()),
+        )
+        .take(4)
+        .collect()
+    }
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn reminder_subject(period: &PayPeriod) -> String {
+    format!(
+        "Reminder: timesheet for {} to {} not submitted",
+        period.start.format("%-m/%-d"),
+        period.end.format("%-m/%-d")
+    )
+}
+
+pub fn reminder_text(period: &PayPeriod, deadline: DateTime<Local>, notion_url: &str) -> String {
+    format!(
+        "Your timesheet for {} hasn't been submitted yet. It is due {}.\n\n\
+         Check your hours in Notion: {}",
+        period,
+        deadline.format("%b %-d, %Y at %H:%M"),
+        notion_url
+    )
+}
+
+pub fn empty_period_subject(period: &PayPeriod) -> String {
+    format!(
+        "No hours logged for {} to {}",
+        period.start.format("%-m/%-d"),
+        period.end.format("%-m/%-d")
+    )
+}
+
+pub fn empty_period_text(period: &PayPeriod, notion_url: &str) -> String {
+    format!(
+        "No shifts have been logged for the pay period {}, so there is nothing to submit. \
+         If you worked, add your shifts in Notion: {}",
+        period, notion_url
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeZone};
+
+    fn at(month: u32, day: u32, hour: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2026, month, day, hour, 0, 0)
+            .unwrap()
+    }
+
+    fn period(start: (u32, u32), end: (u32, u32)) -> PayPeriod {
+        PayPeriod {
+            start: NaiveDate::from_ymd_opt(2026, start.0, start.1).unwrap(),
+            end: NaiveDate::from_ymd_opt(2026, end.0, end.1).unwrap(),
+        }
+    }
+
+    fn config() -> ScheduleConfig {
+        ScheduleConfig {
+            rules: vec![
+                ScheduleRule::AutoSubmit {
+                    after_close: Duration::hours(12),
+                },
+                ScheduleRule::Reminder {
+                    before_deadline: Duration::days(1),
+                },
+                ScheduleRule::EmptyPeriodWarning {
+                    before_close: Duration::days(1),
+                },
+            ],
+            ..ScheduleConfig::new()
+        }
+    }
+
+    #[test]
+    fn fires_rules_across_a_period_boundary() {
+        let config = config();
+        let closing = period((10, 9), (10, 23));
+        let auto_submit = config.rules[0];
+        let warning = config.rules[2];
+
+        // The Oct 9-23 period closes at midnight going into Oct 24, which
+        // is also when its warning stops being useful
+        let since = Some(at(10, 22, 12));
+        assert_eq!(config.due(since, at(10, 23, 12)), vec![(warning, closing)]);
+        assert_eq!(
+            config.due(since, at(10, 24, 13)),
+            vec![(auto_submit, closing)]
+        );
+        assert_eq!(config.due(Some(at(10, 24, 13)), at(10, 25, 0)), vec![]);
+    }
+
+    #[test]
+    fn fires_rules_for_past_and_next_periods() {
+        let config = config();
+        let reminder = config.rules[1];
+        let warning = config.rules[2];
+
+        // The reminder for Oct 9-23 is due 2 days after it closes, and the
+        // warning for the next period the day before that one closes
+        assert_eq!(
+            config.due(Some(at(10, 25, 12)), at(10, 26, 12)),
+            vec![(reminder, period((10, 9), (10, 23)))]
+        );
+        assert_eq!(
+            config.due(Some(at(10, 27, 0)), at(11, 8, 12)),
+            vec![(warning, period((10, 24), (11, 8)))]
+        );
+    }
+
+    #[test]
+    fn catches_up_on_missed_rules_until_their_deadline() {
+        let config = config();
+        let closing = period((10, 9), (10, 23));
+        let [auto_submit, reminder] = [config.rules[0], config.rules[1]];
+
+        // Down since before Oct 9-23 closed: its warning is too late, but
+        // the submission and reminder can still go out before the deadline
+        let now = at(10, 26, 12);
+        let missed = vec![(auto_submit, closing), (reminder, closing)];
+        assert_eq!(config.due(None, now), missed);
+
+        // Both stay due on later checks, so a failed run is retried
+        assert_eq!(config.due(None, now + Duration::hours(11)), missed);
+        assert_eq!(config.due(None, at(10, 27, 0)), vec![]);
+    }
+
+    #[test]
+    fn leaves_out_rules_due_before_since() {
+        let config = config();
+        let reminder = config.rules[1];
+
+        assert_eq!(
+            config.due(Some(at(10, 25, 0)), at(10, 26, 12)),
+            vec![(reminder, period((10, 9), (10, 23)))]
+        );
+        assert_eq!(config.due(Some(at(10, 26, 6)), at(10, 26, 12)), vec![]);
+    }
+
+    #[test]
+    fn expires_rules_once_too_late() {
+        let config = config();
+        let closing = period((10, 9), (10, 23));
+        let [auto_submit, reminder, warning] = [config.rules[0], config.rules[1], config.rules[2]];
+
+        // Closes at midnight going into Oct 24, due 3 days later
+        assert!(!config.expired(&warning, &closing, at(10, 23, 12)));
+        assert!(config.expired(&warning, &closing, at(10, 24, 0)));
+        assert!(!config.expired(&auto_submit, &closing, at(10, 26, 23)));
+        assert!(!config.expired(&reminder, &closing, at(10, 26, 23)));
+        assert!(config.expired(&auto_submit, &closing, at(10, 27, 0)));
+        assert!(config.expired(&reminder, &closing, at(10, 27, 0)));
+    }
+}
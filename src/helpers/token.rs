@@ -0,0 +1,133 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tracing::info;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// What a token vouches for: an action (`purpose`) on one thing (`subject`)
+/// until it expires
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    sub: String,
+    purpose: String,
+    exp: i64,
+}
+
+/// The contents of a verified token
+#[derive(Clone, Debug)]
+pub struct VerifiedToken {
+    pub subject: String,
+    pub purpose: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Issue a URL-safe token of the form `<claims>.<signature>`, signed with
+/// HMAC-SHA256
+pub fn issue(secret: &[u8], subject: &str, purpose: &str, expires_at: DateTime<Utc>) -> String {
+    let claims = Claims {
+        sub: subject.to_string(),
+        purpose: purpose.to_string(),
+        exp: expires_at.timestamp(),
+    };
+    let payload =
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).expect("token claims always serialize"));
+
+    format!(
+        "{}.{}",
+        payload,
+        URL_SAFE_NO_PAD.encode(sign(secret, &payload))
+    )
+}
+
+/// Check a token's signature and expiry. Callers still need to check the
+/// purpose is one they expect.
+pub fn verify(secret: &[u8], token: &str) -> Result<VerifiedToken, String> {
+    let (payload, signature) = token.split_once('.').ok_or("Malformed token".to_string())?;
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| "Malformed token signature".to_string())?;
+
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(payload.as_bytes());
+    mac.verify_slice(&signature)
+        .map_err(|_| "Invalid token signature".to_string())?;
+
+    let claims: Claims = URL_SAFE_NO_PAD
+        .decode(payload)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or("Malformed token claims".to_string())?;
+
+    let expires_at =
+        DateTime::from_timestamp(claims.exp, 0).ok_or("Malformed token expiry".to_string())?;
+    if Utc::now() > expires_at {
+        info!(
+            "Rejected expired {} token for {}",
+            claims.purpose, claims.sub
+        );
+        return Err("This link has expired".to_string());
+    }
+
+    Ok(VerifiedToken {
+        subject: claims.sub,
+        purpose: claims.purpose,
+        expires_at,
+    })
+}
+
+fn sign(secret: &[u8], payload: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(payload.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    const SECRET: &[u8] = b"test-secret";
+
+    #[test]
+    fn round_trips() {
+        let expires_at = DateTime::from_timestamp(Utc::now().timestamp() + 3600, 0).unwrap();
+        let token = issue(SECRET, "sub-1", "approve", expires_at);
+        let verified = verify(SECRET, &token).unwrap();
+        assert_eq!(verified.subject, "sub-1");
+        assert_eq!(verified.purpose, "approve");
+        assert_eq!(verified.expires_at, expires_at);
+    }
+
+    #[test]
+    fn rejects_tampered_tokens() {
+        let token = issue(SECRET, "sub-1", "approve", Utc::now() + Duration::hours(1));
+        let (_, signature) = token.split_once('.').unwrap();
+
+        // Same signature on claims for another subject and purpose
+        let other = issue(SECRET, "sub-2", "reject", Utc::now() + Duration::hours(1));
+        let (other_payload, _) = other.split_once('.').unwrap();
+        let forged = format!("{}.{}", other_payload, signature);
+        assert_eq!(
+            verify(SECRET, &forged).unwrap_err(),
+            "Invalid token signature"
+        );
+
+        assert!(verify(b"another-secret", &token).is_err());
+        assert!(verify(SECRET, &token.replace('.', "")).is_err());
+        assert!(verify(SECRET, &format!("{}A", token)).is_err());
+    }
+
+    #[test]
+    fn rejects_expired_tokens() {
+        let token = issue(
+            SECRET,
+            "sub-1",
+            "approve",
+            Utc::now() - Duration::seconds(1),
+        );
+        assert_eq!(verify(SECRET, &token).unwrap_err(), "This link has expired");
+    }
+}
@@ -139,11 +139,12 @@ impl fmt::Display for NotionResponse {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Page {
     object: String,
-    id: String,
+    pub id: String,
     created_time: String,
-    last_edited_time: String,
+    /// ISO 8601, to the minute, e.g. `2026-10-12T17:43:00.000Z`
+    pub last_edited_time: String,
     pub properties: PageProperties,
-    url: String,
+    pub url: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -157,7 +158,7 @@ pub struct PageProperties {
     #[serde(rename = "Duration")]
     duration: FormulaProperty,
     #[serde(rename = "notes")]
-    notes: RichTextProperty,
+    pub notes: RichTextProperty,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -213,6 +214,16 @@ pub struct RichTextProperty {
     rich_text: Vec<RichTextValue>,
 }
 
+impl RichTextProperty {
+    /// The text with all formatting dropped
+    pub fn plain_text(&self) -> String {
+        self.rich_text
+            .iter()
+            .map(|text| text.plain_text.as_str())
+            .collect()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RichTextValue {
     #[serde(rename = "type")]
@@ -1,16 +1,42 @@
 use axum::{
-    extract::State,
+    body::Bytes,
+    extract::{Form, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
+use chrono::{Local, NaiveDate, Utc};
 use reqwest::Client;
-use resend_rs::Resend;
-use std::{error::Error, sync::Arc};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    sync::{Arc, Mutex},
+};
+use tokio::{sync::Semaphore, task::JoinHandle, time::MissedTickBehavior};
 use tracing::{error, info};
+use uuid::Uuid;
 
 use crate::{
-    helpers::{email, notion, pdf::create_sasi_timesheet},
-    models::notion::WebhookAutomationEvent,
+    helpers::{
+        amendment::{self, Revision, RowChange},
+        approval::{self, ApprovalConfig, PendingApproval},
+        archive::{Submission, SubmissionStatus, SubmissionStore},
+        delivery::{self, DeliveryConfig, DeliveryEvent},
+        email::{self, EmailAttachment, EmailConfig, Mailer, RenderedTimesheet, TimesheetEmail},
+        export::{export_timesheet, ExportFormat},
+        idempotency::{self, IdempotencyStore, MemoryStore},
+        late_edit::{self, Drift, LateEditConfig},
+        notion,
+        pdf::{inspect_template, render_timesheet, PdfConfig},
+        period::PayPeriod,
+        preview::{self, PendingPreview, PreviewConfig},
+        schedule::{self, ScheduleConfig, ScheduleRule},
+        token,
+    },
+    models::notion::{Page, WebhookAutomationEvent},
     TimesheetData,
 };
 
@@ -19,6 +45,29 @@ use crate::{
 pub struct TimesheetConfig {
     pub db_id: String,
     pub automation_id: String,
+    /// Employee name recorded on generated timesheets
+    pub employee_name: Option<String>,
+    pub pdf: PdfConfig,
+    /// Spreadsheet copies attached to the email alongside the PDF
+    pub attachments: Vec<ExportFormat>,
+    /// How many PDFs and spreadsheets may be generated at once; 0 means 1
+    pub max_concurrent_renders: usize,
+    /// Sender, recipients and wording of every email the service sends
+    pub email: EmailConfig,
+    /// Have a supervisor approve timesheets before they reach payroll
+    pub approval: Option<ApprovalConfig>,
+    /// Email the employee a preview to confirm before anything is sent
+    pub preview: Option<PreviewConfig>,
+    /// Submit, remind and warn on a timer; see [`TimesheetService::start_scheduler`]
+    pub schedule: Option<ScheduleConfig>,
+    /// Alert the employee about edits to periods already submitted; see
+    /// [`TimesheetService::start_late_edit_check`]
+    pub late_edits: Option<LateEditConfig>,
+    /// Track whether emails arrive through Resend's webhooks
+    pub delivery: Option<DeliveryConfig>,
+    /// Bearer token for the endpoints that expose or resend archived
+    /// timesheets. Without one, those endpoints are turned off.
+    pub admin_token: Option<String>,
 }
 
 /// The main timesheet service that handles Notion data extraction,
@@ -26,21 +75,79 @@ pub struct TimesheetConfig {
 #[derive(Clone)]
 pub struct TimesheetService {
     pub notion_client: Client,
-    pub resend: Resend,
+    pub mailer: Arc<dyn Mailer>,
     pub config: TimesheetConfig,
+    /// Bounds the number of renders running on the blocking pool
+    render_permits: Arc<Semaphore>,
+    /// Pay periods currently being processed
+    active_periods: Arc<Mutex<HashSet<(NaiveDate, NaiveDate)>>>,
+    /// Submissions already emailed, by event ID and content hash
+    idempotency: Arc<dyn IdempotencyStore>,
+    /// Timesheets waiting for the supervisor, by approval ID. These are kept
+    /// in memory, so a restart drops them.
+    pending_approvals: Arc<Mutex<HashMap<String, PendingApproval>>>,
+    /// Previews waiting for the employee to confirm, by preview ID. Also
+    /// kept in memory only.
+    pending_previews: Arc<Mutex<HashMap<String, PendingPreview>>>,
+    /// Every submission and its PDF, when an archive is set
+    archive: Option<Arc<dyn SubmissionStore>>,
+    /// Held while recording a delivery event, so events for the same email
+    /// arriving together don't overwrite each other
+    delivery_updates: Arc<Mutex<()>>,
+}
+
+/// Marks a pay period as being processed until dropped
+struct PeriodGuard {
+    active_periods: Arc<Mutex<HashSet<(NaiveDate, NaiveDate)>>>,
+    period: (NaiveDate, NaiveDate),
+}
+
+impl Drop for PeriodGuard {
+    fn drop(&mut self) {
+        if let Ok(mut active_periods) = self.active_periods.lock() {
+            active_periods.remove(&self.period);
+        }
+    }
 }
 
 impl TimesheetService {
-    /// Create a new timesheet service instance
-    pub fn new(notion_client: Client, resend: Resend, config: TimesheetConfig) -> Self {
+    /// Create a new timesheet service instance that sends email through
+    /// `mailer`, e.g. a `ResendMailer`, `SmtpMailer` or `FileMailer`
+    pub fn new(
+        notion_client: Client,
+        mailer: impl Mailer + 'static,
+        config: TimesheetConfig,
+    ) -> Self {
         info!("Creating new TimesheetService instance");
+        let render_permits = Arc::new(Semaphore::new(config.max_concurrent_renders.max(1)));
         Self {
             notion_client,
-            resend,
+            mailer: Arc::new(mailer),
             config,
+            render_permits,
+            active_periods: Arc::new(Mutex::new(HashSet::new())),
+            idempotency: Arc::new(MemoryStore::new()),
+            pending_approvals: Arc::new(Mutex::new(HashMap::new())),
+            pending_previews: Arc::new(Mutex::new(HashMap::new())),
+            archive: None,
+            delivery_updates: Arc::new(Mutex::new(())),
         }
     }
 
+    /// Remember sent submissions somewhere other than memory, such as a
+    /// `FileStore`, so retries after a restart are still recognized
+    pub fn with_idempotency_store(mut self, store: impl IdempotencyStore + 'static) -> Self {
+        self.idempotency = Arc::new(store);
+        self
+    }
+
+    /// Keep every submission and its PDF in `store`, such as a `SqliteStore`,
+    /// and serve them from the `/timesheet-submissions` endpoints
+    pub fn with_archive(mut self, store: impl SubmissionStore + 'static) -> Self {
+        self.archive = Some(Arc::new(store));
+        self
+    }
+
     /// Create an Axum router for the timesheet service
     pub fn router(self) -> Router {
         info!("Creating timesheet service router");
@@ -50,58 +157,1246 @@ impl TimesheetService {
             .route("/timesheet-webhook", post(timesheet_webhook))
             .route("/timesheet-test", get(timesheet_test))
             .route("/timesheet-db-info", get(timesheet_db_info))
+            .route("/timesheet-template-info", get(timesheet_template_info))
+            .route("/timesheet-export/{format}", get(timesheet_export))
+            .route("/timesheet/render", post(timesheet_render))
+            .route(
+                "/timesheet-approval",
+                get(timesheet_approval_page).post(timesheet_approval_decision),
+            )
+            .route(
+                "/timesheet-confirm",
+                get(timesheet_confirm_page).post(timesheet_confirm),
+            )
+            .route("/timesheet-submissions", get(timesheet_submissions))
+            .route("/timesheet-submissions/{id}/pdf", get(timesheet_submission_pdf))
+            .route("/timesheet-submissions/{id}/resend", post(timesheet_submission_resend))
+            .route("/timesheet-amendments/{date}", get(timesheet_amendment_diff))
+            .route("/timesheet-late-edits", get(timesheet_late_edits))
+            .route("/timesheet-delivery-webhook", post(timesheet_delivery_webhook))
+            .route(
+                "/timesheet-amend",
+                get(timesheet_amend_page).post(timesheet_amend),
+            )
             .with_state(shared_state)
     }
 
+    /// Fetch the current pay period's entries from Notion
+    pub async fn fetch_timesheet_data(&self) -> Result<TimesheetData, Box<dyn Error>> {
+        self.fetch_period_data(PayPeriod::current().into()).await
+    }
+
+    /// Fetch a pay period's entries from Notion
+    pub async fn fetch_period_data(
+        &self,
+        period: (NaiveDate, NaiveDate),
+    ) -> Result<TimesheetData, Box<dyn Error>> {
+        let timesheet_raw_data =
+            notion::fetch_period_data(&self.notion_client, &self.config.db_id, period).await?;
+        self.timesheet_from_pages(timesheet_raw_data.results, period)
+    }
+
+    fn timesheet_from_pages(
+        &self,
+        pages: Vec<Page>,
+        period: (NaiveDate, NaiveDate),
+    ) -> Result<TimesheetData, Box<dyn Error>> {
+        let mut timesheet_data = TimesheetData::try_from(pages)
+            .map_err(|err| format!("Error with parsing your linked database: {}", err))?;
+        timesheet_data.employee = self.config.employee_name.clone();
+        timesheet_data.period = Some(period);
+
+        info!(
+            "Successfully parsed timesheet data with {} entries",
+            timesheet_data.entries.len()
+        );
+
+        Ok(timesheet_data)
+    }
+
+    /// Run CPU-bound or blocking work, such as rendering, on the blocking
+    /// pool once a render permit is free
+    async fn run_blocking<T, F>(&self, task: F) -> Result<T, String>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T, String> + Send + 'static,
+    {
+        let permit = self
+            .render_permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| format!("Render queue closed: {}", e))?;
+
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            task()
+        })
+        .await
+        .map_err(|e| {
+            error!("Render task failed: {}", e);
+            format!("Render task failed: {}", e)
+        })?
+    }
+
+    /// Run a call against the archive on the blocking pool, since SQLite and
+    /// the PDF files block. Fails when there is no archive.
+    async fn in_archive<T, F>(&self, task: F) -> Result<T, String>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn SubmissionStore) -> Result<T, String> + Send + 'static,
+    {
+        let archive = self
+            .archive
+            .clone()
+            .ok_or("The submission archive is not enabled".to_string())?;
+
+        tokio::task::spawn_blocking(move || task(archive.as_ref()))
+            .await
+            .map_err(|e| {
+                error!("Archive task failed: {}", e);
+                format!("Archive task failed: {}", e)
+            })?
+    }
+
+    /// Render a timesheet PDF with the service's PDF config off the async runtime
+    pub async fn render_pdf(&self, timesheet_data: TimesheetData) -> Result<Vec<u8>, String> {
+        let pdf_config = self.config.pdf.clone();
+        self.run_blocking(move || render_timesheet(timesheet_data, &pdf_config))
+            .await
+    }
+
+    /// Export a timesheet as a spreadsheet off the async runtime
+    pub async fn export(
+        &self,
+        timesheet_data: TimesheetData,
+        format: ExportFormat,
+    ) -> Result<Vec<u8>, String> {
+        self.run_blocking(move || export_timesheet(&timesheet_data, format))
+            .await
+    }
+
+    /// Spreadsheet copies of a timesheet in each configured format, named
+    /// to match its email
+    async fn export_attachments(
+        &self,
+        timesheet_data: &TimesheetData,
+        timesheet_email: &TimesheetEmail,
+    ) -> Result<Vec<EmailAttachment>, String> {
+        let mut attachments = Vec::new();
+        for format in &self.config.attachments {
+            match self.export(timesheet_data.clone(), *format).await {
+                Ok(bytes) => attachments.push(EmailAttachment {
+                    filename: timesheet_email.filename(format.extension()),
+                    content_type: format.content_type().to_string(),
+                    content: bytes,
+                }),
+                Err(e) => {
+                    error!("Failed to export timesheet as {}: {}", format, e);
+                    return Err(format!("Error exporting timesheet as {}: {}", format, e));
+                }
+            }
+        }
+        Ok(attachments)
+    }
+
+    /// File name for a download, falling back to `Timesheet.<ext>` if the
+    /// configured template fails
+    fn download_name(&self, timesheet_data: &TimesheetData, extension: &str) -> String {
+        self.config
+            .email
+            .filename(timesheet_data, extension)
+            .unwrap_or(format!("Timesheet.{}", extension))
+    }
+
+    /// Claim a pay period, failing if it is already being processed
+    fn lock_period(&self, period: (NaiveDate, NaiveDate)) -> Result<PeriodGuard, String> {
+        let mut active_periods = self
+            .active_periods
+            .lock()
+            .map_err(|_| "Pay period lock poisoned".to_string())?;
+        if !active_periods.insert(period) {
+            return Err(format!(
+                "Timesheet for {} to {} is already being processed",
+                period.0, period.1
+            ));
+        }
+
+        Ok(PeriodGuard {
+            active_periods: self.active_periods.clone(),
+            period,
+        })
+    }
+
+    /// Email an error report, ignoring failures to send it
+    async fn report_error(&self, error_info: &str) {
+        let _ = email::send_error_info(self.mailer.as_ref(), &self.config.email, error_info).await;
+    }
+
+    /// Look up the email sent for an idempotency key. Store failures are
+    /// logged and treated as a miss, so a broken store can't block submission.
+    fn recall(&self, key: &str) -> Option<String> {
+        self.idempotency.get(key).unwrap_or_else(|e| {
+            error!("Failed to read idempotency key {}: {}", key, e);
+            None
+        })
+    }
+
+    /// Record the email sent for idempotency keys. A store like `FileStore`
+    /// writes to disk, so this runs on the blocking pool.
+    async fn remember<'a>(&self, keys: impl IntoIterator<Item = &'a String>, email_id: &str) {
+        let store = self.idempotency.clone();
+        let keys = keys.into_iter().cloned().collect::<Vec<_>>();
+        let email_id = email_id.to_string();
+
+        let result = tokio::task::spawn_blocking(move || {
+            for key in keys {
+                if let Err(e) = store.put(&key, &email_id) {
+                    error!("Failed to record idempotency key {}: {}", key, e);
+                }
+            }
+        })
+        .await;
+        if let Err(e) = result {
+            error!("Idempotency task failed: {}", e);
+        }
+    }
+
     /// Process timesheet data: fetch from Notion, create PDF, send email
     pub async fn process_timesheet(&self) -> Result<String, Box<dyn Error>> {
-        info!("Processing timesheet for database: {}", self.config.db_id);
-        
-        let timesheet_raw_data = notion::fetch_data(&self.notion_client, &self.config.db_id).await?;
+        self.process_timesheet_event(None).await
+    }
+
+    /// Process a timesheet for a webhook delivery. A repeat delivery of the
+    /// same event, or entries unchanged since an earlier submission, returns
+    /// the original email ID instead of sending another email.
+    pub async fn process_timesheet_event(
+        &self,
+        event_id: Option<&str>,
+    ) -> Result<String, Box<dyn Error>> {
+        self.process_period(PayPeriod::current().into(), event_id).await
+    }
+
+    /// Process the timesheet for a given pay period
+    pub async fn process_period(
+        &self,
+        period: (NaiveDate, NaiveDate),
+        event_id: Option<&str>,
+    ) -> Result<String, Box<dyn Error>> {
+        info!(
+            "Processing timesheet for database {}, period {} to {}",
+            self.config.db_id, period.0, period.1
+        );
+
+        let event_key = event_id.map(idempotency::event_key);
+        if let Some(email_id) = event_key.as_deref().and_then(|key| self.recall(key)) {
+            info!(
+                "Event {} was already handled, email ID: {}",
+                event_id.unwrap_or_default(),
+                email_id
+            );
+            return Ok(email_id);
+        }
+
+        let _period_guard = match self.lock_period(period) {
+            Ok(guard) => guard,
+            Err(err) => {
+                info!("{}", err);
+                return Err(err.into());
+            }
+        };
+
+        let rendered_at = Utc::now();
+        // Errors are turned into strings so nothing non-Send is held across awaits
+        let timesheet_data = match self.fetch_period_data(period).await.map_err(|e| e.to_string()) {
+            Ok(timesheet_data) => timesheet_data,
+            Err(err) => {
+                error!("Error loading timesheet data: {}", err);
+                self.report_error(&err).await;
+                return Err(err.into());
+            }
+        };
+
+        let content_key = idempotency::content_key(&timesheet_data);
+        let unchanged =
+            idempotency::sent_unchanged(self.idempotency.as_ref(), &timesheet_data, period)
+                .unwrap_or_else(|e| {
+                    error!("Failed to read idempotency keys: {}", e);
+                    None
+                });
+        if let Some(email_id) = unchanged {
+            info!(
+                "Timesheet is unchanged since email {}, not sending it again",
+                email_id
+            );
+            self.remember(&event_key, &email_id).await;
+            return Ok(email_id);
+        }
+
+        let notion_url = notion::utils::database_url(&self.config.db_id);
+        let timesheet_email = match self.config.email.render(&timesheet_data, &notion_url) {
+            Ok(timesheet_email) => timesheet_email,
+            Err(e) => {
+                let error_msg = format!("Error rendering timesheet email: {}", e);
+                self.report_error(&error_msg).await;
+                return Err(e.into());
+            }
+        };
+
+        let attachments = match self.export_attachments(&timesheet_data, &timesheet_email).await {
+            Ok(attachments) => attachments,
+            Err(e) => {
+                self.report_error(&e).await;
+                return Err(e.into());
+            }
+        };
 
-        match TimesheetData::try_from(timesheet_raw_data.results) {
-            Ok(timesheet_data) => {
+        match self.render_pdf(timesheet_data.clone()).await {
+            Ok(timesheet_pdf) => {
                 info!(
-                    "Successfully parsed timesheet data with {} entries",
-                    timesheet_data.entries.len()
+                    "Successfully created timesheet PDF, size: {} bytes",
+                    timesheet_pdf.len()
                 );
 
-                match create_sasi_timesheet(timesheet_data) {
-                    Ok(timesheet_pdf) => {
-                        info!(
-                            "Successfully created timesheet PDF, size: {} bytes",
-                            timesheet_pdf.len()
-                        );
-
-                        match email::send_timesheet_email(&self.resend, timesheet_pdf).await {
-                            Ok(res) => {
-                                info!("Email sent successfully with ID: {}", res.id);
-                                Ok(res.id.to_string())
-                            }
-                            Err(e) => {
-                                error!("Error sending email: {}", e);
-                                let error_msg = format!("Error sending email: {}", e);
-                                let _ = email::send_error_info(&self.resend, &error_msg).await;
-                                Err(Box::new(e))
-                            }
-                        }
-                    }
+                let idempotency_keys: Vec<String> =
+                    [content_key, idempotency::period_key(period)]
+                        .into_iter()
+                        .chain(event_key)
+                        .collect();
+                let timesheet = RenderedTimesheet {
+                    data: timesheet_data,
+                    email: timesheet_email,
+                    pdf: timesheet_pdf,
+                    attachments,
+                    revision: None,
+                    rendered_at,
+                };
+                match &self.config.preview {
+                    Some(preview) => self
+                        .request_preview(preview, period, timesheet, idempotency_keys)
+                        .await
+                        .map_err(Into::into),
+                    None => self
+                        .submit(timesheet, idempotency_keys)
+                        .await
+                        .map_err(Into::into),
+                }
+            }
+            Err(e) => {
+                error!("Failed to create timesheet PDF: {}", e);
+                let error_msg = format!("Error creating timesheet PDF: {}", e);
+                self.report_error(&error_msg).await;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Start running the configured schedule in the background. Returns
+    /// `None` when there is no schedule. Must be called within a Tokio runtime.
+    pub fn start_scheduler(&self) -> Option<JoinHandle<()>> {
+        let schedule = self.config.schedule.clone()?;
+        let service = self.clone();
+        info!("Starting scheduler with {} rules", schedule.rules.len());
+
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(schedule.check_every);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            // Catching up on missed rules is only safe when a restart still
+            // knows which rules already ran; otherwise start from now
+            let remembers = service.idempotency.persistent() || service.archive.is_some();
+            let since = (!remembers).then(Local::now);
+            loop {
+                interval.tick().await;
+                // Rules that failed have nothing recorded, so they come up
+                // again on the next tick until their deadline
+                for (rule, period) in schedule.due(since, Local::now()) {
+                    service.run_rule(&schedule, rule, period).await;
+                }
+            }
+        }))
+    }
+
+    /// Run one scheduled rule for a pay period, unless it already ran.
+    /// Failures are logged and reported, and left unrecorded so the next
+    /// check tries again.
+    async fn run_rule(&self, schedule: &ScheduleConfig, rule: ScheduleRule, period: PayPeriod) {
+        let rule_key = idempotency::schedule_key(rule.name(), period.into());
+        // Rules stay due until their deadline, so this is the usual case
+        if self.recall(&rule_key).is_some() {
+            return;
+        }
+        info!("Running scheduled {:?} for {}", rule, period);
+
+        // The archive also knows, in case the idempotency store was lost
+        let submitted = match self.recall(&idempotency::period_key(period.into())) {
+            Some(email_id) => Some(email_id),
+            None if self.archive.is_some() => self
+                .latest_sent(period.into())
+                .await
+                .ok()
+                .flatten()
+                .map(|submission| submission.email_id.unwrap_or(submission.id)),
+            None => None,
+        };
+        let notify = if schedule.notify.is_empty() {
+            vec![self.config.email.from.clone()]
+        } else {
+            schedule.notify.clone()
+        };
+        let notion_url = notion::utils::database_url(&self.config.db_id);
+
+        let outcome = match rule {
+            ScheduleRule::AutoSubmit { .. } | ScheduleRule::Reminder { .. }
+                if submitted.is_some() =>
+            {
+                let email_id = submitted.unwrap_or_default();
+                info!(
+                    "Timesheet for {} was already submitted, email ID: {}",
+                    period, email_id
+                );
+                Ok(email_id)
+            }
+            // Went past its deadline while earlier rules ran: too late to
+            // submit, remind or warn. AutoSubmit reports an empty period itself.
+            _ if schedule.expired(&rule, &period, Local::now()) => {
+                info!("Too late to run {:?} for {}, skipping it", rule, period);
+                Ok("expired".to_string())
+            }
+            ScheduleRule::AutoSubmit { .. } | ScheduleRule::EmptyPeriodWarning { .. } => {
+                let timesheet_data = match self
+                    .fetch_period_data(period.into())
+                    .await
+                    .map_err(|e| e.to_string())
+                {
+                    Ok(timesheet_data) => timesheet_data,
                     Err(e) => {
-                        error!("Failed to create timesheet PDF: {}", e);
-                        let error_msg = format!("Error creating timesheet PDF: {}", e);
-                        let _ = email::send_error_info(&self.resend, &error_msg).await;
-                        Err(e.into())
+                        let error_msg =
+                            format!("Error loading timesheet data for {}: {}", period, e);
+                        error!("{}", error_msg);
+                        self.report_error(&error_msg).await;
+                        return;
                     }
+                };
+
+                if timesheet_data.entries.is_empty() {
+                    info!("No entries for {}, sending a warning", period);
+                    email::send_notice(
+                        self.mailer.as_ref(),
+                        &self.config.email,
+                        notify,
+                        &schedule::empty_period_subject(&period),
+                        &schedule::empty_period_text(&period, &notion_url),
+                    )
+                    .await
+                } else if matches!(rule, ScheduleRule::AutoSubmit { .. }) {
+                    self.process_period(period.into(), None)
+                        .await
+                        .map_err(|e| e.to_string())
+                        .inspect(|email_id| {
+                            info!("Auto-submitted {}, email ID: {}", period, email_id)
+                        })
+                } else {
+                    Ok("has entries".to_string())
                 }
             }
-            Err(err) => {
-                error!("Error parsing Notion database: {}", err);
-                let error_msg = format!("Error with parsing your linked database: {}", err);
-                let _ = email::send_error_info(&self.resend, &error_msg).await;
-                Err(err.into())
+            ScheduleRule::Reminder { .. } => {
+                email::send_notice(
+                    self.mailer.as_ref(),
+                    &self.config.email,
+                    notify,
+                    &schedule::reminder_subject(&period),
+                    &schedule::reminder_text(&period, schedule.deadline(&period), &notion_url),
+                )
+                .await
             }
+        };
+
+        match outcome {
+            Ok(outcome) => self.remember([&rule_key], &outcome).await,
+            Err(e) => error!("Scheduled {:?} failed for {}: {}", rule, period, e),
         }
     }
+
+    /// Send a rendered timesheet on its way: to the supervisor when approval
+    /// is configured, otherwise straight to the official recipients
+    async fn submit(
+        &self,
+        timesheet: RenderedTimesheet,
+        idempotency_keys: Vec<String>,
+    ) -> Result<String, String> {
+        if let Some(approval) = &self.config.approval {
+            return self
+                .request_approval(approval, timesheet, idempotency_keys)
+                .await;
+        }
+
+        let result = email::send_timesheet_email(
+            self.mailer.as_ref(),
+            &self.config.email,
+            &timesheet.email,
+            timesheet.pdf.clone(),
+            timesheet.attachments.clone(),
+        )
+        .await;
+        self.archive(
+            Submission::new(
+                Uuid::new_v4().to_string(),
+                &timesheet,
+                self.config.email.recipients(),
+                &result,
+                SubmissionStatus::Sent,
+            ),
+            &timesheet.pdf,
+        )
+        .await;
+
+        match result {
+            Ok(email_id) => {
+                info!("Email sent successfully with ID: {}", email_id);
+                self.remember(&idempotency_keys, &email_id).await;
+                Ok(email_id)
+            }
+            Err(e) => {
+                error!("Error sending email: {}", e);
+                let error_msg = format!("Error sending email: {}", e);
+                self.report_error(&error_msg).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Keep a submission and its PDF in the archive, if there is one.
+    /// Failures are reported but don't fail the submission.
+    async fn archive(&self, submission: Submission, pdf: &[u8]) {
+        if self.archive.is_none() {
+            return;
+        }
+        let (id, status) = (submission.id.clone(), submission.status);
+        let pdf = pdf.to_vec();
+        let result = self
+            .in_archive(move |archive| {
+                archive
+                    .save_pdf(&pdf)
+                    .and_then(|_| archive.save(&submission))
+            })
+            .await;
+        match result {
+            Ok(()) => info!("Archived submission {} as {}", id, status),
+            Err(e) => {
+                self.report_error(&format!("Error archiving submission {}: {}", id, e))
+                    .await
+            }
+        }
+    }
+
+    /// Change an archived submission, if there is an archive and it has one
+    async fn update_submission(
+        &self,
+        id: &str,
+        update: impl FnOnce(&mut Submission) + Send + 'static,
+    ) {
+        if self.archive.is_none() {
+            return;
+        }
+        let submission_id = id.to_string();
+        let result = self
+            .in_archive(move |archive| match archive.get(&submission_id)? {
+                Some(mut submission) => {
+                    update(&mut submission);
+                    archive.save(&submission)
+                }
+                None => Err(format!("Submission {} is not in the archive", submission_id)),
+            })
+            .await;
+        if let Err(e) = result {
+            error!("Failed to update submission {}: {}", id, e);
+        }
+    }
+
+    /// Send the employee a preview with a confirm link, and hold the
+    /// timesheet until they confirm or the pay period ends. A newer preview
+    /// for the same period replaces any older one.
+    async fn request_preview(
+        &self,
+        preview: &PreviewConfig,
+        period: (NaiveDate, NaiveDate),
+        timesheet: RenderedTimesheet,
+        idempotency_keys: Vec<String>,
+    ) -> Result<String, String> {
+        let preview_id = Uuid::new_v4().to_string();
+        // A scheduled submission goes out after the period closes, so its
+        // preview has to stay open until the deadline
+        let expires_at = match &self.config.schedule {
+            Some(schedule) => preview::expires_at(period.1)
+                .max(schedule.deadline(&period.into()).with_timezone(&Utc)),
+            None => preview::expires_at(period.1),
+        };
+        let confirm_url = preview.link(&token::issue(
+            preview.secret.as_bytes(),
+            &preview_id,
+            preview::CONFIRM,
+            expires_at,
+        ));
+
+        let preview_email = preview::preview_email(&timesheet.email, &confirm_url, expires_at);
+        let email_id = match email::send_timesheet_for_review(
+            self.mailer.as_ref(),
+            &self.config.email,
+            &preview.recipient,
+            &preview_email,
+            timesheet.pdf.clone(),
+            timesheet.attachments.clone(),
+        )
+        .await
+        {
+            Ok(email_id) => email_id,
+            Err(e) => {
+                self.report_error(&format!("Error sending timesheet preview: {}", e))
+                    .await;
+                return Err(e);
+            }
+        };
+
+        info!(
+            "Sent preview {} to {}, email ID: {}",
+            preview_id, preview.recipient, email_id
+        );
+        // Only the webhook event is settled; unchanged entries may be previewed again
+        self.remember(
+            idempotency_keys.iter().filter(|key| key.starts_with("event:")),
+            &email_id,
+        )
+        .await;
+
+        if let Ok(mut pending_previews) = self.pending_previews.lock() {
+            let now = Utc::now();
+            pending_previews
+                .retain(|_, pending| pending.expires_at > now && pending.period != period);
+            pending_previews.insert(
+                preview_id,
+                PendingPreview {
+                    timesheet,
+                    idempotency_keys,
+                    period,
+                    expires_at,
+                },
+            );
+        }
+
+        Ok(email_id)
+    }
+
+    fn pending_preview(&self, preview_id: &str) -> Option<PendingPreview> {
+        self.pending_previews
+            .lock()
+            .ok()?
+            .get(preview_id)
+            .cloned()
+    }
+
+    /// Send a confirmed preview's PDF, unchanged, on to the official
+    /// recipients (or the supervisor). Returns a message for the employee.
+    pub async fn confirm_preview(&self, preview_id: &str) -> Result<String, String> {
+        let pending = self
+            .pending_previews
+            .lock()
+            .map_err(|_| "Pending previews lock poisoned".to_string())?
+            .remove(preview_id)
+            .filter(|pending| pending.expires_at > Utc::now())
+            .ok_or("This preview has already been sent, was replaced, or has expired".to_string())?;
+
+        let result = match self.lock_period(pending.period) {
+            Ok(_period_guard) => {
+                self.submit(pending.timesheet.clone(), pending.idempotency_keys.clone())
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(email_id) => {
+                info!("Preview {} confirmed, email ID: {}", preview_id, email_id);
+                Ok(if self.config.approval.is_some() {
+                    "Confirmed. The timesheet has been sent to your supervisor for approval."
+                        .to_string()
+                } else {
+                    "Confirmed. The timesheet has been sent.".to_string()
+                })
+            }
+            // Keep the preview so confirming can be retried
+            Err(e) => {
+                error!("Failed to send confirmed preview {}: {}", preview_id, e);
+                if let Ok(mut pending_previews) = self.pending_previews.lock() {
+                    pending_previews.insert(preview_id.to_string(), pending);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Send a rendered timesheet to the supervisor with approve and reject
+    /// links, and hold it until they decide. The archived submission shares
+    /// the approval's ID.
+    async fn request_approval(
+        &self,
+        approval: &ApprovalConfig,
+        timesheet: RenderedTimesheet,
+        idempotency_keys: Vec<String>,
+    ) -> Result<String, String> {
+        let approval_id = Uuid::new_v4().to_string();
+        let expires_at = Utc::now() + approval.link_ttl;
+        let secret = approval.secret.as_bytes();
+        let approve_url =
+            approval.link(&token::issue(secret, &approval_id, approval::APPROVE, expires_at));
+        let reject_url =
+            approval.link(&token::issue(secret, &approval_id, approval::REJECT, expires_at));
+
+        let request =
+            approval::request_email(&timesheet.email, &approve_url, &reject_url, expires_at);
+        let result = email::send_timesheet_for_review(
+            self.mailer.as_ref(),
+            &self.config.email,
+            &approval.supervisor,
+            &request,
+            timesheet.pdf.clone(),
+            timesheet.attachments.clone(),
+        )
+        .await;
+        self.archive(
+            Submission::new(
+                approval_id.clone(),
+                &timesheet,
+                vec![approval.supervisor.clone()],
+                &result,
+                SubmissionStatus::AwaitingApproval,
+            ),
+            &timesheet.pdf,
+        )
+        .await;
+
+        let email_id = match result {
+            Ok(email_id) => email_id,
+            Err(e) => {
+                self.report_error(&format!("Error sending timesheet for approval: {}", e))
+                    .await;
+                return Err(e);
+            }
+        };
+
+        info!(
+            "Sent timesheet to {} for approval {}, email ID: {}",
+            approval.supervisor, approval_id, email_id
+        );
+        // Only the webhook event is settled. The content and period keys wait
+        // for approval, since a restart or rejection drops this request.
+        self.remember(
+            idempotency_keys.iter().filter(|key| key.starts_with("event:")),
+            &email_id,
+        )
+        .await;
+
+        if let Ok(mut pending_approvals) = self.pending_approvals.lock() {
+            let now = Utc::now();
+            pending_approvals.retain(|_, pending| pending.expires_at > now);
+            pending_approvals.insert(
+                approval_id,
+                PendingApproval {
+                    timesheet,
+                    idempotency_keys,
+                    expires_at,
+                },
+            );
+        }
+
+        Ok(email_id)
+    }
+
+    fn pending_approval(&self, approval_id: &str) -> Option<PendingApproval> {
+        self.pending_approvals
+            .lock()
+            .ok()?
+            .get(approval_id)
+            .cloned()
+    }
+
+    /// Act on the supervisor's decision, returning a message for them.
+    /// Fails if the approval is unknown, already decided or couldn't be sent.
+    pub async fn decide_approval(
+        &self,
+        approval_id: &str,
+        action: &str,
+        comment: &str,
+    ) -> Result<String, String> {
+        let approval = self
+            .config
+            .approval
+            .as_ref()
+            .ok_or("Approval is not enabled".to_string())?;
+
+        let pending = self
+            .pending_approvals
+            .lock()
+            .map_err(|_| "Pending approvals lock poisoned".to_string())?
+            .remove(approval_id)
+            .ok_or("This timesheet has already been approved or rejected".to_string())?;
+        let subject = &pending.timesheet.email.subject;
+
+        let result = if action == approval::APPROVE {
+            let approved = approval::approved_email(
+                &pending.timesheet.email,
+                &approval.supervisor,
+                Utc::now(),
+            );
+            let result = email::send_timesheet_email(
+                self.mailer.as_ref(),
+                &self.config.email,
+                &approved,
+                pending.timesheet.pdf.clone(),
+                pending.timesheet.attachments.clone(),
+            )
+            .await;
+            if let Ok(email_id) = &result {
+                info!("Approval {} forwarded to payroll, email ID: {}", approval_id, email_id);
+                self.remember(&pending.idempotency_keys, email_id).await;
+                let recipients = self.config.email.recipients();
+                let email_id = email_id.clone();
+                self.update_submission(approval_id, move |submission| {
+                    submission.status = SubmissionStatus::Sent;
+                    submission.recipients = recipients;
+                    submission.email_id = Some(email_id);
+                    submission.submitted_at = Utc::now();
+                })
+                .await;
+            }
+            result.map(|_| "Approved. The timesheet has been sent to payroll.".to_string())
+        } else {
+            let notify = if approval.notify_on_reject.is_empty() {
+                vec![self.config.email.from.clone()]
+            } else {
+                approval.notify_on_reject.clone()
+            };
+            let result = email::send_notice(
+                self.mailer.as_ref(),
+                &self.config.email,
+                notify,
+                &format!("Rejected: {}", subject),
+                &approval::rejection_text(subject, &approval.supervisor, comment),
+            )
+            .await;
+            if let Ok(email_id) = &result {
+                info!("Approval {} rejected, notice email ID: {}", approval_id, email_id);
+                self.update_submission(approval_id, |submission| {
+                    submission.status = SubmissionStatus::Rejected;
+                })
+                .await;
+            }
+            result.map(|_| "Rejected. The employee has been told.".to_string())
+        };
+
+        // Keep the timesheet so the decision can be retried
+        if let Err(e) = &result {
+            error!("Failed to act on approval {}: {}", approval_id, e);
+            if let Ok(mut pending_approvals) = self.pending_approvals.lock() {
+                pending_approvals.insert(approval_id.to_string(), pending);
+            }
+        }
+        result
+    }
+
+    /// The latest submission payroll received for a period
+    async fn latest_sent(
+        &self,
+        period: (NaiveDate, NaiveDate),
+    ) -> Result<Option<Submission>, String> {
+        self.in_archive(move |archive| archive.latest_sent(period))
+            .await
+    }
+
+    /// Compare a period's entries in Notion with the last version payroll
+    /// received. Returns that submission, the current data and the changes.
+    pub async fn compare_with_submitted(
+        &self,
+        period: (NaiveDate, NaiveDate),
+    ) -> Result<(Submission, TimesheetData, Vec<RowChange>), String> {
+        let submitted = self.latest_sent(period).await?.ok_or(format!(
+            "Nothing has been sent for {} to {} yet",
+            period.0, period.1
+        ))?;
+        let mut current = self
+            .fetch_period_data(period)
+            .await
+            .map_err(|e| format!("Error loading timesheet data: {}", e))?;
+        if current.employee.is_none() {
+            current.employee = submitted.timesheet.employee.clone();
+        }
+
+        let changes = amendment::diff(&submitted.timesheet, &current);
+        Ok((submitted, current, changes))
+    }
+
+    /// Send an amended timesheet for a period that was already submitted,
+    /// marked REVISED and listing what changed since the last version.
+    /// Goes through approval like any other submission.
+    pub async fn amend_period(&self, period: (NaiveDate, NaiveDate)) -> Result<String, String> {
+        self.amend(period, None).await
+    }
+
+    /// Amend the period of an archived submission, as long as it is still
+    /// the latest version payroll received. Returns a message for the employee.
+    pub async fn amend_submission(&self, submission_id: &str) -> Result<String, String> {
+        let period = self
+            .submission(submission_id)
+            .await?
+            .and_then(|submission| submission.period())
+            .ok_or(format!("No submission {}", submission_id))?;
+
+        self.amend(period, Some(submission_id)).await?;
+        Ok(if self.config.approval.is_some() {
+            "The revised timesheet has been sent to your supervisor for approval.".to_string()
+        } else {
+            "The revised timesheet has been sent.".to_string()
+        })
+    }
+
+    async fn amend(
+        &self,
+        period: (NaiveDate, NaiveDate),
+        revises: Option<&str>,
+    ) -> Result<String, String> {
+        let _period_guard = self.lock_period(period)?;
+        let rendered_at = Utc::now();
+        let (submitted, current, changes) = self.compare_with_submitted(period).await?;
+        if revises.is_some_and(|id| id != submitted.id) {
+            return Err(format!(
+                "This timesheet was already replaced by submission {}",
+                submitted.id
+            ));
+        }
+        if changes.is_empty() {
+            return Err(format!(
+                "Nothing has changed since submission {}",
+                submitted.id
+            ));
+        }
+
+        let revision = Revision {
+            number: submitted
+                .revision
+                .as_ref()
+                .map_or(1, |revision| revision.number + 1),
+            revises: submitted.id.clone(),
+            changes: amendment::changes_text(&changes, &submitted.timesheet, &current),
+        };
+        info!(
+            "Amending submission {} as revision {} with {} changes",
+            submitted.id,
+            revision.number,
+            changes.len()
+        );
+
+        let notion_url = notion::utils::database_url(&self.config.db_id);
+        let timesheet_email = self.config.email.render(&current, &notion_url)?;
+        let attachments = self.export_attachments(&current, &timesheet_email).await?;
+        let mut pdf_config = self.config.pdf.clone();
+        pdf_config.banner = Some(amendment::REVISED.to_string());
+        let data = current.clone();
+        let pdf = self
+            .run_blocking(move || render_timesheet(data, &pdf_config))
+            .await
+            .map_err(|e| format!("Error creating timesheet PDF: {}", e))?;
+
+        let idempotency_keys = vec![
+            idempotency::content_key(&current),
+            idempotency::period_key(period),
+        ];
+        let timesheet = RenderedTimesheet {
+            email: amendment::amended_email(&timesheet_email, &revision),
+            data: current,
+            pdf,
+            attachments,
+            revision: Some(revision),
+            rendered_at,
+        };
+        self.submit(timesheet, idempotency_keys).await
+    }
+
+    /// Compare a submitted period with Notion: the pages edited since the
+    /// latest submission and what an amendment would change. `None` when
+    /// nothing has been sent for the period.
+    pub async fn check_late_edits(
+        &self,
+        period: (NaiveDate, NaiveDate),
+    ) -> Result<Option<Drift>, String> {
+        let Some(submitted) = self.latest_sent(period).await? else {
+            return Ok(None);
+        };
+        let pages = notion::fetch_period_data(&self.notion_client, &self.config.db_id, period)
+            .await
+            .map_err(|e| format!("Error loading timesheet data: {}", e))?
+            .results;
+        let late_edits = late_edit::late_edits(&pages, submitted.rendered_at);
+        let mut current = self
+            .timesheet_from_pages(pages, period)
+            .map_err(|e| e.to_string())?;
+        if current.employee.is_none() {
+            current.employee = submitted.timesheet.employee.clone();
+        }
+
+        let changes = amendment::diff(&submitted.timesheet, &current);
+        Ok(Some(Drift {
+            changes: (!changes.is_empty())
+                .then(|| amendment::changes_text(&changes, &submitted.timesheet, &current)),
+            submission_id: submitted.id,
+            period,
+            submitted_at: submitted.submitted_at,
+            late_edits,
+        }))
+    }
+
+    /// Submitted periods that changed in Notion since, among those
+    /// [`LateEditConfig`] watches, newest first
+    pub async fn late_edit_status(&self) -> Result<Vec<Drift>, String> {
+        let config = self
+            .config
+            .late_edits
+            .as_ref()
+            .ok_or("Late edit checks are not enabled".to_string())?;
+
+        let mut drift = Vec::new();
+        for period in config.periods(PayPeriod::current()) {
+            if let Some(found) = self
+                .check_late_edits(period.into())
+                .await?
+                .filter(|found| !found.late_edits.is_empty() || found.needs_amendment())
+            {
+                drift.push(found);
+            }
+        }
+        Ok(drift)
+    }
+
+    /// Check the watched periods and email the employee a link to amend
+    /// each one whose entries changed. The same changes are only reported once.
+    pub async fn alert_late_edits(&self) -> Result<Vec<Drift>, String> {
+        let Some(config) = &self.config.late_edits else {
+            return Ok(Vec::new());
+        };
+
+        let drift = self.late_edit_status().await?;
+        for found in drift.iter().filter(|found| found.needs_amendment()) {
+            let key = idempotency::late_edit_key(
+                &found.submission_id,
+                found.changes.as_deref().unwrap_or_default(),
+            );
+            if let Some(email_id) = self.recall(&key) {
+                info!(
+                    "Already reported these changes to submission {}, email ID: {}",
+                    found.submission_id, email_id
+                );
+                continue;
+            }
+
+            let expires_at = Utc::now() + config.link_ttl;
+            let amend_url = config.link(&token::issue(
+                config.secret.as_bytes(),
+                &found.submission_id,
+                late_edit::AMEND,
+                expires_at,
+            ));
+            match email::send_notice(
+                self.mailer.as_ref(),
+                &self.config.email,
+                vec![config.recipient.clone()],
+                &late_edit::alert_subject(&found.period.into()),
+                &late_edit::alert_text(found, &amend_url),
+            )
+            .await
+            {
+                Ok(email_id) => self.remember([&key], &email_id).await,
+                Err(e) => {
+                    self.report_error(&format!("Error sending late edit alert: {}", e))
+                        .await
+                }
+            }
+        }
+        Ok(drift)
+    }
+
+    /// Start checking for late edits in the background, every
+    /// `LateEditConfig.check_every`. Returns `None` when there is no timer
+    /// configured or no archive. Must be called within a Tokio runtime.
+    pub fn start_late_edit_check(&self) -> Option<JoinHandle<()>> {
+        let check_every = self.config.late_edits.as_ref()?.check_every?;
+        if self.archive.is_none() {
+            error!("Late edit checks need a submission archive");
+            return None;
+        }
+        let service = self.clone();
+        info!("Checking for late edits every {:?}", check_every);
+
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(check_every);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                service.run_late_edit_check().await;
+            }
+        }))
+    }
+
+    /// Run [`Self::alert_late_edits`], logging failures
+    async fn run_late_edit_check(&self) {
+        match self.alert_late_edits().await {
+            Ok(drift) => info!("Late edit check found {} changed periods", drift.len()),
+            Err(e) => error!("Failed to check for late edits: {}", e),
+        }
+    }
+
+    /// Record a Resend delivery event against the archived submission for
+    /// its email, and report bounces, complaints and failures through the
+    /// error channel. Returns the updated submission, or `None` for events
+    /// that aren't tracked or emails that aren't timesheets.
+    pub async fn record_delivery(
+        &self,
+        event: &DeliveryEvent,
+    ) -> Result<Option<Submission>, String> {
+        if self.archive.is_none() {
+            return Err("The submission archive is not enabled".to_string());
+        }
+        let Some(status) = event.status() else {
+            info!("Ignoring {} event for email {}", event.event_type, event.data.email_id);
+            return Ok(None);
+        };
+
+        let delivery_updates = self.delivery_updates.clone();
+        let email_id = event.data.email_id.clone();
+        let created_at = event.created_at;
+        let recorded = self
+            .in_archive(move |archive| {
+                let _updating = delivery_updates
+                    .lock()
+                    .map_err(|_| "Delivery update lock poisoned".to_string())?;
+                let Some(mut submission) = archive.find_by_email_id(&email_id)? else {
+                    info!("Email {} is not an archived timesheet", email_id);
+                    return Ok(None);
+                };
+                if !submission.record_delivery(status, created_at) {
+                    info!(
+                        "Submission {} is already {}, ignoring {} event",
+                        submission.id,
+                        submission.delivery.map(|status| status.as_str()).unwrap_or_default(),
+                        status
+                    );
+                    return Ok(Some((submission, false)));
+                }
+                archive.save(&submission)?;
+                Ok(Some((submission, true)))
+            })
+            .await?;
+        let submission = match recorded {
+            Some((submission, true)) => submission,
+            Some((submission, false)) => return Ok(Some(submission)),
+            None => return Ok(None),
+        };
+        info!("Email for submission {} {}", submission.id, status.description());
+
+        if status.is_problem() {
+            self.report_error(&delivery::problem_report(&submission, status, event))
+                .await;
+        }
+        Ok(Some(submission))
+    }
+
+    /// Archived submissions, newest first
+    pub async fn submissions(&self) -> Result<Vec<Submission>, String> {
+        self.in_archive(|archive| archive.list()).await
+    }
+
+    pub async fn submission(&self, id: &str) -> Result<Option<Submission>, String> {
+        let id = id.to_string();
+        self.in_archive(move |archive| archive.get(&id)).await
+    }
+
+    /// The PDF exactly as it was sent with a submission
+    pub async fn submission_pdf(&self, submission: &Submission) -> Result<Vec<u8>, String> {
+        let sha256 = submission.pdf_sha256.clone();
+        self.in_archive(move |archive| archive.pdf(&sha256)).await
+    }
+
+    /// Whether a submission can be resent: only the latest version payroll
+    /// received for its period can be
+    pub async fn check_resendable(&self, submission: &Submission) -> Result<(), String> {
+        let period = submission
+            .period()
+            .ok_or(format!("Submission {} has no pay period", submission.id))?;
+        match self.latest_sent(period).await? {
+            Some(latest) if latest.id == submission.id => Ok(()),
+            Some(latest) => Err(format!(
+                "Only the latest version payroll received can be resent, which is submission {}",
+                latest.id
+            )),
+            None => Err(format!(
+                "Submission {} is {}, only sent timesheets can be resent",
+                submission.id, submission.status
+            )),
+        }
+    }
+
+    /// Email an archived submission's PDF to the official recipients again,
+    /// with the email and spreadsheets rebuilt from its entries. Only the
+    /// latest submission payroll received for a period can be resent. The
+    /// resend is archived as a new submission.
+    pub async fn resend_submission(&self, submission: &Submission) -> Result<Submission, String> {
+        let period = submission
+            .period()
+            .ok_or(format!("Submission {} has no pay period", submission.id))?;
+        let _period_guard = self.lock_period(period)?;
+        self.check_resendable(submission).await?;
+
+        let pdf = self.submission_pdf(submission).await?;
+        let notion_url = notion::utils::database_url(&self.config.db_id);
+        let timesheet_email = self
+            .config
+            .email
+            .render(&submission.timesheet, &notion_url)?;
+        let attachments = self
+            .export_attachments(&submission.timesheet, &timesheet_email)
+            .await?;
+        let timesheet = RenderedTimesheet {
+            data: submission.timesheet.clone(),
+            email: match &submission.revision {
+                Some(revision) => amendment::amended_email(&timesheet_email, revision),
+                None => timesheet_email,
+            },
+            pdf,
+            attachments,
+            revision: submission.revision.clone(),
+            rendered_at: submission.rendered_at,
+        };
+
+        let result = email::send_timesheet_email(
+            self.mailer.as_ref(),
+            &self.config.email,
+            &timesheet.email,
+            timesheet.pdf.clone(),
+            timesheet.attachments.clone(),
+        )
+        .await;
+        let mut resent = Submission::new(
+            Uuid::new_v4().to_string(),
+            &timesheet,
+            self.config.email.recipients(),
+            &result,
+            SubmissionStatus::Sent,
+        );
+        resent.resend_of = Some(submission.id.clone());
+        self.archive(resent.clone(), &timesheet.pdf).await;
+
+        result.map(|email_id| {
+            info!("Resent submission {}, email ID: {}", submission.id, email_id);
+            resent
+        })
+    }
 }
 
 // Route handlers
@@ -120,7 +1415,22 @@ async fn timesheet_webhook(
         return "not the automation you are looking for".to_string();
     }
 
-    match service.process_timesheet().await {
+    info!(
+        "Webhook event {:?}, delivery attempt {:?}",
+        payload.source.event_id, payload.source.attempt
+    );
+
+    let result = service
+        .process_timesheet_event(payload.source.event_id.as_deref())
+        .await;
+
+    // The edit may have been to a period that was already submitted
+    if service.config.late_edits.is_some() {
+        let service = service.clone();
+        tokio::spawn(async move { service.run_late_edit_check().await });
+    }
+
+    match result {
         Ok(email_id) => {
             info!("Timesheet processed successfully, email ID: {}", email_id);
             email_id
@@ -158,3 +1468,796 @@ async fn timesheet_db_info(State(service): State<Arc<TimesheetService>>) -> Stri
         }
     }
 }
+
+async fn timesheet_template_info(
+    State(service): State<Arc<TimesheetService>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(error) = authorize(&service, &headers) {
+        return error.into_response();
+    }
+    let template_path = service.config.pdf.mapping.template_path.clone();
+    info!("Inspecting template fields for: {}", template_path);
+
+    match service
+        .run_blocking(move || inspect_template(template_path))
+        .await
+    {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => {
+            error!("Failed to inspect template: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Error inspecting template: {}", e),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Download the current pay period's timesheet as `pdf`, `csv` or `xlsx`
+async fn timesheet_export(
+    State(service): State<Arc<TimesheetService>>,
+    headers: HeaderMap,
+    Path(format): Path<String>,
+) -> Response {
+    if let Err(error) = authorize(&service, &headers) {
+        return error.into_response();
+    }
+    info!("Exporting timesheet as {}", format);
+
+    let export_format = match format.as_str() {
+        "pdf" => None,
+        other => match other.parse::<ExportFormat>() {
+            Ok(export_format) => Some(export_format),
+            Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+        },
+    };
+
+    let timesheet_data = match service.fetch_timesheet_data().await {
+        Ok(timesheet_data) => timesheet_data,
+        Err(e) => {
+            error!("Failed to load timesheet data for export: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Error loading timesheet data: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let extension = export_format.map_or("pdf", |export_format| export_format.extension());
+    let filename = service.download_name(&timesheet_data, extension);
+
+    let (bytes, content_type) = match export_format {
+        Some(export_format) => (
+            service.export(timesheet_data, export_format).await,
+            export_format.content_type(),
+        ),
+        None => (service.render_pdf(timesheet_data).await, "application/pdf"),
+    };
+
+    match bytes {
+        Ok(bytes) => (
+            [
+                (header::CONTENT_TYPE, content_type.to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", filename),
+                ),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to export timesheet as {}: {}", format, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Error exporting timesheet: {}", e),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Render a timesheet PDF from JSON entries instead of the Notion database.
+/// The employee name defaults to the configured one. The PDF carries the
+/// employee's signature, so this needs the admin token.
+async fn timesheet_render(
+    State(service): State<Arc<TimesheetService>>,
+    headers: HeaderMap,
+    Json(mut timesheet_data): Json<TimesheetData>,
+) -> Response {
+    if let Err(error) = authorize(&service, &headers) {
+        return error.into_response();
+    }
+    info!(
+        "Rendering timesheet from JSON with {} entries",
+        timesheet_data.entries.len()
+    );
+
+    if timesheet_data.employee.is_none() {
+        timesheet_data.employee = service.config.employee_name.clone();
+    }
+
+    let filename = service.download_name(&timesheet_data, "pdf");
+
+    match service.render_pdf(timesheet_data).await {
+        Ok(timesheet_pdf) => (
+            [
+                (header::CONTENT_TYPE, "application/pdf".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", filename),
+                ),
+            ],
+            timesheet_pdf,
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to render timesheet from JSON: {}", e);
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Error creating timesheet PDF: {}", e),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenParams {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct ApprovalForm {
+    token: String,
+    #[serde(default)]
+    comment: String,
+}
+
+/// Check an approval link's token, returning the approval ID and action
+fn verify_approval_token(
+    service: &TimesheetService,
+    token: &str,
+) -> Result<(String, String), (StatusCode, String)> {
+    let Some(approval) = &service.config.approval else {
+        return Err((StatusCode::NOT_FOUND, "Approval is not enabled".to_string()));
+    };
+
+    verify_link(
+        &approval.secret,
+        token,
+        &[approval::APPROVE, approval::REJECT],
+        "approval",
+    )
+}
+
+/// Check a preview's confirm link, returning the preview ID
+fn verify_preview_token(
+    service: &TimesheetService,
+    token: &str,
+) -> Result<String, (StatusCode, String)> {
+    let Some(preview) = &service.config.preview else {
+        return Err((StatusCode::NOT_FOUND, "Previews are not enabled".to_string()));
+    };
+
+    verify_link(&preview.secret, token, &[preview::CONFIRM], "confirming a preview")
+        .map(|(preview_id, _)| preview_id)
+}
+
+/// Check an amend link's token, returning the submission ID
+fn verify_amend_token(
+    service: &TimesheetService,
+    token: &str,
+) -> Result<String, (StatusCode, String)> {
+    let Some(late_edits) = &service.config.late_edits else {
+        return Err((StatusCode::NOT_FOUND, "Late edit checks are not enabled".to_string()));
+    };
+
+    verify_link(&late_edits.secret, token, &[late_edit::AMEND], "amending a timesheet")
+        .map(|(submission_id, _)| submission_id)
+}
+
+/// Header value as text, or an error naming the missing header
+fn header_value<'a>(
+    headers: &'a HeaderMap,
+    name: &str,
+) -> Result<&'a str, (StatusCode, String)> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .ok_or((StatusCode::BAD_REQUEST, format!("Missing {} header", name)))
+}
+
+/// Check a Resend webhook delivery's Svix signature
+fn verify_delivery(
+    service: &TimesheetService,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<(), (StatusCode, String)> {
+    let Some(config) = &service.config.delivery else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            "Delivery tracking is not enabled".to_string(),
+        ));
+    };
+
+    config
+        .verify(
+            header_value(headers, "svix-id")?,
+            header_value(headers, "svix-timestamp")?,
+            header_value(headers, "svix-signature")?,
+            body,
+        )
+        .map_err(|e| {
+            info!("Rejected delivery webhook: {}", e);
+            (StatusCode::UNAUTHORIZED, e)
+        })
+}
+
+/// Check a signed link's token and that it is for one of `purposes`,
+/// returning its subject and purpose
+fn verify_link(
+    secret: &str,
+    token: &str,
+    purposes: &[&str],
+    kind: &str,
+) -> Result<(String, String), (StatusCode, String)> {
+    match token::verify(secret.as_bytes(), token) {
+        Ok(verified) if purposes.contains(&verified.purpose.as_str()) => {
+            Ok((verified.subject, verified.purpose))
+        }
+        Ok(_) => Err((
+            StatusCode::FORBIDDEN,
+            format!("This link can't be used for {}.", kind),
+        )),
+        Err(e) => {
+            info!("Rejected link for {}: {}", kind, e);
+            Err((StatusCode::FORBIDDEN, e))
+        }
+    }
+}
+
+fn approval_page(status: StatusCode, heading: &str, message: &str) -> Response {
+    let content = format!("<p>{}</p>", approval::escape_html(message));
+    (status, Html(approval::page(heading, &content))).into_response()
+}
+
+/// Show the supervisor a form to confirm the decision in their link
+async fn timesheet_approval_page(
+    State(service): State<Arc<TimesheetService>>,
+    Query(query): Query<TokenParams>,
+) -> Response {
+    let (approval_id, action) = match verify_approval_token(&service, &query.token) {
+        Ok(approval) => approval,
+        Err((status, message)) => return approval_page(status, "Invalid link", &message),
+    };
+
+    match service.pending_approval(&approval_id) {
+        Some(pending) => Html(approval::confirm_page(
+            &query.token,
+            &action,
+            &pending.timesheet.email.subject,
+        ))
+        .into_response(),
+        None => approval_page(
+            StatusCode::GONE,
+            "Already handled",
+            "This timesheet has already been approved or rejected, or the service was restarted.",
+        ),
+    }
+}
+
+/// Approve or reject a timesheet from the confirmation form
+async fn timesheet_approval_decision(
+    State(service): State<Arc<TimesheetService>>,
+    Form(form): Form<ApprovalForm>,
+) -> Response {
+    let (approval_id, action) = match verify_approval_token(&service, &form.token) {
+        Ok(approval) => approval,
+        Err((status, message)) => return approval_page(status, "Invalid link", &message),
+    };
+    info!("Received {} decision for approval {}", action, approval_id);
+
+    match service.decide_approval(&approval_id, &action, &form.comment).await {
+        Ok(message) => approval_page(StatusCode::OK, "Thank you", &message),
+        Err(e) => approval_page(StatusCode::CONFLICT, "Could not complete", &e),
+    }
+}
+
+/// Show the employee a form to confirm sending their previewed timesheet
+async fn timesheet_confirm_page(
+    State(service): State<Arc<TimesheetService>>,
+    Query(query): Query<TokenParams>,
+) -> Response {
+    let preview_id = match verify_preview_token(&service, &query.token) {
+        Ok(preview_id) => preview_id,
+        Err((status, message)) => return approval_page(status, "Invalid link", &message),
+    };
+
+    match service.pending_preview(&preview_id) {
+        Some(pending) => {
+            Html(preview::confirm_page(&query.token, &pending.timesheet.email.subject))
+                .into_response()
+        }
+        None => approval_page(
+            StatusCode::GONE,
+            "Already handled",
+            "This preview was already sent or replaced, or the service was restarted.",
+        ),
+    }
+}
+
+/// Send a previewed timesheet from the confirmation form
+async fn timesheet_confirm(
+    State(service): State<Arc<TimesheetService>>,
+    Form(form): Form<TokenParams>,
+) -> Response {
+    let preview_id = match verify_preview_token(&service, &form.token) {
+        Ok(preview_id) => preview_id,
+        Err((status, message)) => return approval_page(status, "Invalid link", &message),
+    };
+    info!("Received confirmation for preview {}", preview_id);
+
+    match service.confirm_preview(&preview_id).await {
+        Ok(message) => approval_page(StatusCode::OK, "Thank you", &message),
+        Err(e) => approval_page(StatusCode::CONFLICT, "Could not complete", &e),
+    }
+}
+
+/// Check the request carries `Authorization: Bearer <admin_token>`. The
+/// endpoints that need it are turned off when no token is configured.
+fn authorize(service: &TimesheetService, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    let Some(admin_token) = &service.config.admin_token else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            "This endpoint needs an admin token to be configured".to_string(),
+        ));
+    };
+
+    let given = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    // Compare digests so the time taken doesn't hint at the token
+    if Sha256::digest(given.as_bytes()) == Sha256::digest(admin_token.as_bytes()) {
+        Ok(())
+    } else {
+        Err((StatusCode::UNAUTHORIZED, "Missing or wrong admin token".to_string()))
+    }
+}
+
+/// Look up an archived submission, with the status to answer when there
+/// isn't one
+async fn submission_lookup(
+    service: &TimesheetService,
+    id: &str,
+) -> Result<Submission, (StatusCode, String)> {
+    if service.archive.is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            "The submission archive is not enabled".to_string(),
+        ));
+    }
+
+    match service.submission(id).await {
+        Ok(Some(submission)) => Ok(submission),
+        Ok(None) => Err((StatusCode::NOT_FOUND, format!("No submission {}", id))),
+        Err(e) => {
+            error!("Failed to load submission {}: {}", id, e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SubmissionsQuery {
+    /// Any day in a pay period, to list only that period's submissions
+    period: Option<String>,
+}
+
+/// The pay period containing a `YYYY-MM-DD` date
+fn period_from_date(date: &str) -> Result<(NaiveDate, NaiveDate), (StatusCode, String)> {
+    date.parse::<NaiveDate>()
+        .map(|date| PayPeriod::containing(date).into())
+        .map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid date '{}', expected YYYY-MM-DD: {}", date, e),
+            )
+        })
+}
+
+/// List archived submissions as JSON, newest first. With `?period=<date>`,
+/// only that pay period's, which is its revision history.
+async fn timesheet_submissions(
+    State(service): State<Arc<TimesheetService>>,
+    headers: HeaderMap,
+    Query(query): Query<SubmissionsQuery>,
+) -> Response {
+    if let Err(error) = authorize(&service, &headers) {
+        return error.into_response();
+    }
+    if service.archive.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            "The submission archive is not enabled",
+        )
+            .into_response();
+    }
+
+    let period = match query.period.as_deref().map(period_from_date).transpose() {
+        Ok(period) => period,
+        Err(error) => return error.into_response(),
+    };
+
+    match service.submissions().await {
+        Ok(mut submissions) => {
+            if let Some(period) = period {
+                submissions.retain(|submission| submission.period() == Some(period));
+            }
+            Json(submissions).into_response()
+        }
+        Err(e) => {
+            error!("Failed to list submissions: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e).into_response()
+        }
+    }
+}
+
+/// Download the PDF exactly as a submission sent it
+async fn timesheet_submission_pdf(
+    State(service): State<Arc<TimesheetService>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(error) = authorize(&service, &headers) {
+        return error.into_response();
+    }
+    let submission = match submission_lookup(&service, &id).await {
+        Ok(submission) => submission,
+        Err(error) => return error.into_response(),
+    };
+    let filename = service.download_name(&submission.timesheet, "pdf");
+
+    match service.submission_pdf(&submission).await {
+        Ok(pdf) => (
+            [
+                (header::CONTENT_TYPE, "application/pdf".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", filename),
+                ),
+            ],
+            pdf,
+        )
+            .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+/// Email a submission's PDF to the official recipients again
+async fn timesheet_submission_resend(
+    State(service): State<Arc<TimesheetService>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(error) = authorize(&service, &headers) {
+        return error.into_response();
+    }
+    let submission = match submission_lookup(&service, &id).await {
+        Ok(submission) => submission,
+        Err(error) => return error.into_response(),
+    };
+    if let Err(e) = service.check_resendable(&submission).await {
+        info!("Not resending submission {}: {}", id, e);
+        return (StatusCode::CONFLICT, e).into_response();
+    }
+    info!("Resending submission {}", id);
+
+    match service.resend_submission(&submission).await {
+        Ok(resent) => Json(resent).into_response(),
+        Err(e) => {
+            error!("Failed to resend submission {}: {}", id, e);
+            (
+                StatusCode::BAD_GATEWAY,
+                format!("Error resending submission: {}", e),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Show what changed in a pay period since it was last sent, as plain text
+async fn timesheet_amendment_diff(
+    State(service): State<Arc<TimesheetService>>,
+    headers: HeaderMap,
+    Path(date): Path<String>,
+) -> Response {
+    if let Err(error) = authorize(&service, &headers) {
+        return error.into_response();
+    }
+
+    let period = match period_from_date(&date) {
+        Ok(period) => period,
+        Err(error) => return error.into_response(),
+    };
+
+    match service.compare_with_submitted(period).await {
+        Ok((submitted, _, changes)) if changes.is_empty() => format!(
+            "No changes since submission {} on {}",
+            submitted.id,
+            submitted.submitted_at.format("%b %-d, %Y")
+        )
+        .into_response(),
+        Ok((submitted, current, changes)) => format!(
+            "Changes since submission {} on {}:\n\n{}",
+            submitted.id,
+            submitted.submitted_at.format("%b %-d, %Y"),
+            amendment::changes_text(&changes, &submitted.timesheet, &current)
+        )
+        .into_response(),
+        Err(e) => {
+            error!("Failed to compare {} to {}: {}", period.0, period.1, e);
+            (StatusCode::NOT_FOUND, e).into_response()
+        }
+    }
+}
+
+/// Submitted pay periods that changed in Notion since, as JSON
+async fn timesheet_late_edits(
+    State(service): State<Arc<TimesheetService>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(error) = authorize(&service, &headers) {
+        return error.into_response();
+    }
+    if service.config.late_edits.is_none() || service.archive.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            "Late edit checks and the submission archive must be enabled",
+        )
+            .into_response();
+    }
+
+    match service.late_edit_status().await {
+        Ok(drift) => Json(drift).into_response(),
+        Err(e) => {
+            error!("Failed to check for late edits: {}", e);
+            (StatusCode::BAD_GATEWAY, e).into_response()
+        }
+    }
+}
+
+/// Show the employee the changes in their amend link and a form to send them
+async fn timesheet_amend_page(
+    State(service): State<Arc<TimesheetService>>,
+    Query(query): Query<TokenParams>,
+) -> Response {
+    let submission_id = match verify_amend_token(&service, &query.token) {
+        Ok(submission_id) => submission_id,
+        Err((status, message)) => return approval_page(status, "Invalid link", &message),
+    };
+    let submission = match submission_lookup(&service, &submission_id).await {
+        Ok(submission) => submission,
+        Err((status, message)) => return approval_page(status, "Invalid link", &message),
+    };
+    let Some(period) = submission.period() else {
+        return approval_page(
+            StatusCode::NOT_FOUND,
+            "Invalid link",
+            "This submission has no pay period.",
+        );
+    };
+
+    match service.check_late_edits(period).await {
+        Ok(Some(drift)) if drift.submission_id != submission.id => approval_page(
+            StatusCode::GONE,
+            "Already handled",
+            "This timesheet has already been amended.",
+        ),
+        Ok(Some(Drift {
+            changes: Some(changes),
+            ..
+        })) => Html(late_edit::amend_page(&query.token, &submission.subject, &changes))
+            .into_response(),
+        Ok(_) => approval_page(
+            StatusCode::OK,
+            "Nothing to amend",
+            "The timesheet in Notion matches what was sent.",
+        ),
+        Err(e) => approval_page(StatusCode::BAD_GATEWAY, "Could not load changes", &e),
+    }
+}
+
+/// Send an amended timesheet from the confirmation form
+async fn timesheet_amend(
+    State(service): State<Arc<TimesheetService>>,
+    Form(form): Form<TokenParams>,
+) -> Response {
+    let submission_id = match verify_amend_token(&service, &form.token) {
+        Ok(submission_id) => submission_id,
+        Err((status, message)) => return approval_page(status, "Invalid link", &message),
+    };
+    info!("Received amend confirmation for submission {}", submission_id);
+
+    match service.amend_submission(&submission_id).await {
+        Ok(message) => approval_page(StatusCode::OK, "Thank you", &message),
+        Err(e) => approval_page(StatusCode::CONFLICT, "Could not complete", &e),
+    }
+}
+
+/// Receive Resend's delivery events and record them on the submissions
+async fn timesheet_delivery_webhook(
+    State(service): State<Arc<TimesheetService>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if let Err(error) = verify_delivery(&service, &headers, &body) {
+        return error.into_response();
+    }
+    let event: DeliveryEvent = match serde_json::from_slice(&body) {
+        Ok(event) => event,
+        Err(e) => {
+            error!("Failed to parse delivery event: {}", e);
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid delivery event: {}", e),
+            )
+                .into_response();
+        }
+    };
+    info!(
+        "Received {} event for email {}",
+        event.event_type, event.data.email_id
+    );
+
+    // Errors ask Resend to deliver the event again later
+    match service.record_delivery(&event).await {
+        Ok(Some(submission)) => {
+            format!("Recorded for submission {}", submission.id).into_response()
+        }
+        Ok(None) => "Ignored".into_response(),
+        Err(e) => {
+            error!("Failed to record delivery event: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::{email::testing::RecordingMailer, pdf::TimesheetEntry};
+    use chrono::{Datelike, Duration};
+
+    fn preview_service(mailer: &RecordingMailer) -> TimesheetService {
+        let config = TimesheetConfig {
+            db_id: "db".to_string(),
+            automation_id: "automation".to_string(),
+            employee_name: None,
+            pdf: PdfConfig::default(),
+            attachments: Vec::new(),
+            max_concurrent_renders: 1,
+            email: EmailConfig::new("timesheets@example.com", vec!["payroll@ufv.ca".to_string()]),
+            approval: None,
+            preview: Some(PreviewConfig::new(
+                "ada@student.ufv.ca",
+                "https://example.com/timesheet",
+                "secret",
+            )),
+            schedule: None,
+            late_edits: None,
+            delivery: None,
+            admin_token: None,
+        };
+        TimesheetService::new(Client::new(), mailer.clone(), config)
+    }
+
+    /// A pay period that is still open, so its previews haven't expired
+    fn open_period() -> (NaiveDate, NaiveDate) {
+        let today = Local::now().date_naive();
+        (today - Duration::days(7), today + Duration::days(7))
+    }
+
+    fn rendered(service: &TimesheetService, period: (NaiveDate, NaiveDate)) -> RenderedTimesheet {
+        let mut data = TimesheetData::try_from(vec![TimesheetEntry {
+            month: period.0.month(),
+            day: period.0.day(),
+            start: "9:00 AM".to_string(),
+            end: "12:00 PM".to_string(),
+            paid_hours: 3.0,
+            notes: None,
+        }])
+        .unwrap();
+        data.period = Some(period);
+        RenderedTimesheet {
+            email: service.config.email.render(&data, "").unwrap(),
+            data,
+            pdf: b"%PDF-1.5".to_vec(),
+            attachments: Vec::new(),
+            revision: None,
+            rendered_at: Utc::now(),
+        }
+    }
+
+    /// The token in the confirm link of a preview email
+    fn confirm_token(message: &email::EmailMessage) -> String {
+        let (_, rest) = message.text.split_once("?token=").unwrap();
+        rest.split_whitespace().next().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn confirming_a_preview_twice_sends_it_once() {
+        let mailer = RecordingMailer::default();
+        let service = preview_service(&mailer);
+        let preview = service.config.preview.clone().unwrap();
+        let period = open_period();
+
+        let timesheet = rendered(&service, period);
+        service
+            .request_preview(&preview, period, timesheet, Vec::new())
+            .await
+            .unwrap();
+        let preview_email = mailer.last();
+        assert_eq!(preview_email.to, ["ada@student.ufv.ca"]);
+        assert!(preview_email.subject.starts_with("Preview: "));
+
+        let preview_id = verify_preview_token(&service, &confirm_token(&preview_email)).unwrap();
+        let (first, second) = tokio::join!(
+            service.confirm_preview(&preview_id),
+            service.confirm_preview(&preview_id)
+        );
+        assert_eq!(first.unwrap(), "Confirmed. The timesheet has been sent.");
+        assert_eq!(
+            second.unwrap_err(),
+            "This preview has already been sent, was replaced, or has expired"
+        );
+        assert!(service.confirm_preview(&preview_id).await.is_err());
+
+        let sent = mailer.sent();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[1].to, ["payroll@ufv.ca"]);
+        assert_eq!(sent[1].attachments[0].content, b"%PDF-1.5");
+    }
+
+    #[tokio::test]
+    async fn expired_links_and_previews_cannot_be_confirmed() {
+        let mailer = RecordingMailer::default();
+        let service = preview_service(&mailer);
+        let preview = service.config.preview.clone().unwrap();
+        let period = open_period();
+
+        service
+            .request_preview(&preview, period, rendered(&service, period), Vec::new())
+            .await
+            .unwrap();
+        let preview_id = verify_preview_token(&service, &confirm_token(&mailer.last())).unwrap();
+
+        let expired = token::issue(
+            preview.secret.as_bytes(),
+            &preview_id,
+            preview::CONFIRM,
+            Utc::now() - Duration::minutes(1),
+        );
+        assert_eq!(
+            verify_preview_token(&service, &expired).unwrap_err(),
+            (StatusCode::FORBIDDEN, "This link has expired".to_string())
+        );
+
+        // A preview held past its expiry is dropped rather than sent
+        service
+            .pending_previews
+            .lock()
+            .unwrap()
+            .get_mut(&preview_id)
+            .unwrap()
+            .expires_at = Utc::now() - Duration::minutes(1);
+        assert!(service.confirm_preview(&preview_id).await.is_err());
+        assert!(service.pending_preview(&preview_id).is_none());
+        assert_eq!(mailer.sent().len(), 1);
+    }
+}